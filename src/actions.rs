@@ -6,10 +6,15 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
+use crate::error::{ErrorKind, ResultExt};
+use crate::guest::GuestSetup;
 use crate::manifest::Manifest;
-use anyhow::{bail, Context, Result};
+use crate::payload::Payload;
+use crate::profile::ProfileSet;
+use anyhow::{anyhow, bail, Context, Result};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -22,7 +27,7 @@ fn snapshot_dataset<T: AsRef<str>>(dataset: T) -> Result<String> {
 
     let mut cmd = Command::new("/sbin/zfs");
     cmd.env_clear();
-    cmd.args(&["snapshot", &snapshot]);
+    cmd.args(["snapshot", &snapshot]);
 
     let zfs = cmd.output().context("failed to run zfs snapshot command")?;
     if !zfs.status.success() {
@@ -30,33 +35,78 @@ fn snapshot_dataset<T: AsRef<str>>(dataset: T) -> Result<String> {
         bail!("zfs snapshot failed: {}", err);
     }
 
-    println!("snapshot created: {}", &snapshot);
+    info!("snapshot created: {}", &snapshot);
     Ok(snapshot)
 }
 
-pub fn destroy_dataset<T: AsRef<str>>(dataset: T) {
+/*
+ * Where the image root lives while it is being built. Normally this is a zfs
+ * dataset, but dry runs extract into a scratch directory that is never
+ * packaged.
+ */
+pub enum Workspace {
+    Dataset(String),
+    Scratch(PathBuf),
+}
+
+impl Workspace {
+    pub fn destroy(&self) -> Result<()> {
+        match self {
+            Self::Dataset(ds) => destroy_dataset(ds),
+            Self::Scratch(dir) => {
+                fs::remove_dir_all(dir)
+                    .with_context(|| format!("failed to remove {}", dir.display()))
+                    .kind(ErrorKind::Output)?;
+                info!("removed scratch directory {}", dir.display());
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn create_scratch<T: AsRef<str>>(name: T) -> Result<(Workspace, PathBuf)> {
+    let dir = std::env::temp_dir().join(format!("smartos-lx-img-builder-{}", name.as_ref()));
+    let zroot = dir.join("root");
+
+    fs::create_dir_all(&zroot)
+        .with_context(|| format!("failed to create {}", zroot.display()))
+        .kind(ErrorKind::Output)?;
+    info!("created scratch zroot {}", zroot.display());
+
+    Ok((Workspace::Scratch(dir), zroot))
+}
+
+pub fn destroy_dataset<T: AsRef<str>>(dataset: T) -> Result<()> {
     let dataset = dataset.as_ref();
 
     let mut cmd = Command::new("/sbin/zfs");
     cmd.env_clear();
-    cmd.args(&["destroy", "-r", dataset]);
+    cmd.args(["destroy", "-r", dataset]);
 
-    let zfs = cmd.output().expect("failed to run zfs destroy command");
+    let zfs = cmd
+        .output()
+        .context("failed to run zfs destroy command")
+        .kind(ErrorKind::Zfs)?;
     if !zfs.status.success() {
         let err = String::from_utf8_lossy(&zfs.stderr);
-        eprintln!("Oops! Looks like manual cleanup will be required");
-        panic!("zfs destroy failed: {}", err);
+        error!("Oops! Looks like manual cleanup will be required");
+        return Err(anyhow!("zfs destroy failed: {}", err)).kind(ErrorKind::Zfs);
     }
 
-    println!("destroyed dataset {}", &dataset);
+    info!("destroyed dataset {}", &dataset);
+    Ok(())
 }
 
 pub fn create_dataset<T: AsRef<str>>(dataset: T) -> Result<PathBuf> {
+    create_dataset_impl(dataset).kind(ErrorKind::Zfs)
+}
+
+fn create_dataset_impl<T: AsRef<str>>(dataset: T) -> Result<PathBuf> {
     let dataset = dataset.as_ref();
 
     let mut cmd = Command::new("/sbin/zfs");
     cmd.env_clear();
-    cmd.args(&["create", dataset]);
+    cmd.args(["create", dataset]);
 
     let zfs = cmd.output()?;
     if !zfs.status.success() {
@@ -64,11 +114,11 @@ pub fn create_dataset<T: AsRef<str>>(dataset: T) -> Result<PathBuf> {
         bail!("zfs create failed: {}", err);
     }
 
-    println!("created dataset {}", &dataset);
+    info!("created dataset {}", &dataset);
 
     let mut mp_cmd = Command::new("/sbin/zfs");
     mp_cmd.env_clear();
-    mp_cmd.args(&["get", "-Ho", "value", "mountpoint", dataset]);
+    mp_cmd.args(["get", "-Ho", "value", "mountpoint", dataset]);
     let mp = mp_cmd.output()?;
     if !mp.status.success() {
         let err = String::from_utf8_lossy(&mp.stderr);
@@ -84,86 +134,124 @@ pub fn create_dataset<T: AsRef<str>>(dataset: T) -> Result<PathBuf> {
     let zroot: PathBuf = [mountpoint.trim(), "root"].iter().collect();
     mkdirp(&zroot, 0, 0, 0o755).context("failed to create zroot")?;
 
-    println!("created zroot {}", &zroot.display());
+    info!("created zroot {}", &zroot.display());
     Ok(zroot)
 }
 
-pub fn install_tar<P: AsRef<Path>, T: AsRef<Path>>(zroot: P, file: T) -> Result<()> {
-    let zroot = zroot.as_ref();
+/*
+ * Determine the gtar options needed to extract the input file, along with a
+ * description of the input format.
+ */
+pub fn tar_options<T: AsRef<Path>>(file: T) -> Result<(String, &'static str)> {
     let file = file.as_ref();
 
+    if !file.is_file() {
+        return Err(anyhow!("tar file {} does not exist", file.display())).kind(ErrorKind::Input);
+    }
+
     let mut file_ext = file
         .extension()
-        .context("tar file doesn't have an extension")?
+        .context("tar file doesn't have an extension")
+        .kind(ErrorKind::Input)?
         .to_str()
-        .ok_or_else(|| anyhow::anyhow!("invalid utf8 characters"))?
+        .ok_or_else(|| anyhow!("invalid utf8 characters"))
+        .kind(ErrorKind::Input)?
         .to_string();
     file_ext.make_ascii_lowercase();
 
     let mut gtaropts = String::from("-");
-    match file_ext.as_str() {
-        "gzip" => gtaropts.push_str("xz"),
-        "bzip2" => gtaropts.push_str("xj"),
-        "compressed" => gtaropts.push_str("xz"),
-        "ustar" => gtaropts.push('x'),
-        "xz" => gtaropts.push_str("xJ"),
-        "tar" => gtaropts.push_str("x"),
-        _ => bail!("unknown tar extension \"{}\"", file_ext),
+    let format = match file_ext.as_str() {
+        "gzip" => {
+            gtaropts.push_str("xz");
+            "gzip compressed tar"
+        }
+        "bzip2" => {
+            gtaropts.push_str("xj");
+            "bzip2 compressed tar"
+        }
+        "compressed" => {
+            gtaropts.push_str("xz");
+            "compressed tar"
+        }
+        "ustar" => {
+            gtaropts.push('x');
+            "ustar"
+        }
+        "xz" => {
+            gtaropts.push_str("xJ");
+            "xz compressed tar"
+        }
+        "tar" => {
+            gtaropts.push('x');
+            "tar"
+        }
+        _ => return Err(anyhow!("unknown tar extension \"{}\"", file_ext)).kind(ErrorKind::Input),
     };
     gtaropts.push('f');
 
+    Ok((gtaropts, format))
+}
+
+pub fn install_tar<P: AsRef<Path>, T: AsRef<Path>>(zroot: P, file: T) -> Result<()> {
+    let zroot = zroot.as_ref();
+    let file = file.as_ref();
+
+    let (gtaropts, format) = tar_options(file)?;
+    debug!("extracting {} as {}", file.display(), format);
+
     let mut cmd = Command::new("/usr/bin/gtar");
     cmd.env_clear();
-    cmd.args(&[
+    cmd.args([
         &gtaropts,
         file.to_str().unwrap(),
         "-C",
         zroot.to_str().unwrap(),
     ]);
 
-    let tar = cmd.output()?;
+    let tar = cmd
+        .output()
+        .context("failed to run gtar")
+        .kind(ErrorKind::Extract)?;
     if !tar.status.success() {
         let err = String::from_utf8_lossy(&tar.stderr);
-        bail!("untar failed: {}", err);
+        return Err(anyhow!("untar failed: {}", err)).kind(ErrorKind::Extract);
     }
 
-    println!("extracted {} into {}", file.display(), zroot.display());
+    info!("extracted {} into {}", file.display(), zroot.display());
 
     Ok(())
 }
 
+// XXX these probably are not needed but historically they have been created
+pub const NATIVE_DIRS: &[&str] = &[
+    "native/dev",
+    "native/etc/default",
+    "native/etc/svc/volatile",
+    "native/lib",
+    "native/proc",
+    "native/tmp",
+    "native/usr",
+    "native/var",
+];
+
 pub fn modify_image<P: AsRef<Path>>(zroot: P, product: &str, motd: &str) -> Result<()> {
     let zroot = zroot.as_ref();
 
-    // XXX these probably are not needed but historically they have been created
-    let paths = [
-        "native/dev",
-        "native/etc/default",
-        "native/etc/svc/volatile",
-        "native/lib",
-        "native/proc",
-        "native/tmp",
-        "native/usr",
-        "native/var",
-    ];
-
     /*
      * If the tar was created from a docker image, this file might still be
      * around.
      */
     let unwanted_files = [".dockerenv"];
 
-    for p in &paths {
+    for p in NATIVE_DIRS {
         let to_create = zroot.join(p);
         mkdirp(&to_create, 0, 0, 0o755)?;
     }
 
     for p in &unwanted_files {
         let file = zroot.join(p);
-        if file.exists() {
-            fs::remove_file(&file)
-                .with_context(|| format!("failed to unlink {}", &file.display()))?;
-            println!("unlinked {}", &file.display());
+        if exists(&file) {
+            remove_file(&file)?;
         }
     }
 
@@ -172,19 +260,24 @@ pub fn modify_image<P: AsRef<Path>>(zroot: P, product: &str, motd: &str) -> Resu
 
     let fstab_path = zroot.join("etc/fstab");
     let fstab = include_str!("../files/fstab");
-    create_file_contents(&fstab_path, &fstab)?;
+    create_file_contents(&fstab_path, fstab)?;
 
     let product_path = zroot.join("etc/product");
-    create_file_contents(&product_path, &product)?;
+    create_file_contents(&product_path, product)?;
 
     let motd_path = zroot.join("etc/motd");
-    create_file_contents(&motd_path, &motd)?;
+    create_file_contents(&motd_path, motd)?;
 
     Ok(())
 }
 
-pub fn install_guest_tools<P: AsRef<Path>>(zroot: P) -> Result<()> {
-    crate::guest::install_tools(zroot)
+pub fn install_guest_tools<P: AsRef<Path>>(
+    zroot: P,
+    payload: &Payload,
+    profiles: &ProfileSet,
+    forced: Option<&str>,
+) -> Result<GuestSetup> {
+    crate::guest::install_tools(zroot, payload, profiles, forced).kind(ErrorKind::Guest)
 }
 
 pub fn create_dataset_gzip<T: AsRef<str>, P: AsRef<Path>>(dataset: T, output: P) -> Result<()> {
@@ -195,31 +288,43 @@ pub fn create_dataset_gzip<T: AsRef<str>, P: AsRef<Path>>(dataset: T, output: P)
         .write(true)
         .create(true)
         .truncate(true)
-        .open(&output)
-        .with_context(|| format!("failed to create {}:", &output.display()))?;
+        .open(output)
+        .with_context(|| format!("failed to create {}:", &output.display()))
+        .kind(ErrorKind::Output)?;
 
-    let snapshot = snapshot_dataset(&dataset)?;
+    let snapshot = snapshot_dataset(dataset).kind(ErrorKind::Zfs)?;
     let mut zfs_send = Command::new("/sbin/zfs")
-        .args(&["send", &snapshot])
+        .args(["send", &snapshot])
         .stdout(Stdio::piped())
         .spawn()
-        .context("failed to spawn zfs send")?;
+        .context("failed to spawn zfs send")
+        .kind(ErrorKind::Zfs)?;
     let gzip = Command::new("/usr/bin/gzip")
         .arg("-9")
         .stdin(zfs_send.stdout.take().unwrap())
         .stdout(Stdio::piped())
         .output()
-        .context("failed to run gzip")?;
+        .context("failed to run gzip")
+        .kind(ErrorKind::Output)?;
 
     gz.write_all(&gzip.stdout)
-        .context("failed to write gzip stdout to file")?;
+        .context("failed to write gzip stdout to file")
+        .kind(ErrorKind::Output)?;
 
     if !gzip.status.success() {
         let err = String::from_utf8_lossy(&gzip.stderr);
-        bail!("gzip failed: {}", err);
+        return Err(anyhow!("gzip failed: {}", err)).kind(ErrorKind::Output);
+    }
+
+    let send = zfs_send
+        .wait()
+        .context("failed to wait for zfs send")
+        .kind(ErrorKind::Zfs)?;
+    if !send.success() {
+        return Err(anyhow!("zfs send failed: {}", send)).kind(ErrorKind::Zfs);
     }
 
-    println!("created zfs gzip at {}", &output.display());
+    info!("created zfs gzip at {}", &output.display());
     Ok(())
 }
 
@@ -229,9 +334,11 @@ pub fn create_manifest<P: AsRef<Path>>(manifest: Manifest, output: P) -> Result<
         .write(true)
         .create(true)
         .truncate(true)
-        .open(&output)?;
-    manifest.to_writer(&mut m)?;
+        .open(output)
+        .with_context(|| format!("failed to create {}", &output.display()))
+        .kind(ErrorKind::Output)?;
+    manifest.to_writer(&mut m).kind(ErrorKind::Manifest)?;
 
-    println!("created manifest at {}", &output.display());
+    info!("created manifest at {}", &output.display());
     Ok(())
 }
@@ -0,0 +1,226 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Audit the permissions of the finished image root: setuid and setgid
+ * executables, world-writable files and directories without the sticky bit,
+ * device nodes, and paths owned by uids that the image's /etc/passwd does not
+ * define. Most images legitimately have some of these, such as passwd being
+ * setuid, so findings are compared against the distro profile's allowlist.
+ *
+ * The /native mount points are created by the builder, and are covered by
+ * the platform's own filesystems in a running instance, as are /proc and
+ * /sys. They are not audited.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::collections::{BTreeMap, HashSet};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::actions::NATIVE_DIRS;
+use crate::profile::AuditAllow;
+use crate::utils::*;
+
+pub const CATEGORIES: &[&str] = &[
+    "setuid",
+    "setgid",
+    "world-writable",
+    "device",
+    "unknown-owner",
+];
+
+const SKIP: &[&str] = &["proc", "sys"];
+
+#[derive(Debug)]
+pub struct Finding {
+    pub category: &'static str,
+    pub path: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    /*
+     * The number of findings that the allowlist expected.
+     */
+    pub allowed: u64,
+}
+
+/*
+ * The findings expected in an image, by path and optionally category.
+ */
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    entries: Vec<(Pattern, Option<String>)>,
+}
+
+impl Allowlist {
+    pub fn new(allow: &[AuditAllow]) -> Result<Self> {
+        let mut entries = vec![];
+        for a in allow {
+            if let Some(c) = &a.category {
+                if !CATEGORIES.contains(&c.as_str()) {
+                    return Err(anyhow!(
+                        "unknown audit category \"{}\" for {}, categories are: {}",
+                        c,
+                        a.path,
+                        CATEGORIES.join(", ")
+                    ));
+                }
+            }
+            let pattern = Pattern::new(a.path.trim_start_matches('/'))
+                .with_context(|| format!("invalid audit pattern {}", a.path))?;
+            entries.push((pattern, a.category.clone()));
+        }
+        Ok(Allowlist { entries })
+    }
+
+    fn allows(&self, path: &str, category: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_leading_dot: false,
+            ..MatchOptions::new()
+        };
+        self.entries.iter().any(|(p, c)| {
+            c.as_deref().is_none_or(|c| c == category) && p.matches_with(path, options)
+        })
+    }
+}
+
+/*
+ * The uids defined by the image's /etc/passwd, or None if it has none.
+ */
+fn known_uids(zroot: &Path) -> Result<Option<HashSet<u32>>> {
+    let passwd = match read_in_root(zroot, "etc/passwd")? {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    Ok(Some(
+        passwd
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| l.split(':').nth(2))
+            .filter_map(|id| id.parse().ok())
+            .collect(),
+    ))
+}
+
+fn skipped(rel: &Path) -> bool {
+    SKIP.iter()
+        .chain(NATIVE_DIRS.iter())
+        .any(|s| rel == Path::new(s))
+}
+
+pub fn audit<P: AsRef<Path>>(zroot: P, allow: &Allowlist) -> Result<Report> {
+    let zroot = zroot.as_ref();
+    let uids = known_uids(zroot)?;
+    if uids.is_none() {
+        warn!("the image has no /etc/passwd, not checking file owners");
+    }
+
+    let mut report = Report::default();
+    let mut unknown: BTreeMap<u32, (String, u64)> = BTreeMap::new();
+
+    /*
+     * In a dry run, skip what earlier stages have planned to remove.
+     */
+    let walk = WalkDir::new(zroot)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let rel = e.path().strip_prefix(zroot).unwrap_or(e.path());
+            lexists(e.path()) && !skipped(rel)
+        });
+    for entry in walk {
+        let entry = entry.with_context(|| format!("failed to walk {}", zroot.display()))?;
+        let md = entry
+            .metadata()
+            .with_context(|| format!("failed to stat {}", entry.path().display()))?;
+        let rel = entry
+            .path()
+            .strip_prefix(zroot)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .into_owned();
+        let ft = md.file_type();
+        let mode = md.mode() & 0o7777;
+
+        let mut found = vec![];
+        if ft.is_file() && mode & 0o4000 != 0 {
+            found.push((
+                "setuid",
+                format!("mode {:o}, owned by uid {}", mode, md.uid()),
+            ));
+        }
+        if ft.is_file() && mode & 0o2000 != 0 {
+            found.push(("setgid", format!("mode {:o}, group gid {}", mode, md.gid())));
+        }
+        if ft.is_file() && mode & 0o002 != 0 {
+            found.push(("world-writable", format!("file with mode {:o}", mode)));
+        }
+        if ft.is_dir() && mode & 0o002 != 0 && mode & 0o1000 == 0 {
+            found.push((
+                "world-writable",
+                format!("directory with mode {:o}, without the sticky bit", mode),
+            ));
+        }
+        if ft.is_char_device() || ft.is_block_device() {
+            let kind = if ft.is_char_device() {
+                "character"
+            } else {
+                "block"
+            };
+            found.push(("device", format!("{} device", kind)));
+        }
+
+        for (category, detail) in found {
+            if allow.allows(&rel, category) {
+                report.allowed += 1;
+                continue;
+            }
+            report.findings.push(Finding {
+                category,
+                path: format!("/{}", rel),
+                detail,
+            });
+        }
+
+        /*
+         * Images built without root can have thousands of paths with an
+         * unknown owner, so these are reported once for each uid.
+         */
+        if let Some(uids) = &uids {
+            if !uids.contains(&md.uid()) {
+                if allow.allows(&rel, "unknown-owner") {
+                    report.allowed += 1;
+                } else {
+                    let e = unknown
+                        .entry(md.uid())
+                        .or_insert_with(|| (format!("/{}", rel), 0));
+                    e.1 += 1;
+                }
+            }
+        }
+    }
+
+    for (uid, (path, count)) in unknown {
+        report.findings.push(Finding {
+            category: "unknown-owner",
+            path,
+            detail: format!("uid {} is not in /etc/passwd and owns {} paths", uid, count),
+        });
+    }
+
+    Ok(report)
+}
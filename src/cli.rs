@@ -6,27 +6,30 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
- * Copyright 2023 MNX Cloud, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
+use anyhow::{anyhow, Result};
+use structopt::clap;
 use structopt::StructOpt;
 
+use crate::error::{ErrorKind, ResultExt};
+use crate::owners;
+
+pub const DEFAULT_KERNEL: &str = "5.10.0";
+
 #[derive(Debug, StructOpt)]
 pub struct Opts {
-    #[structopt(
-        name = "tar",
-        long = "tar",
-        short = "t",
-        help = "lx userland tar file",
-        required = true
-    )]
-    pub tar: String,
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+    #[structopt(name = "tar", long = "tar", short = "t", help = "lx userland tar file")]
+    pub tar: Option<String>,
     #[structopt(
         name = "kernel",
         long = "kernel",
         short = "k",
-        help = "the kernel version",
-        default_value = "5.10.0"
+        help = "the kernel version, or \"auto\" to raise the default to the minimum the image's glibc requires",
+        default_value = DEFAULT_KERNEL
     )]
     pub kernel: String,
     #[structopt(
@@ -69,8 +72,253 @@ pub struct Opts {
         default_value = ""
     )]
     pub zfs_parent: String,
+    #[structopt(
+        name = "json",
+        long = "json",
+        short = "j",
+        help = "write a machine readable build result to this file, or \"-\" for stdout"
+    )]
+    pub json: Option<String>,
+    #[structopt(
+        name = "quiet",
+        long = "quiet",
+        short = "q",
+        help = "only display warnings and errors",
+        conflicts_with = "verbose"
+    )]
+    pub quiet: bool,
+    #[structopt(
+        name = "verbose",
+        long = "verbose",
+        short = "v",
+        help = "display more detail, may be repeated",
+        parse(from_occurrences)
+    )]
+    pub verbose: u8,
+    #[structopt(
+        name = "log_file",
+        long = "log-file",
+        help = "append every log record to this file as a line of JSON"
+    )]
+    pub log_file: Option<String>,
+    #[structopt(
+        name = "dry_run",
+        long = "dry-run",
+        short = "n",
+        help = "extract into a scratch directory and report every change that would be made, without creating a dataset or image"
+    )]
+    pub dry_run: bool,
+    #[structopt(
+        name = "skip",
+        long = "skip",
+        help = "skip the named stage, may be repeated or comma separated",
+        use_delimiter = true,
+        number_of_values = 1
+    )]
+    pub skip: Vec<String>,
+    #[structopt(
+        name = "stages",
+        long = "stages",
+        help = "comma separated list of the stages to run, in order",
+        use_delimiter = true
+    )]
+    pub stages: Vec<String>,
+    #[structopt(
+        name = "guest_dir",
+        long = "guest-dir",
+        help = "install guest tools from this directory instead of the copy embedded in the binary"
+    )]
+    pub guest_dir: Option<String>,
+    #[structopt(
+        name = "profile",
+        long = "profile",
+        help = "load a distro profile from this file, or every .json file in this directory, may be repeated",
+        number_of_values = 1
+    )]
+    pub profiles: Vec<String>,
+    #[structopt(
+        name = "distro",
+        long = "distro",
+        help = "use the named distro profile instead of detecting it"
+    )]
+    pub distro: Option<String>,
+    #[structopt(
+        name = "mask_unit",
+        long = "mask-unit",
+        help = "mask this systemd unit in addition to the built-in list, may be repeated",
+        number_of_values = 1
+    )]
+    pub mask_units: Vec<String>,
+    #[structopt(
+        name = "keep_network",
+        long = "keep-network",
+        help = "leave the image's network managers and their configuration alone"
+    )]
+    pub keep_network: bool,
+    #[structopt(
+        name = "no_mdata_hooks",
+        long = "no-mdata-hooks",
+        help = "if the image ships cloud-init, leave user-data and user-script to it and do not install mdata-fetch and mdata-execute"
+    )]
+    pub no_mdata_hooks: bool,
+    #[structopt(
+        name = "fail_on_lint",
+        long = "fail-on-lint",
+        help = "fail the build if the lint stage finds errors"
+    )]
+    pub fail_on_lint: bool,
+    #[structopt(
+        name = "strict_lint",
+        long = "strict-lint",
+        help = "fail the build if the lint stage finds warnings or errors"
+    )]
+    pub strict_lint: bool,
+    #[structopt(
+        name = "sbom",
+        long = "sbom",
+        help = "write a software bill of materials listing the image's packages next to the manifest",
+        possible_values = &["spdx", "cyclonedx"]
+    )]
+    pub sbom: Option<String>,
+    #[structopt(
+        name = "osv_db",
+        long = "osv-db",
+        help = "scan the image's packages for vulnerabilities listed in this OSV database snapshot"
+    )]
+    pub osv_db: Option<String>,
+    #[structopt(
+        name = "osv_ecosystem",
+        long = "osv-ecosystem",
+        help = "the OSV ecosystem of the image's packages, e.g. \"Debian:12\". The default is detected from /etc/os-release.",
+        requires = "osv_db"
+    )]
+    pub osv_ecosystem: Option<String>,
+    #[structopt(
+        name = "fail_on_vuln",
+        long = "fail-on-vuln",
+        help = "fail the build if the image has vulnerabilities of this severity or higher",
+        possible_values = &["low", "medium", "high", "critical"],
+        requires = "osv_db"
+    )]
+    pub fail_on_vuln: Option<String>,
+    #[structopt(
+        name = "file_list",
+        long = "file-list",
+        help = "write a list of every file in the image, with its mode, owner and digest, next to the manifest"
+    )]
+    pub file_list: bool,
+    #[structopt(
+        name = "minimize",
+        long = "minimize",
+        help = "remove documentation, man pages, unused locales and caches from the image, and configure the package manager to leave them out"
+    )]
+    pub minimize: bool,
+    #[structopt(
+        name = "secrets_allow",
+        long = "secrets-allow",
+        help = "a file of paths and authorized keys the secrets stage should not report"
+    )]
+    pub secrets_allow: Option<String>,
+    #[structopt(
+        name = "fail_on_secrets",
+        long = "fail-on-secrets",
+        help = "fail the build if the secrets stage finds credentials in the image"
+    )]
+    pub fail_on_secrets: bool,
+    #[structopt(
+        name = "fail_on_audit",
+        long = "fail-on-audit",
+        help = "fail the build if the audit stage finds permissions the distro profile does not allow"
+    )]
+    pub fail_on_audit: bool,
+    #[structopt(
+        name = "uid_map",
+        long = "uid-map",
+        help = "change the owner of files owned by uid FROM to uid TO, given as FROM:TO, may be repeated",
+        number_of_values = 1,
+        parse(try_from_str = owners::parse_map)
+    )]
+    pub uid_maps: Vec<(u32, u32)>,
+    #[structopt(
+        name = "gid_map",
+        long = "gid-map",
+        help = "change the group of files owned by gid FROM to gid TO, given as FROM:TO, may be repeated",
+        number_of_values = 1,
+        parse(try_from_str = owners::parse_map)
+    )]
+    pub gid_maps: Vec<(u32, u32)>,
+    #[structopt(
+        name = "squash_owners",
+        long = "squash-owners",
+        help = "make root the owner and group of files not owned by a system account or group of the image"
+    )]
+    pub squash_owners: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(name = "guest-tools", about = "inspect the embedded guest tools")]
+    GuestTools(GuestToolsCommand),
+    #[structopt(
+        name = "diff",
+        about = "list the package changes between two builds, from their manifests"
+    )]
+    Diff {
+        #[structopt(name = "old", help = "manifest of the older build")]
+        old: String,
+        #[structopt(name = "new", help = "manifest of the newer build")]
+        new: String,
+        #[structopt(
+            name = "files",
+            long = "files",
+            help = "also compare the builds' file lists, written with --file-list"
+        )]
+        files: bool,
+        #[structopt(
+            name = "format",
+            long = "format",
+            help = "output format",
+            possible_values = &["text", "json"],
+            default_value = "text"
+        )]
+        format: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum GuestToolsCommand {
+    #[structopt(name = "list", about = "list the embedded guest tools")]
+    List,
+    #[structopt(
+        name = "extract",
+        about = "write the embedded guest tools to a directory"
+    )]
+    Extract {
+        #[structopt(name = "dir", help = "directory to extract into")]
+        dir: String,
+    },
+}
+
+impl Opts {
+    pub fn tar(&self) -> Result<&str> {
+        self.tar
+            .as_deref()
+            .ok_or_else(|| anyhow!("--tar is required"))
+            .kind(ErrorKind::Usage)
+    }
 }
 
 pub fn get_opts() -> Opts {
-    Opts::from_args()
+    let opts = Opts::from_args();
+
+    // --tar is required unless a subcommand is given.
+    if opts.cmd.is_none() && opts.tar.is_none() {
+        clap::Error::with_description(
+            "The following required arguments were not provided:\n    --tar <tar>",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+
+    opts
 }
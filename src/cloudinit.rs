@@ -0,0 +1,77 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Images that ship cloud-init would otherwise run it with no usable
+ * datasource alongside the lib/smartdc scripts. When cloud-init is present it
+ * is pinned to the SmartOS datasource, its network configuration is
+ * disabled, and its module lists are replaced with ones that leave out the
+ * modules that would fight the platform (hostname, disk resizing and mounts,
+ * time). Since
+ * cloud-init then handles user-data and user-script itself, the mdata-fetch
+ * and mdata-execute hooks can optionally be left out.
+ */
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::utils::*;
+
+const MARKERS: &[&str] = &[
+    "usr/bin/cloud-init",
+    "usr/local/bin/cloud-init",
+    "etc/cloud/cloud.cfg",
+];
+
+const MDATA_HOOKS: &[&str] = &["lib/smartdc/mdata-fetch", "lib/smartdc/mdata-execute"];
+
+/*
+ * Return the path that shows cloud-init is installed, if it is.
+ */
+pub fn detect<P: AsRef<Path>>(zroot: P) -> Option<&'static str> {
+    let zroot = zroot.as_ref();
+    MARKERS.iter().copied().find(|m| exists(zroot.join(m)))
+}
+
+pub fn configure<P: AsRef<Path>>(zroot: P) -> Result<()> {
+    let zroot = zroot.as_ref();
+
+    let dir = zroot.join("etc/cloud/cloud.cfg.d");
+    if !exists(&dir) {
+        mkdirp(&dir, 0, 0, 0o755)?;
+    }
+
+    /*
+     * cloud.cfg.d is read in lexical order, so this sorts after the distro's
+     * own configuration but before anything a user drops in at 99.
+     */
+    let cfg = dir.join("90_triton.cfg");
+    if lexists(&cfg) {
+        remove_file(&cfg)?;
+    }
+    create_file_contents(&cfg, include_str!("../files/cloud-init-triton.cfg"))?;
+    change_perms(&cfg, 0, 0, 0o644)?;
+
+    Ok(())
+}
+
+pub fn remove_mdata_hooks<P: AsRef<Path>>(zroot: P) -> Result<()> {
+    let zroot = zroot.as_ref();
+
+    for hook in MDATA_HOOKS {
+        let path = zroot.join(hook);
+        if lexists(&path) {
+            remove_file(&path)?;
+            info!("removed /{}, cloud-init handles user-data", hook);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,421 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Compare two builds of an image: the packages listed in their SBOMs and,
+ * optionally, their file lists. A build is identified by its manifest, and
+ * the other files are found next to it by the manifest's name and version,
+ * as the build writes them.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::files::{self, Entry};
+use crate::packages::{Inventory, Manager, Package};
+use crate::sbom;
+use crate::version;
+
+pub struct Build {
+    pub name: String,
+    pub version: String,
+    pub inventory: Inventory,
+    pub files: Option<Vec<Entry>>,
+}
+
+impl Build {
+    pub fn load<P: AsRef<Path>>(manifest: P, with_files: bool) -> Result<Self> {
+        let manifest = manifest.as_ref();
+        let f = File::open(manifest)
+            .with_context(|| format!("failed to open {}", manifest.display()))?;
+        let doc: Value = serde_json::from_reader(BufReader::new(f))
+            .with_context(|| format!("failed to parse {}", manifest.display()))?;
+        let field = |key: &str| {
+            doc[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("{} has no {}", manifest.display(), key))
+        };
+        let name = field("name")?;
+        let version = field("version")?;
+        let sibling = |ext: &str| -> PathBuf {
+            manifest.with_file_name(format!("{}-{}.{}", name, version, ext))
+        };
+
+        let sbom = [sbom::Format::Spdx, sbom::Format::CycloneDx]
+            .iter()
+            .map(|f| sibling(f.extension()))
+            .find(|p| p.exists())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no SBOM for {} {} next to {}, was it built with --sbom?",
+                    name,
+                    version,
+                    manifest.display()
+                )
+            })?;
+        let inventory = sbom::read(&sbom)?;
+
+        let files = if with_files {
+            let path = sibling(files::EXTENSION);
+            if !path.exists() {
+                return Err(anyhow!(
+                    "no file list for {} {} next to {}, was it built with --file-list?",
+                    name,
+                    version,
+                    manifest.display()
+                ));
+            }
+            Some(files::read(&path)?)
+        } else {
+            None
+        };
+
+        Ok(Build {
+            name,
+            version,
+            inventory,
+            files,
+        })
+    }
+}
+
+pub struct Change {
+    pub manager: Manager,
+    pub name: String,
+    pub arch: Option<String>,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Default)]
+pub struct PackageDiff {
+    pub added: Vec<Package>,
+    pub removed: Vec<Package>,
+    pub upgraded: Vec<Change>,
+    pub downgraded: Vec<Change>,
+}
+
+impl PackageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+    }
+}
+
+#[derive(Default)]
+pub struct FileDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+type Key<'a> = (Manager, &'a str, Option<&'a str>);
+
+/*
+ * Packages by name and architecture. A name can be installed at several
+ * versions at once, such as rpm's install-only kernels, so each key has a
+ * list.
+ */
+fn by_key(inv: &Inventory) -> BTreeMap<Key<'_>, Vec<&Package>> {
+    let mut map: BTreeMap<Key<'_>, Vec<&Package>> = BTreeMap::new();
+    for p in &inv.packages {
+        map.entry((p.manager, p.name.as_str(), p.arch.as_deref()))
+            .or_default()
+            .push(p);
+    }
+    map
+}
+
+fn diff_packages(old: &Inventory, new: &Inventory) -> PackageDiff {
+    let old = by_key(old);
+    let new = by_key(new);
+    let mut diff = PackageDiff::default();
+    let none = vec![];
+
+    let keys: BTreeSet<&Key> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let o = old.get(key).unwrap_or(&none);
+        let n = new.get(key).unwrap_or(&none);
+
+        /*
+         * A package installed at one version in both builds was upgraded or
+         * downgraded. Otherwise each version is added or removed on its own,
+         * so that a second kernel being installed is not shown as the first
+         * being upgraded.
+         */
+        if let ([o], [n]) = (o.as_slice(), n.as_slice()) {
+            let change = || Change {
+                manager: key.0,
+                name: n.name.clone(),
+                arch: n.arch.clone(),
+                old: o.version.clone(),
+                new: n.version.clone(),
+            };
+            match version::compare(key.0, &o.version, &n.version) {
+                Ordering::Less => diff.upgraded.push(change()),
+                Ordering::Greater => diff.downgraded.push(change()),
+                Ordering::Equal => (),
+            }
+            continue;
+        }
+
+        let has = |list: &[&Package], v: &str| list.iter().any(|p| p.version == v);
+        diff.added.extend(
+            n.iter()
+                .filter(|p| !has(o, &p.version))
+                .map(|p| (*p).clone()),
+        );
+        diff.removed.extend(
+            o.iter()
+                .filter(|p| !has(n, &p.version))
+                .map(|p| (*p).clone()),
+        );
+    }
+
+    diff
+}
+
+fn diff_files(old: &[Entry], new: &[Entry]) -> FileDiff {
+    let old: BTreeMap<&str, &Entry> = old.iter().map(|e| (e.path.as_str(), e)).collect();
+    let new: BTreeMap<&str, &Entry> = new.iter().map(|e| (e.path.as_str(), e)).collect();
+    let mut diff = FileDiff::default();
+
+    for (path, n) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.to_string()),
+            Some(o) if o != n => diff.changed.push(path.to_string()),
+            Some(_) => (),
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .map(|path| path.to_string())
+        .collect();
+
+    diff
+}
+
+pub struct Diff {
+    pub old: (String, String),
+    pub new: (String, String),
+    pub packages: PackageDiff,
+    pub files: Option<FileDiff>,
+}
+
+impl Diff {
+    pub fn new(old: &Build, new: &Build) -> Self {
+        let files = match (&old.files, &new.files) {
+            (Some(o), Some(n)) => Some(diff_files(o, n)),
+            _ => None,
+        };
+        Diff {
+            old: (old.name.clone(), old.version.clone()),
+            new: (new.name.clone(), new.version.clone()),
+            packages: diff_packages(&old.inventory, &new.inventory),
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let package = |p: &Package| {
+            json!({
+                "manager": p.manager.to_string(),
+                "name": p.name,
+                "arch": p.arch,
+                "version": p.version,
+            })
+        };
+        let change = |c: &Change| {
+            json!({
+                "manager": c.manager.to_string(),
+                "name": c.name,
+                "arch": c.arch,
+                "old_version": c.old,
+                "new_version": c.new,
+            })
+        };
+        let files = match &self.files {
+            Some(f) => json!({
+                "added": f.added,
+                "removed": f.removed,
+                "changed": f.changed,
+            }),
+            None => Value::Null,
+        };
+
+        json!({
+            "old": { "name": self.old.0, "version": self.old.1 },
+            "new": { "name": self.new.0, "version": self.new.1 },
+            "packages": {
+                "added": self.packages.added.iter().map(package).collect::<Vec<_>>(),
+                "removed": self.packages.removed.iter().map(package).collect::<Vec<_>>(),
+                "upgraded": self.packages.upgraded.iter().map(change).collect::<Vec<_>>(),
+                "downgraded": self.packages.downgraded.iter().map(change).collect::<Vec<_>>(),
+            },
+            "files": files,
+        })
+    }
+
+    /*
+     * Write the changes as text suitable for release notes. Architectures are
+     * only shown for packages installed for more than one, e.g. "libc6:i386".
+     */
+    pub fn write_text<W: Write>(&self, mut w: W) -> Result<()> {
+        let p = &self.packages;
+        let names = p
+            .added
+            .iter()
+            .chain(p.removed.iter())
+            .map(|p| (p.name.as_str(), p.arch.as_deref()))
+            .chain(
+                p.upgraded
+                    .iter()
+                    .chain(p.downgraded.iter())
+                    .map(|c| (c.name.as_str(), c.arch.as_deref())),
+            );
+        let mut arches: HashMap<&str, HashSet<Option<&str>>> = HashMap::new();
+        for (name, arch) in names {
+            arches.entry(name).or_default().insert(arch);
+        }
+        let label = |name: &str, arch: &Option<String>| match arch {
+            Some(a) if arches.get(name).is_some_and(|s| s.len() > 1) => format!("{}:{}", name, a),
+            _ => name.to_string(),
+        };
+
+        writeln!(
+            w,
+            "Changes from {} {} to {} {}",
+            self.old.0, self.old.1, self.new.0, self.new.1
+        )?;
+
+        if p.is_empty() {
+            writeln!(w, "\nNo package changes.")?;
+        }
+        if !p.upgraded.is_empty() {
+            writeln!(w, "\nUpgraded packages ({}):", p.upgraded.len())?;
+            for c in &p.upgraded {
+                writeln!(w, "    {} {} -> {}", label(&c.name, &c.arch), c.old, c.new)?;
+            }
+        }
+        if !p.downgraded.is_empty() {
+            writeln!(w, "\nDowngraded packages ({}):", p.downgraded.len())?;
+            for c in &p.downgraded {
+                writeln!(w, "    {} {} -> {}", label(&c.name, &c.arch), c.old, c.new)?;
+            }
+        }
+        if !p.added.is_empty() {
+            writeln!(w, "\nAdded packages ({}):", p.added.len())?;
+            for a in &p.added {
+                writeln!(w, "    {} {}", label(&a.name, &a.arch), a.version)?;
+            }
+        }
+        if !p.removed.is_empty() {
+            writeln!(w, "\nRemoved packages ({}):", p.removed.len())?;
+            for r in &p.removed {
+                writeln!(w, "    {} {}", label(&r.name, &r.arch), r.version)?;
+            }
+        }
+
+        if let Some(f) = &self.files {
+            writeln!(
+                w,
+                "\nFiles: {} added, {} removed, {} changed",
+                f.added.len(),
+                f.removed.len(),
+                f.changed.len()
+            )?;
+            for path in &f.added {
+                writeln!(w, "    + {}", path)?;
+            }
+            for path in &f.removed {
+                writeln!(w, "    - {}", path)?;
+            }
+            for path in &f.changed {
+                writeln!(w, "    ~ {}", path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory(pkgs: &[(&str, &str)]) -> Inventory {
+        Inventory {
+            databases: vec![],
+            packages: pkgs
+                .iter()
+                .map(|(name, version)| {
+                    let mut p = Package::new(Manager::Rpm, name, version);
+                    p.arch = Some("x86_64".to_string());
+                    p
+                })
+                .collect(),
+        }
+    }
+
+    fn versions(pkgs: &[Package]) -> Vec<(&str, &str)> {
+        pkgs.iter()
+            .map(|p| (p.name.as_str(), p.version.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn single_version() {
+        let old = inventory(&[("bash", "5.1.8-6.el9"), ("curl", "7.76.1-26.el9")]);
+        let new = inventory(&[("bash", "5.1.8-9.el9"), ("curl", "7.76.1-19.el9")]);
+        let diff = diff_packages(&old, &new);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.upgraded.len(), 1);
+        assert_eq!(diff.upgraded[0].name, "bash");
+        assert_eq!(diff.downgraded.len(), 1);
+        assert_eq!(diff.downgraded[0].old, "7.76.1-26.el9");
+    }
+
+    #[test]
+    fn multiple_versions() {
+        let old = inventory(&[
+            ("kernel-core", "5.14.0-362.el9"),
+            ("kernel-core", "5.14.0-427.el9"),
+            ("bash", "5.1.8-6.el9"),
+        ]);
+        let new = inventory(&[
+            ("kernel-core", "5.14.0-427.el9"),
+            ("kernel-core", "5.14.0-503.el9"),
+            ("bash", "5.1.8-6.el9"),
+        ]);
+        let diff = diff_packages(&old, &new);
+        assert_eq!(versions(&diff.added), [("kernel-core", "5.14.0-503.el9")]);
+        assert_eq!(versions(&diff.removed), [("kernel-core", "5.14.0-362.el9")]);
+        assert!(diff.upgraded.is_empty() && diff.downgraded.is_empty());
+
+        /*
+         * A second version installed alongside the first is only added.
+         */
+        let one = inventory(&[("kernel-core", "5.14.0-427.el9"), ("bash", "5.1.8-6.el9")]);
+        let diff = diff_packages(&one, &new);
+        assert_eq!(versions(&diff.added), [("kernel-core", "5.14.0-503.el9")]);
+        assert!(diff.removed.is_empty() && diff.upgraded.is_empty());
+        let diff = diff_packages(&new, &one);
+        assert_eq!(versions(&diff.removed), [("kernel-core", "5.14.0-503.el9")]);
+    }
+}
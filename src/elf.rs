@@ -0,0 +1,459 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * lx zones only run x86_64 Linux binaries, but an image of any architecture
+ * can be packaged, and fails at boot. The headers of a few binaries every
+ * image runs are read to find the architecture of the image root. Only the
+ * ELF and program headers are parsed, nothing in the image is executed.
+ */
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::{ErrorKind, ResultExt};
+use crate::utils::*;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const PT_INTERP: u32 = 3;
+const PT_NOTE: u32 = 4;
+const NT_GNU_ABI_TAG: u32 = 1;
+const MAX_PHNUM: u16 = 4096;
+
+/*
+ * The binaries that must match, relative to the image root. The dynamic
+ * loader named by each is checked as well.
+ */
+const BINARIES: &[&str] = &["bin/sh", "sbin/init"];
+
+pub const NATIVE_ARCH: &str = "x86_64";
+
+#[derive(Debug)]
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub offset: u64,
+    pub filesz: u64,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    path: PathBuf,
+    pub class64: bool,
+    pub little_endian: bool,
+    pub machine: u16,
+    pub phdrs: Vec<ProgramHeader>,
+}
+
+impl Elf {
+    fn u16(&self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        }
+    }
+
+    fn u32(&self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    }
+
+    fn u64(&self, b: &[u8]) -> u64 {
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        if self.little_endian {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        }
+    }
+
+    /*
+     * Read the ELF and program headers of a file. Returns None if the file is
+     * not an ELF object, e.g. a shell script.
+     */
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let mut f =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+
+        let mut ehdr = [0u8; 64];
+        let n = f
+            .read(&mut ehdr)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if n < 52 || &ehdr[..4] != ELF_MAGIC {
+            return Ok(None);
+        }
+
+        let mut elf = Elf {
+            path: path.to_path_buf(),
+            class64: match ehdr[4] {
+                1 => false,
+                2 => true,
+                c => bail!("{}: unknown ELF class {}", path.display(), c),
+            },
+            little_endian: match ehdr[5] {
+                1 => true,
+                2 => false,
+                d => bail!("{}: unknown ELF data encoding {}", path.display(), d),
+            },
+            machine: 0,
+            phdrs: vec![],
+        };
+        if elf.class64 && n < 64 {
+            bail!("{}: truncated ELF header", path.display());
+        }
+        elf.machine = elf.u16(&ehdr[18..]);
+
+        let (phoff, phentsize, phnum) = if elf.class64 {
+            (
+                elf.u64(&ehdr[32..]),
+                elf.u16(&ehdr[54..]),
+                elf.u16(&ehdr[56..]),
+            )
+        } else {
+            (
+                u64::from(elf.u32(&ehdr[28..])),
+                elf.u16(&ehdr[42..]),
+                elf.u16(&ehdr[44..]),
+            )
+        };
+        let min = if elf.class64 { 56 } else { 32 };
+        if phnum == 0 {
+            return Ok(Some(elf));
+        }
+        if usize::from(phentsize) < min {
+            bail!("{}: invalid program header size", path.display());
+        }
+
+        /*
+         * The program header table is sized by the untrusted header, so is
+         * bounded before it is read, as segments are.
+         */
+        let size = u64::from(phentsize) * u64::from(phnum);
+        if phnum > MAX_PHNUM || size > 1 << 20 {
+            bail!("{}: too many program headers", path.display());
+        }
+        let len = f
+            .metadata()
+            .with_context(|| format!("failed to stat {}", path.display()))?
+            .len();
+        if phoff > len || size > len - phoff {
+            bail!(
+                "{}: program headers extend past the end of the file",
+                path.display()
+            );
+        }
+
+        let mut table = vec![0u8; size as usize];
+        f.seek(SeekFrom::Start(phoff))
+            .and_then(|_| f.read_exact(&mut table))
+            .with_context(|| format!("{}: failed to read program headers", path.display()))?;
+
+        for ph in table.chunks(usize::from(phentsize)) {
+            let p_type = elf.u32(ph);
+            let (offset, filesz) = if elf.class64 {
+                (elf.u64(&ph[8..]), elf.u64(&ph[32..]))
+            } else {
+                (u64::from(elf.u32(&ph[4..])), u64::from(elf.u32(&ph[16..])))
+            };
+            elf.phdrs.push(ProgramHeader {
+                p_type,
+                offset,
+                filesz,
+            });
+        }
+
+        Ok(Some(elf))
+    }
+
+    /*
+     * Read the contents of a segment.
+     */
+    pub fn segment(&self, ph: &ProgramHeader) -> Result<Vec<u8>> {
+        if ph.filesz > 1 << 20 {
+            bail!("{}: segment is too large", self.path.display());
+        }
+        let mut f = File::open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        let mut data = vec![0u8; ph.filesz as usize];
+        f.seek(SeekFrom::Start(ph.offset))
+            .and_then(|_| f.read_exact(&mut data))
+            .with_context(|| format!("{}: failed to read segment", self.path.display()))?;
+        Ok(data)
+    }
+
+    /*
+     * The dynamic loader requested by the binary, if it is dynamically linked.
+     */
+    pub fn interpreter(&self) -> Result<Option<String>> {
+        let ph = match self.phdrs.iter().find(|p| p.p_type == PT_INTERP) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let data = self.segment(ph)?;
+        let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        Ok(Some(String::from_utf8_lossy(&data[..end]).into_owned()))
+    }
+
+    /*
+     * The notes in the binary's PT_NOTE segments, as (name, type, desc).
+     */
+    pub fn notes(&self) -> Result<Vec<(String, u32, Vec<u8>)>> {
+        let align = |n: usize| (n + 3) & !3;
+        let mut notes = vec![];
+
+        for ph in self.phdrs.iter().filter(|p| p.p_type == PT_NOTE) {
+            let data = self.segment(ph)?;
+            let mut off = 0;
+            while off + 12 <= data.len() {
+                let namesz = self.u32(&data[off..]) as usize;
+                let descsz = self.u32(&data[off + 4..]) as usize;
+                let n_type = self.u32(&data[off + 8..]);
+                let name_start = off + 12;
+                let desc_start = name_start + align(namesz);
+                let next = desc_start + align(descsz);
+                if desc_start + descsz > data.len() {
+                    break;
+                }
+                let name = &data[name_start..name_start + namesz];
+                let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+                notes.push((
+                    String::from_utf8_lossy(&name[..end]).into_owned(),
+                    n_type,
+                    data[desc_start..desc_start + descsz].to_vec(),
+                ));
+                off = next;
+            }
+        }
+
+        Ok(notes)
+    }
+
+    /*
+     * The minimum Linux kernel version from the NT_GNU_ABI_TAG note, which
+     * glibc's libc and dynamic loader carry. The note also names the OS,
+     * which is 0 for Linux.
+     */
+    pub fn abi_tag(&self) -> Result<Option<(u32, u32, u32)>> {
+        for (name, n_type, desc) in self.notes()? {
+            if name != "GNU" || n_type != NT_GNU_ABI_TAG || desc.len() < 16 {
+                continue;
+            }
+            if self.u32(&desc) != 0 {
+                continue;
+            }
+            return Ok(Some((
+                self.u32(&desc[4..]),
+                self.u32(&desc[8..]),
+                self.u32(&desc[12..]),
+            )));
+        }
+        Ok(None)
+    }
+
+    pub fn arch(&self) -> &'static str {
+        match (self.machine, self.class64) {
+            (62, true) => "x86_64",
+            (62, false) => "x32",
+            (3, _) => "i386",
+            (183, _) => "aarch64",
+            (40, _) => "arm",
+            (8, true) => "mips64",
+            (8, false) => "mips",
+            (20, _) => "ppc",
+            (21, _) => "ppc64",
+            (22, _) => "s390x",
+            (243, true) => "riscv64",
+            (243, false) => "riscv32",
+            _ => "unknown",
+        }
+    }
+}
+
+/*
+ * The architecture of each binary that was checked, by path in the image.
+ */
+#[derive(Debug, Default)]
+pub struct ArchReport {
+    pub binaries: Vec<(String, &'static str)>,
+}
+
+impl ArchReport {
+    /*
+     * The architecture of the image root, if every binary checked agrees.
+     */
+    pub fn arch(&self) -> Option<&'static str> {
+        let first = self.binaries.first()?.1;
+        if self.binaries.iter().all(|(_, a)| *a == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+}
+
+fn check_binary(zroot: &Path, path: &str, report: &mut ArchReport) -> Result<Option<String>> {
+    if report.binaries.iter().any(|(p, _)| p == path) {
+        return Ok(None);
+    }
+    let resolved = match resolve_in_root(zroot, path) {
+        Some(p) if p.is_file() => p,
+        _ => {
+            debug!("/{} not found, not checking it", path);
+            return Ok(None);
+        }
+    };
+    let elf = match Elf::read(&resolved)? {
+        Some(e) => e,
+        None => {
+            debug!("/{} is not an ELF binary, not checking it", path);
+            return Ok(None);
+        }
+    };
+
+    debug!("/{} is {}", path, elf.arch());
+    report.binaries.push((path.to_string(), elf.arch()));
+    elf.interpreter()
+}
+
+/*
+ * Find the architecture of the image root, failing if it is not one lx zones
+ * can run.
+ */
+pub fn check_arch<P: AsRef<Path>>(zroot: P) -> Result<ArchReport> {
+    let zroot = zroot.as_ref();
+    let mut report = ArchReport::default();
+
+    for bin in BINARIES {
+        let interp = check_binary(zroot, bin, &mut report).kind(ErrorKind::Input)?;
+        if let Some(interp) = interp {
+            let interp = interp.trim_start_matches('/');
+            if resolve_in_root(zroot, interp).is_none() {
+                return Err(anyhow!(
+                    "/{} needs the dynamic loader /{}, which is not in the image",
+                    bin,
+                    interp
+                ))
+                .kind(ErrorKind::Input);
+            }
+            check_binary(zroot, interp, &mut report).kind(ErrorKind::Input)?;
+        }
+    }
+
+    if report.binaries.is_empty() {
+        warn!(
+            "found none of {} to check the image architecture",
+            BINARIES
+                .iter()
+                .map(|b| format!("/{}", b))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        return Ok(report);
+    }
+
+    let foreign: Vec<_> = report
+        .binaries
+        .iter()
+        .filter(|(_, a)| *a != NATIVE_ARCH)
+        .map(|(p, a)| format!("/{} is {}", p, a))
+        .collect();
+    if !foreign.is_empty() {
+        return Err(anyhow!(
+            "lx zones only run {} binaries, but {}",
+            NATIVE_ARCH,
+            foreign.join(", ")
+        ))
+        .kind(ErrorKind::Input);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /*
+     * A little-endian ELF64 header with the given program header table.
+     */
+    fn ehdr(phoff: u64, phentsize: u16, phnum: u16) -> Vec<u8> {
+        let mut h = vec![0u8; 64];
+        h[..4].copy_from_slice(ELF_MAGIC);
+        h[4] = 2;
+        h[5] = 1;
+        h[18..20].copy_from_slice(&62u16.to_le_bytes());
+        h[32..40].copy_from_slice(&phoff.to_le_bytes());
+        h[54..56].copy_from_slice(&phentsize.to_le_bytes());
+        h[56..58].copy_from_slice(&phnum.to_le_bytes());
+        h
+    }
+
+    fn read(name: &str, data: &[u8]) -> Result<Option<Elf>> {
+        let path = std::env::temp_dir().join(format!("elf-test-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        let elf = Elf::read(&path);
+        fs::remove_file(&path).unwrap();
+        elf
+    }
+
+    #[test]
+    fn program_headers() {
+        let mut data = ehdr(64, 56, 2);
+        let mut ph = vec![0u8; 56];
+        ph[..4].copy_from_slice(&PT_INTERP.to_le_bytes());
+        ph[8..16].copy_from_slice(&176u64.to_le_bytes());
+        ph[32..40].copy_from_slice(&28u64.to_le_bytes());
+        data.extend(&ph);
+        data.extend(&[0u8; 56]);
+
+        let elf = read("phdrs", &data).unwrap().unwrap();
+        assert_eq!(elf.machine, 62);
+        assert_eq!(elf.phdrs.len(), 2);
+        assert_eq!(elf.phdrs[0].p_type, PT_INTERP);
+        assert_eq!(elf.phdrs[0].offset, 176);
+        assert_eq!(elf.phdrs[0].filesz, 28);
+    }
+
+    #[test]
+    fn not_elf() {
+        assert!(read("script", b"#!/bin/sh\necho hello, world\n")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn oversized_program_headers() {
+        for (name, phoff, phentsize, phnum) in &[
+            ("phnum", 64, 56, 4097),
+            ("phentsize", 64, 65535, 64),
+            ("maximum", 64, 65535, 65535),
+            ("phoff", 1 << 40, 56, 1),
+            ("overflow", u64::MAX, 56, 1),
+            ("truncated", 64, 56, 2),
+        ] {
+            let err = read(name, &ehdr(*phoff, *phentsize, *phnum)).unwrap_err();
+            assert!(
+                err.to_string().contains("program headers"),
+                "{}: {}",
+                name,
+                err
+            );
+        }
+    }
+}
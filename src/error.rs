@@ -0,0 +1,127 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Errors are still carried around as anyhow::Error so that context can be
+ * attached freely, but the failures that callers care to tell apart are
+ * tagged with an ErrorKind. The kind determines the process exit code and is
+ * reported in the build result. The exit codes are part of the public
+ * interface and are documented in the README; do not renumber them.
+ */
+
+use anyhow::Result;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Internal,
+    Usage,
+    Input,
+    Extract,
+    Distro,
+    Guest,
+    Zfs,
+    Manifest,
+    Output,
+    Lint,
+    Vulns,
+    Secrets,
+    Audit,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Internal => 1,
+            Self::Usage => 2,
+            Self::Input => 10,
+            Self::Extract => 11,
+            Self::Distro => 12,
+            Self::Guest => 13,
+            Self::Zfs => 14,
+            Self::Manifest => 15,
+            Self::Output => 16,
+            Self::Lint => 17,
+            Self::Vulns => 18,
+            Self::Secrets => 19,
+            Self::Audit => 20,
+        }
+    }
+
+    /*
+     * Find the kind of an error, if one was attached anywhere in its chain.
+     */
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|e| e.downcast_ref::<BuildError>())
+            .map(|e| e.kind)
+            .unwrap_or(Self::Internal)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Internal => "internal",
+            Self::Usage => "usage",
+            Self::Input => "input",
+            Self::Extract => "extract",
+            Self::Distro => "distro",
+            Self::Guest => "guest",
+            Self::Zfs => "zfs",
+            Self::Manifest => "manifest",
+            Self::Output => "output",
+            Self::Lint => "lint",
+            Self::Vulns => "vulns",
+            Self::Secrets => "secrets",
+            Self::Audit => "audit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildError {
+    kind: ErrorKind,
+    inner: anyhow::Error,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.inner)
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+pub trait ResultExt<T> {
+    /*
+     * Tag the error with a kind. If the error has already been tagged the
+     * existing kind is kept, so the most specific call site wins.
+     */
+    fn kind(self, kind: ErrorKind) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn kind(self, kind: ErrorKind) -> Result<T> {
+        self.map_err(|e| {
+            let inner = e.into();
+            if inner.chain().any(|e| e.is::<BuildError>()) {
+                return inner;
+            }
+            anyhow::Error::new(BuildError { kind, inner })
+        })
+    }
+}
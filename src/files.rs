@@ -0,0 +1,120 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * A listing of every path in the image root, with enough detail to tell
+ * whether it changed between builds: type, mode, owner, and the size and
+ * SHA-1 of regular files or the target of symlinks. It is written as one JSON
+ * object per line, sorted by path.
+ */
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::manifest::sha1_digest;
+
+/*
+ * The file name extension of a file list, after "<name>-<version>".
+ */
+pub const EXTENSION: &str = "files.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+pub fn list<P: AsRef<Path>>(zroot: P) -> Result<Vec<Entry>> {
+    let zroot = zroot.as_ref();
+    let mut entries = vec![];
+
+    for dent in WalkDir::new(zroot).min_depth(1).sort_by_file_name() {
+        let dent = dent.with_context(|| format!("failed to walk {}", zroot.display()))?;
+        let path = dent.path();
+        let md = dent
+            .metadata()
+            .with_context(|| format!("failed to stat {}", path.display()))?;
+        let rel = path.strip_prefix(zroot).unwrap_or(path);
+
+        let ft = md.file_type();
+        let mut entry = Entry {
+            path: format!("/{}", rel.to_string_lossy()),
+            kind: "other".to_string(),
+            mode: format!("{:o}", md.mode() & 0o7777),
+            uid: md.uid(),
+            gid: md.gid(),
+            size: None,
+            sha1: None,
+            target: None,
+        };
+        if ft.is_dir() {
+            entry.kind = "dir".to_string();
+        } else if ft.is_symlink() {
+            entry.kind = "symlink".to_string();
+            let target = fs::read_link(path)
+                .with_context(|| format!("failed to read link {}", path.display()))?;
+            entry.target = Some(target.to_string_lossy().into_owned());
+        } else if ft.is_file() {
+            entry.kind = "file".to_string();
+            entry.size = Some(md.len());
+            let f =
+                File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+            entry.sha1 = Some(sha1_digest(BufReader::new(f))?);
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+pub fn write<P: AsRef<Path>>(entries: &[Entry], output: P) -> Result<()> {
+    let output = output.as_ref();
+    let f =
+        File::create(output).with_context(|| format!("failed to create {}", output.display()))?;
+    let mut w = BufWriter::new(f);
+    for e in entries {
+        serde_json::to_writer(&mut w, e)?;
+        writeln!(w)?;
+    }
+    w.flush()
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(())
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<Entry>> {
+    let path = path.as_ref();
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut entries = vec![];
+    for (n, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.with_context(|| format!("failed to read {}", path.display()))?;
+        if line.is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid file list entry", path.display(), n + 1))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
@@ -6,193 +6,38 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
- * Copyright 2023 MNX Cloud, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
-use anyhow::{bail, Context, Result};
-use std::fs;
+use anyhow::Result;
 use std::path::Path;
 
+use crate::init::{BootHook, InitSystem};
+use crate::payload::Payload;
+use crate::profile::{Detection, ProfileSet};
 use crate::utils::*;
 
-#[derive(Copy, Debug, Clone)]
-enum Distro {
-    Alpine,
-    Arch,
-    Debian,
-    Redhat,
-    Void,
-    Unknown,
-}
-
-impl Distro {
-    fn detect<P: AsRef<Path>>(zroot: P) -> Self {
-        let zroot = zroot.as_ref();
-
-        let supported = [
-            (Distro::Alpine, "etc/alpine-release"),
-            (Distro::Arch, "etc/arch-release"),
-            (Distro::Debian, "etc/debian_version"),
-            (Distro::Redhat, "etc/redhat-release"),
-            (Distro::Void, "etc/void-release"),
-        ];
-
-        if let Some((d, _)) = supported.iter().find(|(_, p)| zroot.join(p).exists()) {
-            println!("detected distro as {:?}", d);
-            return *d;
-        }
-
-        Distro::Unknown
-    }
-
-    fn install<P: AsRef<Path>>(&self, zroot: P) -> Result<()> {
-        let zroot = zroot.as_ref();
-
-        match self {
-            Self::Alpine => {
-                let rclocal = zroot.join("etc/local.d/joyent.start");
-                copy_file("guest/lib/smartdc/joyent_rc.local", &rclocal, 0, 0, 0o755)?;
-                let shutdown = zroot.join("sbin/shutdown");
-                copy_file("guest/sbin/shutdown", &shutdown, 0, 0, 0o755)?;
-                copy_file(
-                    "guest/lib/smartdc/alpine",
-                    zroot.join("lib/smartdc/alpine"),
-                    0,
-                    0,
-                    0o755,
-                )?;
-
-                let rm_files = [
-                    "etc/hostname",
-                    "etc/hosts",
-                    "etc/resolv.conf",
-                    "sbin/halt",
-                    "sbin/reboot",
-                ];
-                for f in rm_files {
-                    let fname = zroot.join(f);
-                    if fname.exists() || fname.is_symlink() {
-                        fs::remove_file(fname)?;
-                    }
-                }
-
-                /*
-                 * illumos has hard coded into it to replace several init
-                 * init scripts with /sbin/runscript as the interpreter. This
-                 * used to be included with openrc, but is not anymore.
-                 * Symlinking runscript to openrc-run seems to solve the issue
-                 * for us.
-                 */
-                let ln_paths = [
-                    // ["real file", "symlink"]
-                    ["sbin/openrc-run", "sbin/runscript"],
-                    ["sbin/shutdown", "sbin/halt"],
-                    ["sbin/shutdown", "sbin/reboot"],
-                ];
-
-                for p in &ln_paths {
-                    let src = Path::new("/").join(p[0]);
-                    let dst = zroot.join(p[1]);
-                    if !dst.exists() {
-                        create_symlink(&src, &dst, 0, 0)?;
-                    }
-                }
-            }
-            Self::Arch => {
-                let system = zroot.join("etc/systemd/system");
-                mkdirp(&system, 0, 0, 0o755)?;
-                let service = &system.join("joyent.service");
-                copy_file("etc/systemd/system/joyent.service", &service, 0, 0, 0o644)?;
-                let enable =
-                    zroot.join("etc/systemd/system/multi-user.target.wants/joyent.service");
-                create_symlink(&service, &enable, 0, 0)?;
-                copy_file(
-                    "guest/lib/smartdc/arch",
-                    zroot.join("lib/smartdc/arch"),
-                    0,
-                    0,
-                    0o755,
-                )?;
-            }
-            Self::Debian => {
-                let rclocal = zroot.join("etc/rc.local");
-                copy_file("guest/lib/smartdc/joyent_rc.local", &rclocal, 0, 0, 0o755)?;
-                copy_file(
-                    "guest/lib/smartdc/debian",
-                    zroot.join("lib/smartdc/debian"),
-                    0,
-                    0,
-                    0o755,
-                )?;
-            }
-            Self::Redhat => {
-                let dst = zroot.join("etc/rc.local");
-                copy_file("guest/lib/smartdc/joyent_rc.local", &dst, 0, 0, 0o755)?;
-                copy_file(
-                    "guest/lib/smartdc/redhat",
-                    zroot.join("lib/smartdc/redhat"),
-                    0,
-                    0,
-                    0o755,
-                )?;
-                let rm_files = [
-                    "root/anaconda-ks.cfg",
-                    "root/anaconda-post-nochroot.log",
-                    "root/anaconda-post.log",
-                    "root/original-ks.cfg",
-                ];
-                for f in rm_files {
-                    let fname = zroot.join(f);
-                    if fname.exists() {
-                        fs::remove_file(fname)?;
-                    }
-                }
-                let rm_dirs = [
-                    "root/buildinfo"
-                ];
-                for d in rm_dirs {
-                    let dname = zroot.join(d);
-                    if dname.exists() {
-                        fs::remove_dir_all(dname)?;
-                    }
-                }
-            }
-            Self::Void => {
-                let rclocal = zroot.join("etc/rc.local");
-                copy_file("guest/lib/smartdc/joyent_rc.local", &rclocal, 0, 0, 0o755)?;
-                let shutdown = zroot.join("sbin/shutdown");
-                copy_file("guest/sbin/shutdown", &shutdown, 0, 0, 0o755)?;
-                copy_file(
-                    "guest/lib/smartdc/void",
-                    zroot.join("lib/smartdc/void"),
-                    0,
-                    0,
-                    0o755,
-                )?;
-            }
-            Self::Unknown => {
-                bail!("failed to detect supported Linux Distribution");
-            }
-        };
-
-        Ok(())
-    }
+/*
+ * What install_tools found out about the image and chose to install.
+ */
+pub struct GuestSetup {
+    pub distro: Detection,
+    pub init: Option<(InitSystem, String)>,
+    pub boot_hook: BootHook,
 }
 
-fn install_native_manpath<P: AsRef<Path>>(zroot: P) -> Result<()> {
+fn install_native_manpath<P: AsRef<Path>>(zroot: P, payload: &Payload) -> Result<()> {
     let zroot = zroot.as_ref();
 
-    copy_file(
-        "guest/etc/profile.d/native_manpath.sh",
+    payload.install(
+        "etc/profile.d/native_manpath.sh",
         zroot.join("etc/profile.d/native_manpath.sh"),
-        0,
-        0,
         0o744,
     )?;
     Ok(())
 }
 
-fn install_smartdc<P: AsRef<Path>>(zroot: P) -> Result<()> {
+fn install_smartdc<P: AsRef<Path>>(zroot: P, payload: &Payload) -> Result<()> {
     let zroot = zroot.as_ref();
 
     mkdirp(zroot.join("lib/smartdc"), 0, 0, 0o755)?;
@@ -205,23 +50,49 @@ fn install_smartdc<P: AsRef<Path>>(zroot: P) -> Result<()> {
         "lib/smartdc/set-provision-state",
     ];
 
-    let guest = Path::new("guest");
     for p in &paths {
-        let src = guest.join(p);
         let dst = zroot.join(p);
-        copy_file(&src, &dst, 0, 0, 0o755)?;
+        payload.install(p, &dst, 0o755)?;
     }
 
     Ok(())
 }
 
-fn install_distro<P: AsRef<Path>>(zroot: P) -> Result<()> {
+fn install_distro<P: AsRef<Path>>(
+    zroot: P,
+    payload: &Payload,
+    profiles: &ProfileSet,
+    forced: Option<&str>,
+) -> Result<GuestSetup> {
     let zroot = zroot.as_ref();
 
-    let distro = Distro::detect(zroot);
-    distro.install(zroot)?;
+    let distro = profiles.detect(zroot, forced)?;
 
-    Ok(())
+    let init = InitSystem::detect(zroot);
+    match &init {
+        Some((i, rule)) => info!("detected init system as {} (rule: {})", i, rule),
+        None => warn!("failed to detect the init system"),
+    }
+
+    /*
+     * Images without a recognizable init system, such as those meant for
+     * application containers, get the rc.local hook as they always have.
+     */
+    let boot_hook = match (distro.profile.boot_hook, &init) {
+        (Some(h), _) => h,
+        (None, Some((i, _))) => i.default_hook(),
+        (None, None) => BootHook::RcLocal,
+    };
+    info!("installing {} boot hook", boot_hook);
+    boot_hook.install(zroot, payload)?;
+
+    distro.profile.install(zroot, payload)?;
+
+    Ok(GuestSetup {
+        distro,
+        init,
+        boot_hook,
+    })
 }
 
 fn install_mdata_commands<P: AsRef<Path>>(zroot: P) -> Result<()> {
@@ -237,10 +108,8 @@ fn install_mdata_commands<P: AsRef<Path>>(zroot: P) -> Result<()> {
     for p in &paths {
         let dst = zroot.join(p);
         let src = Path::new("/native").join(p);
-        if dst.exists() {
-            fs::remove_file(&dst)
-                .with_context(|| format!("failed to unlink {}", &src.display()))?;
-            println!("unlinked {}", &src.display());
+        if exists(&dst) {
+            remove_file(&dst)?;
         }
         create_symlink(&src, &dst, 0, 0)?;
     }
@@ -248,12 +117,17 @@ fn install_mdata_commands<P: AsRef<Path>>(zroot: P) -> Result<()> {
     Ok(())
 }
 
-pub fn install_tools<P: AsRef<Path>>(zroot: P) -> Result<()> {
+pub fn install_tools<P: AsRef<Path>>(
+    zroot: P,
+    payload: &Payload,
+    profiles: &ProfileSet,
+    forced: Option<&str>,
+) -> Result<GuestSetup> {
     let zroot = zroot.as_ref();
 
     install_mdata_commands(zroot)?;
-    install_native_manpath(zroot)?;
-    install_smartdc(zroot)?;
-    install_distro(&zroot)?;
-    Ok(())
+    install_native_manpath(zroot, payload)?;
+    install_smartdc(zroot, payload)?;
+    let setup = install_distro(zroot, payload, profiles, forced)?;
+    Ok(setup)
 }
@@ -0,0 +1,530 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The boot hook that runs joyent_rc.local depends on the image's init system
+ * rather than its package family: Debian and RHEL images run systemd, where
+ * /etc/rc.local only works through rc-local.service compatibility, while
+ * Devuan runs sysvinit. The init system is detected from the image root and
+ * selects the hook, unless the distro profile names one.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::payload::Payload;
+use crate::utils::*;
+
+/*
+ * s6-linux-init runs rc.init once the supervision tree is up. The s6-rc
+ * database it starts services from is compiled from its source directory by
+ * the distro's tools, so a service added to the source would not run until
+ * the database is recompiled; the s6 hook is added to rc.init instead.
+ */
+pub const S6_RC_INIT: &str = "etc/s6-linux-init/current/scripts/rc.init";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InitSystem {
+    Systemd,
+    Openrc,
+    Runit,
+    S6,
+    Sysvinit,
+    Busybox,
+}
+
+impl InitSystem {
+    /*
+     * Paths whose presence indicates the init system, checked in order. OpenRC
+     * comes before busybox because Alpine runs OpenRC from busybox init.
+     */
+    const PROBES: &'static [(InitSystem, &'static str)] = &[
+        (InitSystem::Systemd, "usr/lib/systemd/systemd"),
+        (InitSystem::Systemd, "lib/systemd/systemd"),
+        (InitSystem::Openrc, "sbin/openrc"),
+        (InitSystem::Openrc, "usr/sbin/openrc"),
+        (InitSystem::Runit, "sbin/runit"),
+        (InitSystem::Runit, "usr/bin/runit"),
+        (InitSystem::S6, "etc/s6-linux-init"),
+        (InitSystem::S6, "usr/bin/s6-svscan"),
+    ];
+
+    fn from_init_target(target: &str) -> Option<Self> {
+        if target.contains("systemd") {
+            Some(Self::Systemd)
+        } else if target.contains("openrc") {
+            Some(Self::Openrc)
+        } else if target.contains("runit") {
+            Some(Self::Runit)
+        } else if target.contains("s6") {
+            Some(Self::S6)
+        } else {
+            None
+        }
+    }
+
+    /*
+     * Detect the init system of an image root, returning the rule that
+     * matched along with it.
+     */
+    pub fn detect<P: AsRef<Path>>(zroot: P) -> Option<(Self, String)> {
+        let zroot = zroot.as_ref();
+        let init = zroot.join("sbin/init");
+        let target = fs::read_link(&init)
+            .ok()
+            .map(|t| t.to_string_lossy().into_owned());
+
+        if let Some(t) = &target {
+            if let Some(i) = Self::from_init_target(t) {
+                return Some((i, format!("sbin/init -> {}", t)));
+            }
+        }
+
+        for (i, p) in Self::PROBES {
+            if zroot.join(p).exists() {
+                return Some((*i, format!("file {}", p)));
+            }
+        }
+
+        match &target {
+            Some(t) if t.contains("busybox") => {
+                return Some((Self::Busybox, format!("sbin/init -> {}", t)));
+            }
+            None if init.is_file() && zroot.join("etc/inittab").exists() => {
+                return Some((Self::Sysvinit, "file sbin/init, etc/inittab".to_string()));
+            }
+            _ => (),
+        }
+
+        None
+    }
+
+    pub fn default_hook(&self) -> BootHook {
+        match self {
+            Self::Systemd => BootHook::Systemd,
+            Self::Openrc => BootHook::OpenrcLocal,
+            Self::Runit => BootHook::Runit,
+            Self::S6 => BootHook::S6,
+            Self::Sysvinit => BootHook::RcLocal,
+            Self::Busybox => BootHook::Inittab,
+        }
+    }
+}
+
+impl fmt::Display for InitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Systemd => "systemd",
+            Self::Openrc => "openrc",
+            Self::Runit => "runit",
+            Self::S6 => "s6",
+            Self::Sysvinit => "sysvinit",
+            Self::Busybox => "busybox",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BootHook {
+    /*
+     * Install joyent_rc.local as /etc/rc.local.
+     */
+    RcLocal,
+    /*
+     * Install joyent_rc.local as an OpenRC local.d script.
+     */
+    OpenrcLocal,
+    /*
+     * Install joyent.service and enable it for multi-user.target.
+     */
+    Systemd,
+    /*
+     * Install a runit service that runs joyent_rc.local once.
+     */
+    Runit,
+    /*
+     * Run joyent_rc.local from s6-linux-init's rc.init.
+     */
+    S6,
+    /*
+     * Run joyent_rc.local once from /etc/inittab.
+     */
+    Inittab,
+}
+
+impl fmt::Display for BootHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::RcLocal => "rc-local",
+            Self::OpenrcLocal => "openrc-local",
+            Self::Systemd => "systemd",
+            Self::Runit => "runit",
+            Self::S6 => "s6",
+            Self::Inittab => "inittab",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * Add joyent_rc.local to an rc.init script, to run after the services have
+ * been started. rc.init normally ends by exec'ing the runlevel script, so
+ * that is run without exec first. Returns None if the hook is already there.
+ */
+fn s6_rc_init(data: &str) -> Option<String> {
+    let hook = "/lib/smartdc/joyent_rc.local";
+    if data.lines().any(|l| l.trim() == hook) {
+        return None;
+    }
+
+    let mut lines: Vec<String> = data.lines().map(String::from).collect();
+    let exec = lines
+        .iter()
+        .rposition(|l| l.trim_start().starts_with("exec "));
+    match exec {
+        Some(i) => {
+            let cmd = lines[i].trim_start();
+            let indent = lines[i][..lines[i].len() - cmd.len()].to_string();
+            let cmd = cmd["exec ".len()..].trim_start().to_string();
+            lines.splice(
+                i..=i,
+                vec![format!("{}{}", indent, cmd), format!("{}{}", indent, hook)],
+            );
+        }
+        None => lines.push(hook.to_string()),
+    }
+
+    let mut data = lines.join("\n");
+    data.push('\n');
+    Some(data)
+}
+
+impl BootHook {
+    pub fn install(&self, zroot: &Path, payload: &Payload) -> Result<()> {
+        let rclocal = "lib/smartdc/joyent_rc.local";
+
+        match self {
+            Self::RcLocal => {
+                payload.install(rclocal, zroot.join("etc/rc.local"), 0o755)?;
+            }
+            Self::OpenrcLocal => {
+                let locald = zroot.join("etc/local.d");
+                mkdirp(&locald, 0, 0, 0o755)?;
+                payload.install(rclocal, locald.join("joyent.start"), 0o755)?;
+
+                /*
+                 * local.d scripts are run by the local service, which is not
+                 * in the default runlevel of minimal images.
+                 */
+                let runlevel = zroot.join("etc/runlevels/default");
+                let enable = runlevel.join("local");
+                if exists(zroot.join("etc/init.d/local")) && !lexists(&enable) {
+                    mkdirp(&runlevel, 0, 0, 0o755)?;
+                    create_symlink("/etc/init.d/local", &enable, 0, 0)?;
+                }
+            }
+            Self::Systemd => {
+                payload.install(rclocal, zroot.join(rclocal), 0o755)?;
+                let system = zroot.join("etc/systemd/system");
+                mkdirp(&system, 0, 0, 0o755)?;
+                payload.install(
+                    "etc/systemd/system/joyent.service",
+                    system.join("joyent.service"),
+                    0o644,
+                )?;
+                let wants = system.join("multi-user.target.wants");
+                if !exists(&wants) {
+                    mkdirp(&wants, 0, 0, 0o755)?;
+                }
+                let enable = wants.join("joyent.service");
+                if !lexists(&enable) {
+                    create_symlink("/etc/systemd/system/joyent.service", &enable, 0, 0)?;
+                }
+            }
+            Self::Runit => {
+                payload.install(rclocal, zroot.join(rclocal), 0o755)?;
+                let sv = zroot.join("etc/sv/joyent");
+                mkdirp(&sv, 0, 0, 0o755)?;
+                payload.install("etc/sv/joyent/run", sv.join("run"), 0o755)?;
+
+                /*
+                 * Void and Artix enable services in the default runsvdir,
+                 * other distros use /etc/service directly.
+                 */
+                let runsvdir = ["etc/runit/runsvdir/default", "etc/service"]
+                    .iter()
+                    .map(|d| zroot.join(d))
+                    .find(|d| exists(d))
+                    .unwrap_or_else(|| zroot.join("etc/service"));
+                mkdirp(&runsvdir, 0, 0, 0o755)?;
+                let enable = runsvdir.join("joyent");
+                if !lexists(&enable) {
+                    create_symlink("/etc/sv/joyent", &enable, 0, 0)?;
+                }
+            }
+            Self::S6 => {
+                let rcinit = match resolve_in_root(zroot, S6_RC_INIT) {
+                    Some(p) if p.is_file() => p,
+                    _ => {
+                        return Err(anyhow!(
+                            "s6 images without s6-linux-init are not supported: /{} is missing",
+                            S6_RC_INIT
+                        ))
+                    }
+                };
+                payload.install(rclocal, zroot.join(rclocal), 0o755)?;
+                let data = fs::read_to_string(&rcinit)
+                    .with_context(|| format!("failed to read /{}", S6_RC_INIT))?;
+                if let Some(data) = s6_rc_init(&data) {
+                    create_file_contents(&rcinit, data)?;
+                }
+            }
+            Self::Inittab => {
+                payload.install(rclocal, zroot.join(rclocal), 0o755)?;
+                let inittab = zroot.join("etc/inittab");
+                let line = "::once:/lib/smartdc/joyent_rc.local";
+                let mut data = if exists(&inittab) {
+                    fs::read_to_string(&inittab)
+                        .with_context(|| format!("failed to read {}", inittab.display()))?
+                } else {
+                    String::new()
+                };
+                if !data.lines().any(|l| l.trim() == line) {
+                    if !data.is_empty() && !data.ends_with('\n') {
+                        data.push('\n');
+                    }
+                    data.push_str(line);
+                    data.push('\n');
+                    create_file_contents(&inittab, data)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::PathBuf;
+
+    fn root(name: &str, dirs: &[&str], files: &[&str], links: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for d in dirs.iter().chain(["etc", "sbin", "lib/smartdc"].iter()) {
+            fs::create_dir_all(zroot.join(d)).unwrap();
+        }
+        for f in files {
+            fs::write(zroot.join(f), "").unwrap();
+        }
+        for (target, link) in links {
+            symlink(target, zroot.join(link)).unwrap();
+        }
+        zroot
+    }
+
+    fn detect(zroot: &Path) -> Option<(InitSystem, String)> {
+        let found = InitSystem::detect(zroot);
+        fs::remove_dir_all(zroot).unwrap();
+        found
+    }
+
+    #[test]
+    fn detect_init_target() {
+        for (target, init) in &[
+            ("/lib/systemd/systemd", InitSystem::Systemd),
+            ("/sbin/openrc-init", InitSystem::Openrc),
+            ("runit-init", InitSystem::Runit),
+            ("/usr/bin/s6-linux-init", InitSystem::S6),
+            ("/bin/busybox", InitSystem::Busybox),
+        ] {
+            let zroot = root("init-target", &[], &[], &[(target, "sbin/init")]);
+            assert_eq!(
+                detect(&zroot),
+                Some((*init, format!("sbin/init -> {}", target)))
+            );
+        }
+    }
+
+    #[test]
+    fn detect_probes() {
+        for (init, path) in InitSystem::PROBES {
+            let dir = Path::new(path).parent().unwrap().to_str().unwrap();
+            let zroot = root("init-probe", &[dir], &[path], &[]);
+            assert_eq!(detect(&zroot), Some((*init, format!("file {}", path))));
+        }
+
+        /*
+         * Alpine runs OpenRC from busybox init.
+         */
+        let zroot = root(
+            "init-alpine",
+            &[],
+            &["sbin/openrc"],
+            &[("/bin/busybox", "sbin/init")],
+        );
+        assert_eq!(
+            detect(&zroot),
+            Some((InitSystem::Openrc, "file sbin/openrc".to_string()))
+        );
+    }
+
+    #[test]
+    fn detect_sysvinit() {
+        let zroot = root("init-sysv", &[], &["sbin/init", "etc/inittab"], &[]);
+        assert_eq!(
+            detect(&zroot),
+            Some((
+                InitSystem::Sysvinit,
+                "file sbin/init, etc/inittab".to_string()
+            ))
+        );
+
+        let zroot = root("init-none", &[], &["sbin/init"], &[]);
+        assert_eq!(detect(&zroot), None);
+    }
+
+    fn install(zroot: &Path, hook: BootHook) -> Result<()> {
+        hook.install(zroot, &Payload::embedded())
+    }
+
+    fn link(zroot: &Path, path: &str) -> Option<PathBuf> {
+        fs::read_link(zroot.join(path)).ok()
+    }
+
+    #[test]
+    fn install_rc_local() {
+        let zroot = root("hook-rc-local", &[], &[], &[]);
+        install(&zroot, BootHook::RcLocal).unwrap();
+        let mode = fs::metadata(zroot.join("etc/rc.local")).map(|m| m.permissions().mode());
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(mode.unwrap() & 0o777, 0o755);
+    }
+
+    #[test]
+    fn install_openrc_local() {
+        let zroot = root("hook-openrc", &["etc/init.d"], &["etc/init.d/local"], &[]);
+        install(&zroot, BootHook::OpenrcLocal).unwrap();
+        let start = zroot.join("etc/local.d/joyent.start").is_file();
+        let enabled = link(&zroot, "etc/runlevels/default/local");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(start);
+        assert_eq!(enabled, Some(PathBuf::from("/etc/init.d/local")));
+    }
+
+    #[test]
+    fn install_systemd() {
+        let zroot = root("hook-systemd", &[], &[], &[]);
+        install(&zroot, BootHook::Systemd).unwrap();
+        let unit = zroot.join("etc/systemd/system/joyent.service").is_file();
+        let enabled = link(
+            &zroot,
+            "etc/systemd/system/multi-user.target.wants/joyent.service",
+        );
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(unit);
+        assert_eq!(
+            enabled,
+            Some(PathBuf::from("/etc/systemd/system/joyent.service"))
+        );
+    }
+
+    #[test]
+    fn install_runit() {
+        let zroot = root("hook-runit", &["etc/runit/runsvdir/default"], &[], &[]);
+        install(&zroot, BootHook::Runit).unwrap();
+        let run = zroot.join("etc/sv/joyent/run").is_file();
+        let enabled = link(&zroot, "etc/runit/runsvdir/default/joyent");
+        let service = zroot.join("etc/service").exists();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(run);
+        assert_eq!(enabled, Some(PathBuf::from("/etc/sv/joyent")));
+        assert!(!service);
+
+        let zroot = root("hook-runit-service", &[], &[], &[]);
+        install(&zroot, BootHook::Runit).unwrap();
+        let enabled = link(&zroot, "etc/service/joyent");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(enabled, Some(PathBuf::from("/etc/sv/joyent")));
+    }
+
+    #[test]
+    fn install_s6() {
+        let scripts = "etc/s6-linux-init/current/scripts";
+        let zroot = root("hook-s6", &[scripts], &[], &[]);
+        fs::write(
+            zroot.join(S6_RC_INIT),
+            "#!/bin/sh -e\nexec /etc/s6-linux-init/current/scripts/runlevel \"$1\"\n",
+        )
+        .unwrap();
+        install(&zroot, BootHook::S6).unwrap();
+        let rcinit = fs::read_to_string(zroot.join(S6_RC_INIT));
+        let rclocal = zroot.join("lib/smartdc/joyent_rc.local").is_file();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(rcinit
+            .unwrap()
+            .ends_with("runlevel \"$1\"\n/lib/smartdc/joyent_rc.local\n"));
+        assert!(rclocal);
+
+        let zroot = root("hook-s6-missing", &[], &[], &[]);
+        let err = install(&zroot, BootHook::S6);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn install_inittab() {
+        let zroot = root("hook-inittab", &[], &[], &[]);
+        fs::write(zroot.join("etc/inittab"), "::sysinit:/sbin/openrc sysinit").unwrap();
+        install(&zroot, BootHook::Inittab).unwrap();
+        install(&zroot, BootHook::Inittab).unwrap();
+        let inittab = fs::read_to_string(zroot.join("etc/inittab"));
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            inittab.unwrap(),
+            "::sysinit:/sbin/openrc sysinit\n::once:/lib/smartdc/joyent_rc.local\n"
+        );
+    }
+
+    #[test]
+    fn rc_init_exec() {
+        let data = "#!/bin/sh -e\nrl=\"$1\"\n\
+            s6-rc-init -c /etc/s6-rc/compiled /run/service\n\
+            exec /etc/s6-linux-init/current/scripts/runlevel \"$rl\"\n";
+        let want = "#!/bin/sh -e\nrl=\"$1\"\n\
+            s6-rc-init -c /etc/s6-rc/compiled /run/service\n\
+            /etc/s6-linux-init/current/scripts/runlevel \"$rl\"\n\
+            /lib/smartdc/joyent_rc.local\n";
+        let got = s6_rc_init(data).unwrap();
+        assert_eq!(got, want);
+        assert_eq!(s6_rc_init(&got), None);
+    }
+
+    #[test]
+    fn rc_init_indented_exec() {
+        let data = "if true; then\n    exec  runlevel \"$rl\"\nfi";
+        let want = "if true; then\n    runlevel \"$rl\"\n    \
+            /lib/smartdc/joyent_rc.local\nfi\n";
+        assert_eq!(s6_rc_init(data).unwrap(), want);
+    }
+
+    #[test]
+    fn rc_init_no_exec() {
+        let data = "#!/bin/sh\ns6-rc -up change default\n";
+        let want = "#!/bin/sh\ns6-rc -up change default\n/lib/smartdc/joyent_rc.local\n";
+        assert_eq!(s6_rc_init(data).unwrap(), want);
+    }
+}
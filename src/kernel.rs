@@ -0,0 +1,289 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The kernel version in the image manifest is the version lx emulates for
+ * the instance. glibc refuses to start ("FATAL: kernel too old") when it is
+ * lower than the version glibc was built for, which glibc records in the
+ * NT_GNU_ABI_TAG note of libc and its dynamic loader. musl has no such
+ * requirement.
+ */
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::path::Path;
+
+use crate::elf::Elf;
+use crate::utils::*;
+
+/*
+ * Where glibc's libc is found, relative to the image root.
+ */
+const LIBC: &[&str] = &[
+    "lib/x86_64-linux-gnu/libc.so.6",
+    "usr/lib/x86_64-linux-gnu/libc.so.6",
+    "lib64/libc.so.6",
+    "usr/lib64/libc.so.6",
+    "lib/libc.so.6",
+    "usr/lib/libc.so.6",
+];
+
+const MUSL: &[&str] = &["lib/ld-musl-x86_64.so.1", "usr/lib/ld-musl-x86_64.so.1"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl Version {
+    /*
+     * Parse a version such as "5.10.0" or "4.3". Anything after the numeric
+     * components, such as "-generic", is ignored.
+     */
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parts = [0u32; 3];
+        let mut n = 0;
+        for (i, part) in s.split('.').take(3).enumerate() {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if digits.is_empty() {
+                break;
+            }
+            parts[i] = digits
+                .parse()
+                .map_err(|_| anyhow!("invalid kernel version {}", s))?;
+            n += 1;
+            if digits.len() != part.len() {
+                break;
+            }
+        }
+        if n < 2 {
+            return Err(anyhow!("invalid kernel version {}", s));
+        }
+        Ok(Version(parts[0], parts[1], parts[2]))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Debug)]
+pub struct Requirement {
+    /*
+     * glibc or musl, if either was found.
+     */
+    pub libc: Option<&'static str>,
+    /*
+     * The minimum kernel, and the file it was read from.
+     */
+    pub minimum: Option<(Version, String)>,
+}
+
+fn abi_tag(zroot: &Path, path: &str) -> Result<Option<Version>> {
+    let resolved = match resolve_in_root(zroot, path) {
+        Some(p) if p.is_file() => p,
+        _ => return Ok(None),
+    };
+    let tag = match Elf::read(&resolved)? {
+        Some(elf) => elf.abi_tag()?,
+        None => None,
+    };
+    Ok(tag.map(|(a, b, c)| Version(a, b, c)))
+}
+
+/*
+ * Find the minimum kernel the image's userland requires.
+ */
+pub fn requirement<P: AsRef<Path>>(zroot: P) -> Result<Requirement> {
+    let zroot = zroot.as_ref();
+
+    /*
+     * The dynamic loader /bin/sh uses is the one that matters most, and is
+     * the only way to find libc on distros with unusual library paths.
+     */
+    let loader = match resolve_in_root(zroot, "bin/sh").filter(|p| p.is_file()) {
+        Some(sh) => match Elf::read(&sh)? {
+            Some(elf) => elf.interpreter()?,
+            None => None,
+        },
+        None => None,
+    };
+    let mut candidates: Vec<String> = vec![];
+    if let Some(l) = &loader {
+        candidates.push(l.trim_start_matches('/').to_string());
+    }
+    candidates.extend(LIBC.iter().map(|l| l.to_string()));
+
+    let mut minimum: Option<(Version, String)> = None;
+    for path in &candidates {
+        if let Some(v) = abi_tag(zroot, path)? {
+            debug!("/{} requires kernel {}", path, v);
+            if minimum.as_ref().is_none_or(|(m, _)| v > *m) {
+                minimum = Some((v, format!("/{}", path)));
+            }
+        }
+    }
+
+    let libc = if minimum.is_some() {
+        Some("glibc")
+    } else if loader.is_some_and(|l| l.contains("ld-musl"))
+        || MUSL.iter().any(|p| resolve_in_root(zroot, p).is_some())
+    {
+        Some("musl")
+    } else {
+        None
+    };
+
+    Ok(Requirement { libc, minimum })
+}
+
+/*
+ * Compare the requested kernel version with the minimum. Returns the version
+ * to use.
+ */
+pub fn choose(requested: &str, default: &str, req: &Requirement) -> Result<String> {
+    let minimum = req.minimum.as_ref().map(|(v, _)| *v);
+
+    if requested == "auto" {
+        /*
+         * Emulating an older kernel than necessary hides features from the
+         * userland, so auto only moves up from the default.
+         */
+        let default = Version::parse(default)?;
+        return Ok(match minimum {
+            Some(m) if m > default => m.to_string(),
+            _ => default.to_string(),
+        });
+    }
+
+    match (Version::parse(requested), &req.minimum) {
+        (Ok(v), Some((m, source))) if v < *m => {
+            warn!(
+                "--kernel {} is lower than {}, the minimum required by {}; the image will fail with \"kernel too old\"",
+                requested, m, source
+            );
+        }
+        (Err(e), _) => warn!("{:#}, not checking it against the userland", e),
+        _ => (),
+    }
+
+    Ok(requested.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn requirement_of(minimum: Option<Version>) -> Requirement {
+        Requirement {
+            libc: Some("glibc"),
+            minimum: minimum.map(|v| (v, "/lib64/libc.so.6".to_string())),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Version::parse("5.10.0").unwrap(), Version(5, 10, 0));
+        assert_eq!(Version::parse("4.3").unwrap(), Version(4, 3, 0));
+        assert_eq!(Version::parse("6.1.0-18-amd64").unwrap(), Version(6, 1, 0));
+        assert_eq!(Version::parse("3.10-generic").unwrap(), Version(3, 10, 0));
+        assert_eq!(Version::parse("2.6.32.1").unwrap(), Version(2, 6, 32));
+
+        for bad in &["", "5", "auto", "v5.10", "5.x", "99999999999.1"] {
+            assert!(Version::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn choose_auto() {
+        let newer = requirement_of(Some(Version(5, 15, 0)));
+        let older = requirement_of(Some(Version(3, 2, 0)));
+        let none = requirement_of(None);
+
+        assert_eq!(choose("auto", "5.10.0", &newer).unwrap(), "5.15.0");
+        assert_eq!(choose("auto", "5.10.0", &older).unwrap(), "5.10.0");
+        assert_eq!(choose("auto", "5.10.0", &none).unwrap(), "5.10.0");
+    }
+
+    #[test]
+    fn choose_explicit() {
+        let req = requirement_of(Some(Version(5, 15, 0)));
+
+        /*
+         * A version lower than the requirement, or one that cannot be
+         * checked, is warned about but used as given.
+         */
+        assert_eq!(choose("4.3.0", "5.10.0", &req).unwrap(), "4.3.0");
+        assert_eq!(choose("6.1", "5.10.0", &req).unwrap(), "6.1");
+        assert_eq!(choose("latest", "5.10.0", &req).unwrap(), "latest");
+        assert!(choose("auto", "bogus", &req).is_err());
+    }
+
+    /*
+     * A little-endian ELF64 file with one PT_NOTE segment holding a
+     * NT_GNU_ABI_TAG note for Linux 3.2.0.
+     */
+    fn libc() -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[18..20].copy_from_slice(&62u16.to_le_bytes());
+        data[32..40].copy_from_slice(&64u64.to_le_bytes());
+        data[54..56].copy_from_slice(&56u16.to_le_bytes());
+        data[56..58].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut ph = vec![0u8; 56];
+        ph[..4].copy_from_slice(&4u32.to_le_bytes());
+        ph[8..16].copy_from_slice(&120u64.to_le_bytes());
+        ph[32..40].copy_from_slice(&32u64.to_le_bytes());
+        data.extend(ph);
+
+        for word in &[4u32, 16, 1] {
+            data.extend(&word.to_le_bytes());
+        }
+        data.extend(b"GNU\0");
+        for word in &[0u32, 3, 2, 0] {
+            data.extend(&word.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn abi_tag_note() {
+        let zroot = test_dir("kernel-abi-tag");
+        fs::create_dir_all(zroot.join("usr/lib64")).unwrap();
+        fs::write(zroot.join("usr/lib64/libc.so.6"), libc()).unwrap();
+        std::os::unix::fs::symlink("usr/lib64", zroot.join("lib64")).unwrap();
+
+        let req = requirement(&zroot).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(req.libc, Some("glibc"));
+        assert_eq!(
+            req.minimum,
+            Some((Version(3, 2, 0), "/lib64/libc.so.6".to_string()))
+        );
+    }
+
+    #[test]
+    fn musl() {
+        let zroot = test_dir("kernel-musl");
+        fs::create_dir_all(zroot.join("lib")).unwrap();
+        fs::write(zroot.join("lib/ld-musl-x86_64.so.1"), b"").unwrap();
+
+        let req = requirement(&zroot).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(req.libc, Some("musl"));
+        assert_eq!(req.minimum, None);
+    }
+}
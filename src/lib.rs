@@ -0,0 +1,43 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+#[macro_use]
+pub mod logging;
+
+pub mod actions;
+pub mod audit;
+pub mod cli;
+pub mod cloudinit;
+pub mod diff;
+pub mod elf;
+pub mod error;
+pub mod files;
+pub mod guest;
+pub mod init;
+pub mod kernel;
+pub mod lint;
+pub mod manifest;
+pub mod minimize;
+pub mod network;
+pub mod osv;
+pub mod owners;
+pub mod packages;
+pub mod payload;
+pub mod pipeline;
+pub mod profile;
+pub mod result;
+pub mod rpmdb;
+pub mod sanitize;
+pub mod sbom;
+pub mod secrets;
+pub mod stages;
+pub mod systemd;
+pub mod utils;
+pub mod version;
@@ -0,0 +1,493 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The guest scripts are plain bash, and only find out at first boot that the
+ * image they were installed into lacks something they need. The linter checks
+ * the finished image root for the usual causes: a missing interpreter or
+ * command, a boot hook that is not executable or not enabled, and an
+ * os-release that cannot be read.
+ *
+ * Problems that stop the guest tools from running at all are errors. Missing
+ * commands are warnings, as most scripts only use them on some code paths.
+ */
+
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::init::{BootHook, S6_RC_INIT};
+use crate::payload::{Payload, EMBEDDED};
+use crate::profile::Profile;
+use crate::utils::*;
+
+/*
+ * Where the guest's PATH finds commands.
+ */
+const PATH: &[&str] = &[
+    "usr/local/sbin",
+    "usr/local/bin",
+    "usr/sbin",
+    "usr/bin",
+    "sbin",
+    "bin",
+];
+
+/*
+ * The commands each guest script runs, other than shell builtins and the
+ * mdata commands, which are provided by the platform under /native. Every
+ * executable script in the payload has an entry. mdata-image also runs the
+ * software it configures (mysql, dpkg and so on), which is left out.
+ */
+const COMMANDS: &[(&str, &[&str])] = &[
+    ("lib/smartdc/common.lib", &["logger", "which"]),
+    (
+        "lib/smartdc/joyent_rc.local",
+        &[
+            "grep", "hostname", "mktemp", "mv", "rm", "touch", "tr", "uname",
+        ],
+    ),
+    ("lib/smartdc/mdata-execute", &["touch"]),
+    ("lib/smartdc/mdata-fetch", &["chmod", "mkdir", "mv", "rm"]),
+    (
+        "lib/smartdc/mdata-image",
+        &[
+            "awk", "chown", "date", "grep", "head", "ls", "mv", "nproc", "od", "rm", "sed",
+            "service", "sleep", "su", "tail", "touch", "tr",
+        ],
+    ),
+    ("lib/smartdc/mount-zfs", &[]),
+    ("lib/smartdc/set-provision-state", &["mv"]),
+    ("lib/smartdc/alpine", &["cat", "hostname", "tr"]),
+    ("lib/smartdc/archlinux", &[]),
+    ("lib/smartdc/debian", &["find", "ssh-keygen", "wc"]),
+    ("lib/smartdc/redhat", &[]),
+    (
+        "lib/smartdc/gentoo",
+        &["cat", "find", "sed", "ssh-keygen", "tr", "wc"],
+    ),
+    (
+        "lib/smartdc/suse",
+        &["find", "grep", "rm", "sed", "ssh-keygen", "tr", "wc"],
+    ),
+    ("lib/smartdc/void", &["cat", "hostname", "jq"]),
+    ("etc/sv/joyent/run", &["sv"]),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn warning(&mut self, message: String) {
+        warn!("{}", &message);
+        self.findings.push(Finding {
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        error!("{}", &message);
+        self.findings.push(Finding {
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+}
+
+fn find_command(zroot: &Path, cmd: &str) -> bool {
+    PATH.iter()
+        .any(|d| resolve_in_root(zroot, Path::new(d).join(cmd)).is_some())
+}
+
+/*
+ * Check a script's #! line. Scripts without one (common.lib) are sourced
+ * rather than run.
+ */
+fn check_interpreter(zroot: &Path, path: &str, contents: &[u8], report: &mut Report) {
+    let first = contents.split(|b| *b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(first);
+    let line = match line.strip_prefix("#!") {
+        Some(l) => l,
+        None => return,
+    };
+
+    let mut words = line.split_whitespace();
+    let interp = match words.next() {
+        Some(i) => i,
+        None => {
+            report.error(format!("/{} has an empty #! line", path));
+            return;
+        }
+    };
+    if resolve_in_root(zroot, interp).is_none() {
+        report.error(format!(
+            "/{} needs {}, which is not in the image",
+            path, interp
+        ));
+        return;
+    }
+
+    if Path::new(interp).file_name().is_some_and(|n| n == "env") {
+        match words.find(|w| !w.starts_with('-')) {
+            Some(cmd) if !find_command(zroot, cmd) => report.error(format!(
+                "/{} needs {}, which is not in the image",
+                path, cmd
+            )),
+            Some(_) => (),
+            None => report.error(format!("/{} runs env without a command", path)),
+        }
+    }
+}
+
+/*
+ * Check a script installed at path from the payload file src.
+ */
+fn check_script(zroot: &Path, path: &str, src: &str, contents: &[u8], report: &mut Report) {
+    check_interpreter(zroot, path, contents, report);
+
+    let commands = COMMANDS
+        .iter()
+        .find(|(p, _)| *p == src)
+        .map(|(_, c)| *c)
+        .unwrap_or_default();
+    for cmd in commands {
+        if !find_command(zroot, cmd) {
+            report.warning(format!("/{} uses {}, which is not in the image", path, cmd));
+        }
+    }
+}
+
+/*
+ * Check that a file exists and, if it has really been installed rather than
+ * just planned, that it is executable.
+ */
+fn check_executable(zroot: &Path, path: &str, report: &mut Report) {
+    if !lexists(zroot.join(path)) {
+        report.error(format!("/{} is missing", path));
+        return;
+    }
+    if fs::symlink_metadata(zroot.join(path)).is_err() {
+        return;
+    }
+    let mode = resolve_in_root(zroot, path)
+        .and_then(|p| fs::metadata(p).ok())
+        .map(|m| m.permissions().mode());
+    match mode {
+        Some(m) if m & 0o111 != 0 => (),
+        Some(_) => report.error(format!("/{} is not executable", path)),
+        None => report.error(format!("/{} is a dangling symlink", path)),
+    }
+}
+
+fn check_enabled(zroot: &Path, hook: BootHook, report: &mut Report) {
+    let enabled = match hook {
+        /*
+         * rc.local is run by the init system whenever it is executable.
+         */
+        BootHook::RcLocal => true,
+        BootHook::OpenrcLocal => lexists(zroot.join("etc/runlevels/default/local")),
+        BootHook::Systemd => {
+            lexists(zroot.join("etc/systemd/system/multi-user.target.wants/joyent.service"))
+        }
+        BootHook::Runit => ["etc/runit/runsvdir/default/joyent", "etc/service/joyent"]
+            .iter()
+            .any(|p| lexists(zroot.join(p))),
+        BootHook::S6 => resolve_in_root(zroot, S6_RC_INIT)
+            .and_then(|p| fs::read_to_string(p).ok())
+            .is_some_and(|d| {
+                d.lines()
+                    .any(|l| l.trim() == "/lib/smartdc/joyent_rc.local")
+            }),
+        BootHook::Inittab => fs::read_to_string(zroot.join("etc/inittab"))
+            .is_ok_and(|d| d.contains("/lib/smartdc/joyent_rc.local")),
+    };
+
+    /*
+     * In a dry run files the hook appends to have not been written.
+     */
+    if !enabled && !matches!(hook, BootHook::S6 | BootHook::Inittab if is_planning()) {
+        report.error(format!("the {} boot hook is not enabled", hook));
+    }
+}
+
+fn check_boot_hook(zroot: &Path, hook: BootHook, report: &mut Report) {
+    let executables: &[&str] = match hook {
+        BootHook::RcLocal => &["etc/rc.local"],
+        BootHook::OpenrcLocal => &["etc/local.d/joyent.start"],
+        BootHook::Runit => &["lib/smartdc/joyent_rc.local", "etc/sv/joyent/run"],
+        BootHook::Systemd | BootHook::S6 | BootHook::Inittab => &["lib/smartdc/joyent_rc.local"],
+    };
+    for path in executables {
+        check_executable(zroot, path, report);
+    }
+
+    if hook == BootHook::Systemd && !lexists(zroot.join("etc/systemd/system/joyent.service")) {
+        report.error("/etc/systemd/system/joyent.service is missing".to_string());
+    }
+
+    check_enabled(zroot, hook, report);
+}
+
+fn check_os_release(zroot: &Path, report: &mut Report) {
+    match read_os_release(zroot) {
+        Ok(r) if r.id.is_empty() => report.error("os-release does not set ID".to_string()),
+        Ok(_) => (),
+        Err(e) => report.error(format!("{:#}", e)),
+    }
+}
+
+/*
+ * Lint the guest tools installed in the image root. The scripts are read from
+ * the payload they were installed from, so that a dry run can be linted too.
+ */
+pub fn lint<P: AsRef<Path>>(
+    zroot: P,
+    payload: &Payload,
+    profile: Option<&Profile>,
+    hook: Option<BootHook>,
+) -> Report {
+    let zroot = zroot.as_ref();
+    let mut report = Report::default();
+
+    let mut scripts: Vec<(&str, &str)> = EMBEDDED
+        .iter()
+        .filter(|f| f.path.starts_with("lib/smartdc/") || f.path.starts_with("etc/sv/"))
+        .map(|f| (f.path, f.path))
+        .collect();
+
+    /*
+     * Some boot hooks install joyent_rc.local under another name.
+     */
+    match hook {
+        Some(BootHook::RcLocal) => scripts.push(("etc/rc.local", "lib/smartdc/joyent_rc.local")),
+        Some(BootHook::OpenrcLocal) => {
+            scripts.push(("etc/local.d/joyent.start", "lib/smartdc/joyent_rc.local"))
+        }
+        _ => (),
+    }
+    if let Some(p) = profile {
+        for f in &p.files {
+            if !scripts.iter().any(|(dst, _)| *dst == f.dst) {
+                scripts.push((f.dst.as_str(), f.src.as_str()));
+            }
+        }
+    }
+
+    for (dst, src) in scripts {
+        if !lexists(zroot.join(dst)) {
+            continue;
+        }
+        match payload.contents(src) {
+            Ok(contents) => check_script(zroot, dst, src, &contents, &mut report),
+            Err(e) => report.error(format!("{:#}", e)),
+        }
+    }
+
+    match hook {
+        Some(h) => check_boot_hook(zroot, h, &mut report),
+        None => info!("no boot hook was installed, not checking it"),
+    }
+
+    check_os_release(zroot, &mut report);
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    fn root(name: &str, files: &[(&str, u32)], links: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for (path, mode) in files {
+            let path = zroot.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(*mode)).unwrap();
+        }
+        for (target, link) in links {
+            let link = zroot.join(link);
+            fs::create_dir_all(link.parent().unwrap()).unwrap();
+            symlink(target, link).unwrap();
+        }
+        zroot
+    }
+
+    fn messages(report: &Report, severity: Severity) -> Vec<&str> {
+        report
+            .findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .map(|f| f.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn commands_cover_payload() {
+        for f in EMBEDDED {
+            let script = f.path.starts_with("lib/smartdc/") || f.path.starts_with("etc/sv/");
+            if script && f.mode & 0o111 != 0 {
+                assert!(
+                    COMMANDS.iter().any(|(p, _)| *p == f.path),
+                    "{} has no COMMANDS entry",
+                    f.path
+                );
+            }
+        }
+        for (path, _) in COMMANDS {
+            assert!(
+                EMBEDDED.iter().any(|f| f.path == *path),
+                "{} is not in the payload",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn interpreter() {
+        let env = b"#!/usr/bin/env bash\n";
+
+        /*
+         * An absolute /bin symlink is resolved within the root.
+         */
+        let zroot = root(
+            "lint-interp",
+            &[("usr/bin/env", 0o755), ("usr/bin/bash", 0o755)],
+            &[("/usr/bin", "bin")],
+        );
+        let mut report = Report::default();
+        check_interpreter(&zroot, "lib/smartdc/debian", env, &mut report);
+        check_interpreter(&zroot, "etc/sv/joyent/run", b"#!/bin/bash\n", &mut report);
+        check_interpreter(
+            &zroot,
+            "lib/smartdc/common.lib",
+            b"# sourced\n",
+            &mut report,
+        );
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(report.findings.is_empty());
+
+        let zroot = root("lint-interp-missing", &[("usr/bin/env", 0o755)], &[]);
+        let mut report = Report::default();
+        check_interpreter(&zroot, "lib/smartdc/alpine", env, &mut report);
+        check_interpreter(&zroot, "etc/sv/joyent/run", b"#!/bin/sh\n", &mut report);
+        check_interpreter(&zroot, "lib/smartdc/void", b"#!\n", &mut report);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Error),
+            [
+                "/lib/smartdc/alpine needs bash, which is not in the image",
+                "/etc/sv/joyent/run needs /bin/sh, which is not in the image",
+                "/lib/smartdc/void has an empty #! line",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_commands() {
+        let zroot = root(
+            "lint-commands",
+            &[("bin/hostname", 0o755), ("usr/bin/tr", 0o755)],
+            &[],
+        );
+        let mut report = Report::default();
+        check_script(
+            &zroot,
+            "lib/smartdc/alpine",
+            "lib/smartdc/alpine",
+            b"",
+            &mut report,
+        );
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            ["/lib/smartdc/alpine uses cat, which is not in the image"]
+        );
+        assert!(messages(&report, Severity::Error).is_empty());
+    }
+
+    #[test]
+    fn boot_hook() {
+        let zroot = root("lint-hook", &[], &[]);
+        for dir in &["lib/smartdc", "etc/systemd/system"] {
+            fs::create_dir_all(zroot.join(dir)).unwrap();
+        }
+        BootHook::Systemd
+            .install(&zroot, &Payload::embedded())
+            .unwrap();
+        let mut report = Report::default();
+        check_boot_hook(&zroot, BootHook::Systemd, &mut report);
+        assert!(report.findings.is_empty());
+
+        fs::remove_file(zroot.join("etc/systemd/system/multi-user.target.wants/joyent.service"))
+            .unwrap();
+        fs::set_permissions(
+            zroot.join("lib/smartdc/joyent_rc.local"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        let mut report = Report::default();
+        check_boot_hook(&zroot, BootHook::Systemd, &mut report);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Error),
+            [
+                "/lib/smartdc/joyent_rc.local is not executable",
+                &format!("the {} boot hook is not enabled", BootHook::Systemd),
+            ]
+        );
+    }
+
+    #[test]
+    fn os_release() {
+        let zroot = root("lint-os-release", &[("etc/os-release", 0o644)], &[]);
+        let report = lint(&zroot, &Payload::embedded(), None, None);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            messages(&report, Severity::Error),
+            ["os-release does not set ID"]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * A small leveled logger. Human readable records go to stderr, filtered by
+ * the verbosity requested on the command line. If a log file is configured
+ * every record, regardless of verbosity, is also appended to it as a line of
+ * JSON. Records are tagged with the build stage (span) that emitted them, and
+ * warnings are retained so they can be included in the build result.
+ */
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /*
+     * Map the -q and -v flags to the most verbose level that is displayed.
+     */
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        if quiet {
+            return Level::Warn;
+        }
+
+        match verbose {
+            0 => Level::Info,
+            1 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+struct Logger {
+    level: Level,
+    file: Option<Mutex<File>>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+static SPANS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/*
+ * Configure the logger. Records logged before this is called are displayed
+ * at the default (info) level.
+ */
+pub fn init<P: AsRef<Path>>(level: Level, log_file: Option<P>) -> Result<()> {
+    let file = match log_file {
+        Some(p) => {
+            let p = p.as_ref();
+            let f = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(p)
+                .with_context(|| format!("failed to open log file {}", p.display()))?;
+            Some(Mutex::new(f))
+        }
+        None => None,
+    };
+
+    // Only the first call takes effect.
+    let _ = LOGGER.set(Logger { level, file });
+    Ok(())
+}
+
+fn current_span() -> Option<&'static str> {
+    SPANS.lock().unwrap().last().copied()
+}
+
+pub fn log(level: Level, args: fmt::Arguments) {
+    let msg = args.to_string();
+    let span = current_span();
+
+    if level == Level::Warn {
+        WARNINGS.lock().unwrap().push(msg.clone());
+    }
+
+    let (max, file) = match LOGGER.get() {
+        Some(l) => (l.level, l.file.as_ref()),
+        None => (Level::Info, None),
+    };
+
+    if let Some(file) = file {
+        let record = serde_json::json!({
+            "time": Utc::now().to_rfc3339(),
+            "level": level.to_string(),
+            "stage": span,
+            "msg": msg,
+        });
+        let mut f = file.lock().unwrap();
+        let _ = writeln!(f, "{}", record);
+    }
+
+    if level > max {
+        return;
+    }
+
+    let prefix = match level {
+        Level::Error => "ERROR: ",
+        Level::Warn => "WARNING: ",
+        _ => "",
+    };
+    match span {
+        Some(s) => eprintln!("{}[{}] {}", prefix, s, msg),
+        None => eprintln!("{}{}", prefix, msg),
+    }
+}
+
+/*
+ * Returns every warning logged so far.
+ */
+pub fn warnings() -> Vec<String> {
+    WARNINGS.lock().unwrap().clone()
+}
+
+/*
+ * A build stage. Records logged while a span is alive are tagged with its
+ * name, and the time spent in the stage is logged when it is dropped.
+ */
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+pub fn span(name: &'static str) -> Span {
+    SPANS.lock().unwrap().push(name);
+    log(Level::Debug, format_args!("starting stage"));
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Span {
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        log(
+            Level::Debug,
+            format_args!(
+                "finished stage {} in {:.2}s",
+                self.name,
+                self.start.elapsed().as_secs_f64()
+            ),
+        );
+        let mut spans = SPANS.lock().unwrap();
+        if let Some(pos) = spans.iter().rposition(|s| *s == self.name) {
+            spans.remove(pos);
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        $crate::logging::log($crate::logging::Level::Error, format_args!($($arg)+))
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        $crate::logging::log($crate::logging::Level::Warn, format_args!($($arg)+))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::logging::log($crate::logging::Level::Info, format_args!($($arg)+))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::logging::log($crate::logging::Level::Debug, format_args!($($arg)+))
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        $crate::logging::log($crate::logging::Level::Trace, format_args!($($arg)+))
+    };
+}
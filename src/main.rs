@@ -6,141 +6,176 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
- * Copyright 2023 MNX Cloud, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
-extern crate os_release;
-
-use anyhow::{Context, Result};
-use chrono::prelude::*;
-use os_release::OsRelease;
-use std::fs;
-use std::path::Path;
-use uuid::Uuid;
-
-mod actions;
-mod cli;
-mod guest;
-mod manifest;
-mod utils;
-
-use actions::*;
-use manifest::*;
-
-macro_rules! run_action {
-    ($fn:expr, $ds:expr) => {{
-        match $fn {
-            Ok(v) => v,
-            Err(e) => {
-                actions::destroy_dataset($ds);
-                return Err(e);
-            }
-        }
-    }};
+#[macro_use]
+extern crate smartos_lx_img_builder;
+
+use anyhow::Result;
+use std::io::Write;
+use std::process;
+
+use smartos_lx_img_builder::actions::tar_options;
+use smartos_lx_img_builder::cli::{self, Command, GuestToolsCommand};
+use smartos_lx_img_builder::diff::{Build, Diff};
+use smartos_lx_img_builder::error::{ErrorKind, ResultExt};
+use smartos_lx_img_builder::logging;
+use smartos_lx_img_builder::payload::{Payload, EMBEDDED};
+use smartos_lx_img_builder::pipeline::{BuildContext, Pipeline};
+use smartos_lx_img_builder::utils;
+
+fn print_plan<W: Write>(mut w: W, ctx: &BuildContext, changes: &[String]) -> Result<()> {
+    let tar = ctx.opts.tar()?;
+    let (_, format) = tar_options(tar)?;
+    let distro = match &ctx.distro {
+        Some(d) => format!(
+            "{} ({}, {} confidence)",
+            d.profile.name, d.rule, d.confidence
+        ),
+        None => "not detected".to_string(),
+    };
+
+    write!(w, "\n\n\n========== Plan ==========\n\n")?;
+    writeln!(w, "input: {} ({})", tar, format)?;
+    if let Some(arch) = &ctx.result.arch {
+        writeln!(w, "arch: {}", arch)?;
+    }
+    writeln!(w, "distro: {}", distro)?;
+    if let Some(hook) = &ctx.result.boot_hook {
+        let init = match &ctx.result.init {
+            Some(i) => i.as_str(),
+            None => "not detected",
+        };
+        writeln!(w, "init: {} (boot hook: {})", init, hook)?;
+    }
+    writeln!(w, "changes:")?;
+    for c in changes {
+        writeln!(w, "    {}", c)?;
+    }
+
+    if let Some(image) = &ctx.image {
+        writeln!(w, "filesystem: {}", &image.filesystem)?;
+        writeln!(w, "manifest: {}", &image.manifest)?;
+
+        ctx.manifest()?.to_plan_writer(&mut w)?;
+        writeln!(w)?;
+    }
+
+    Ok(())
 }
 
-fn read_os_release<P: AsRef<Path>>(zroot: P) -> Result<OsRelease> {
-    let zroot = zroot.as_ref();
-    let path = &zroot.join("etc/os-release");
-    let release = OsRelease::new_from(path)?;
-    Ok(release)
+fn print_output<W: Write>(mut w: W, ctx: &BuildContext) -> Result<()> {
+    write!(w, "\n\n\n========== Output ==========\n\n")?;
+    if let Some(fs) = &ctx.result.filesystem {
+        writeln!(w, "filesystem: {}", std::fs::canonicalize(fs)?.display())?;
+    }
+    if let Some(manifest) = &ctx.result.manifest {
+        writeln!(
+            w,
+            "manifest: {}",
+            std::fs::canonicalize(manifest)?.display()
+        )?;
+    }
+
+    Ok(())
 }
 
-fn get_zfs_parent(s: &str) -> String {
-    let zonename = zonename::getzonename().expect("failed to get zonename");
-    if s.is_empty() {
-        if zonename == "global" {
-            // This bakes in a smartos-ism, and poorly. It would be better to
-            // query SMF for the blessed system_pool like we're supposed to.
-            // It may also be a good idea to search for alternate pools
-            // (e.g., rpool). For now at least, they can' use -z to specify.
-            return "zones".to_string();
-        } else {
-            return format!("zones/{}/data", &zonename);
-        }
+fn build(ctx: &mut BuildContext) -> Result<()> {
+    let mut pipeline = Pipeline::standard();
+    if !ctx.opts.stages.is_empty() {
+        pipeline.reorder(&ctx.opts.stages)?;
+    }
+    for s in &ctx.opts.skip {
+        pipeline.skip(s)?;
+    }
+
+    pipeline.run(ctx)?;
+
+    /*
+     * With --json -, stdout carries only the build result.
+     */
+    let w: Box<dyn Write> = match ctx.opts.json.as_deref() {
+        Some("-") => Box::new(std::io::stderr()),
+        _ => Box::new(std::io::stdout()),
+    };
+    if ctx.opts.dry_run {
+        let changes = utils::finish_plan();
+        print_plan(w, ctx, &changes)
     } else {
-        return s.to_string();
+        print_output(w, ctx)
     }
 }
 
-fn main() -> Result<()> {
+fn run_command(cmd: &Command) -> Result<()> {
+    match cmd {
+        Command::GuestTools(GuestToolsCommand::List) => {
+            for f in EMBEDDED {
+                println!("{:o} {:>6} {}", f.mode, f.contents.len(), f.path);
+            }
+            Ok(())
+        }
+        Command::GuestTools(GuestToolsCommand::Extract { dir }) => {
+            Payload::extract(dir).kind(ErrorKind::Output)
+        }
+        Command::Diff {
+            old,
+            new,
+            files,
+            format,
+        } => {
+            let old = Build::load(old, *files).kind(ErrorKind::Input)?;
+            let new = Build::load(new, *files).kind(ErrorKind::Input)?;
+            let diff = Diff::new(&old, &new);
+
+            let stdout = std::io::stdout();
+            let mut handle = stdout.lock();
+            if format == "json" {
+                serde_json::to_writer_pretty(&mut handle, &diff.to_json())
+                    .kind(ErrorKind::Output)?;
+                writeln!(handle).kind(ErrorKind::Output)
+            } else {
+                diff.write_text(&mut handle).kind(ErrorKind::Output)
+            }
+        }
+    }
+}
+
+fn main() {
     let opts = cli::get_opts();
-    let utc: DateTime<Utc> = Utc::now();
-    let build_date = utc.format("%Y%m%d").to_string();
-    let uuid = Uuid::new_v4();
-    let iuuid = format!("{}-{}", &uuid, &build_date);
-    let zfs_parent = get_zfs_parent(&opts.zfs_parent);
-
-    let dataset = format!("{}/{}", &zfs_parent, &iuuid);
-    let zroot = create_dataset(&dataset)?;
-    run_action!(install_tar(&zroot, &opts.tar), &dataset);
-
-    let os_release = read_os_release(&zroot).context("failed to read os-release")?;
-    let name: String;
-    if opts.image_name.to_string().len() > 0 {
-        name = opts.image_name;
-    } else {
-        name = format!("{}-{}", os_release.id, os_release.version_id)
-            .trim_end_matches("-")
-            .to_string();
+    let level = logging::Level::from_flags(opts.quiet, opts.verbose);
+    if let Err(e) = logging::init(level, opts.log_file.as_ref()) {
+        error!("{:#}", e);
+        process::exit(ErrorKind::Output.exit_code());
     }
-    let zfs_tar = format!("output/{}-{}.zfs.gz", &name, &build_date);
-    let image_manifest = &format!("output/{}-{}.json", &name, &build_date);
-
-    let desc = format!(
-        "Container-native {} 64-bit image. {}",
-        os_release.pretty_name, &opts.description
-    );
-    let manifest = Manifest {
-        name: &name,
-        version: &build_date,
-        description: &desc.trim(),
-        homepage: &opts.url,
-        min_platform: &opts.min_platform,
-        uuid: &uuid,
-        os: "linux",
-        kernel: &opts.kernel,
-        tar_file: &zfs_tar,
-    };
-    let product = format!(
-        r#"Name: Triton Instance
-Image: {} {}
-Documentation: {}
-Description: {}
-
-"#,
-        &os_release.pretty_name, &build_date, &opts.url, &desc
-    );
-
-    let motd = format!(
-        r#"         *--+--*--*
-         |\ |\ |\ |\
-         | \| \| \| \     #####  ####   #  #####  ###   #   # TM
-         +--*--+--*--*      #    #   #  #    #   #   #  ##  #
-         |\ |\ |\ |\ |      #    ####   #    #   #   #  # # #
-         | \| \| \| \|      #    #  #   #    #   #   #  #  ##
-         *--+--+--+--+      #    #   #  #    #    ###   #   #
-          \ |\ |\ |\ |
-           \| \| \| \|     LX Instance ({} {})
-            *--+--*--*     {}
-
-"#, &os_release.pretty_name, &build_date, &opts.url
-    );
-
-    fs::create_dir_all("output")
-        .expect("Unable to create output directory");
-    run_action!(modify_image(&zroot, &product, &motd), &dataset);
-    run_action!(install_guest_tools(&zroot), &dataset);
-    run_action!(create_dataset_gzip(&dataset, &zfs_tar), &dataset);
-    run_action!(create_manifest(manifest, &image_manifest), &dataset);
-    destroy_dataset(dataset);
-
-    print!("\n\n\n========== Output ==========\n\n");
-    println!("filesystem: {}", std::fs::canonicalize(&zfs_tar)?.display());
-    println!(
-        "manifest: {}",
-        std::fs::canonicalize(&image_manifest)?.display()
-    );
 
-    Ok(())
+    if let Some(cmd) = &opts.cmd {
+        if let Err(e) = run_command(cmd) {
+            error!("{:#}", e);
+            process::exit(ErrorKind::of(&e).exit_code());
+        }
+        return;
+    }
+
+    let mut ctx = BuildContext::new(opts);
+
+    let mut code = 0;
+    if let Err(e) = build(&mut ctx) {
+        let kind = ErrorKind::of(&e);
+        error!("{:#}", e);
+        code = kind.exit_code();
+        ctx.result.error = Some((kind, format!("{:#}", e)));
+    }
+    ctx.result.warnings = logging::warnings();
+
+    if let Some(json) = &ctx.opts.json {
+        if let Err(e) = ctx.result.write(json) {
+            error!("failed to write build result to {}: {:#}", json, e);
+            if code == 0 {
+                code = ErrorKind::Output.exit_code();
+            }
+        }
+    }
+
+    process::exit(code);
 }
@@ -6,6 +6,7 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
 use anyhow::Result;
@@ -29,7 +30,7 @@ pub struct Manifest<'a> {
     pub tar_file: &'a str,
 }
 
-fn sha1_digest<R: Read>(mut reader: R) -> Result<String> {
+pub fn sha1_digest<R: Read>(mut reader: R) -> Result<String> {
     let mut hasher = Sha1::default();
     let mut buffer = [0; BUFSIZE];
 
@@ -46,14 +47,30 @@ fn sha1_digest<R: Read>(mut reader: R) -> Result<String> {
 
 impl<'a> Manifest<'a> {
     pub fn to_writer<W: Write>(&self, writer: W) -> Result<()> {
-        let file = File::open(&self.tar_file)?;
+        let file = File::open(self.tar_file)?;
         let bufreader = BufReader::new(file);
         let shasum = sha1_digest(bufreader)?;
-        let filesize = fs::metadata(&self.tar_file)?.len().to_string();
+        let filesize = fs::metadata(self.tar_file)?.len().to_string();
+
+        let manifest = self.to_value(&shasum, &filesize);
+        Ok(serde_json::to_writer_pretty(writer, &manifest)?)
+    }
+
+    /*
+     * Write the manifest as it would be emitted, with placeholders for the
+     * file digest and size, which are only known once the image has been
+     * packaged.
+     */
+    pub fn to_plan_writer<W: Write>(&self, writer: W) -> Result<()> {
+        let manifest = self.to_value("<sha1 of image file>", "<size of image file>");
+        Ok(serde_json::to_writer_pretty(writer, &manifest)?)
+    }
+
+    fn to_value(&self, shasum: &str, filesize: &str) -> serde_json::Value {
         let utc: DateTime<Utc> = Utc::now();
         let published_at = utc.format("%Y-%m-%dT%TZ").to_string();
 
-        let manifest = serde_json::json!({
+        serde_json::json!({
           "v": "2",
           "name": self.name,
           "version": self.version,
@@ -88,8 +105,6 @@ impl<'a> Manifest<'a> {
             "role": "os",
             "kernel_version": self.kernel
           }
-        });
-
-        Ok(serde_json::to_writer_pretty(writer, &manifest)?)
+        })
     }
 }
@@ -0,0 +1,763 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Images derived from containers or full installs carry documentation, man
+ * pages, info pages and message catalogs for every language, which take up
+ * space in every instance and are rarely read in one. Minimizing removes them,
+ * keeping the copyright files that licenses require and the catalogs for the
+ * image's configured language, and then configures the distro's package
+ * manager so that packages installed later leave the same paths out.
+ *
+ * /native is never touched: its directories are mount points for the
+ * platform, and the guest's MANPATH includes /native/usr/share/man (see
+ * etc/profile.d/native_manpath.sh) so that the platform's man pages, such as
+ * those of the mdata tools, remain readable after the image's own are gone.
+ */
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::sanitize::{disk_bytes, Summary};
+use crate::utils::*;
+
+/*
+ * Written into package manager configuration so that it is only added once.
+ */
+const MARKER: &str = "smartos-lx-img-builder --minimize";
+
+/*
+ * Paths that are never removed, whatever the rules say.
+ */
+const PROTECTED: &[&str] = &["native", "etc/profile.d/native_manpath.sh"];
+
+struct Rule {
+    category: &'static str,
+    patterns: &'static [&'static str],
+    keep: &'static [&'static str],
+    /*
+     * Whether the package manager is configured to leave these paths out of
+     * packages installed later.
+     */
+    exclude: bool,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        category: "docs",
+        patterns: &["usr/share/doc/*", "usr/share/gtk-doc/*", "usr/share/help/*"],
+        keep: &["usr/share/doc/*/copyright"],
+        exclude: true,
+    },
+    Rule {
+        category: "man",
+        patterns: &["usr/share/man/*"],
+        keep: &[],
+        exclude: true,
+    },
+    Rule {
+        category: "info",
+        patterns: &["usr/share/info/*"],
+        keep: &[],
+        exclude: true,
+    },
+    Rule {
+        category: "locales",
+        patterns: &["usr/share/locale/*"],
+        keep: &["usr/share/locale/locale.alias"],
+        exclude: true,
+    },
+    Rule {
+        category: "local-docs",
+        patterns: &["usr/local/share/doc/*", "usr/local/share/man/*"],
+        keep: &[],
+        exclude: false,
+    },
+    Rule {
+        category: "caches",
+        patterns: &[
+            "var/cache/man/*",
+            "var/cache/fontconfig/*",
+            "var/cache/debconf/*-old",
+        ],
+        keep: &[],
+        exclude: false,
+    },
+];
+
+/*
+ * Files that may set the image's language, and the variable that does.
+ */
+const LANG_FILES: &[(&str, &str)] = &[
+    ("etc/locale.conf", "LANG"),
+    ("etc/default/locale", "LANG"),
+    ("etc/env.d/02locale", "LANG"),
+    ("etc/sysconfig/language", "RC_LANG"),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exclude {
+    Dpkg,
+    Rpm,
+    Pacman,
+    Xbps,
+    Portage,
+}
+
+impl fmt::Display for Exclude {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Dpkg => "dpkg",
+            Self::Rpm => "rpm",
+            Self::Pacman => "pacman",
+            Self::Xbps => "xbps",
+            Self::Portage => "portage",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * A distro profile's minimize rules.
+ */
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    /*
+     * How to keep the package manager from installing excluded paths.
+     */
+    #[serde(default)]
+    pub exclude: Option<Exclude>,
+    /*
+     * Glob patterns, relative to the image root, of additional paths to
+     * remove.
+     */
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /*
+     * Glob patterns of paths to keep.
+     */
+    #[serde(default)]
+    pub keep: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub summary: Summary,
+    /*
+     * The languages whose message catalogs were kept.
+     */
+    pub languages: Vec<String>,
+    /*
+     * The package manager configuration that was written, and its path.
+     */
+    pub exclude: Option<(Exclude, String)>,
+}
+
+fn options() -> MatchOptions {
+    MatchOptions {
+        require_literal_leading_dot: false,
+        ..MatchOptions::new()
+    }
+}
+
+/*
+ * The language of the image's LANG, and its territory variant, e.g. "de" and
+ * "de_AT" for de_AT.UTF-8.
+ */
+fn languages(zroot: &Path) -> Vec<String> {
+    for (file, var) in LANG_FILES {
+        let data = match resolve_in_root(zroot, file).filter(|p| p.is_file()) {
+            Some(p) => fs::read_to_string(p).unwrap_or_default(),
+            None => continue,
+        };
+        let prefix = format!("{}=", var);
+        let value = data
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix(&prefix))
+            .map(|v| v.trim_matches(|c| c == '"' || c == '\''))
+            .next_back();
+        let value = match value {
+            Some(v) => v,
+            None => continue,
+        };
+        let locale = value.split(['.', '@']).next().unwrap_or_default();
+        if locale.is_empty() || locale == "C" || locale == "POSIX" {
+            return vec![];
+        }
+        let mut langs = vec![];
+        if let Some((lang, _)) = locale.split_once('_') {
+            langs.push(lang.to_string());
+        }
+        langs.push(locale.to_string());
+        return langs;
+    }
+    vec![]
+}
+
+fn kept(keep: &[Pattern], rel: &Path) -> bool {
+    PROTECTED.iter().any(|p| rel.starts_with(p))
+        || keep.iter().any(|p| p.matches_path_with(rel, options()))
+}
+
+/*
+ * Remove a path, or if something below it is kept, whatever else is below it.
+ */
+fn prune(
+    zroot: &Path,
+    path: &Path,
+    keep: &[Pattern],
+    category: &str,
+    summary: &mut Summary,
+) -> Result<()> {
+    let rel = path.strip_prefix(zroot).unwrap_or(path);
+    if !lexists(path) || has_symlink_parent(zroot, path) || kept(keep, rel) {
+        return Ok(());
+    }
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return Ok(()),
+    };
+
+    if !meta.is_dir() {
+        remove_file(path)?;
+        summary.record(category, if meta.is_file() { meta.len() } else { 0 });
+        return Ok(());
+    }
+
+    let keeps_some = WalkDir::new(path)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .any(|e| kept(keep, e.path().strip_prefix(zroot).unwrap_or(e.path())));
+    if !keeps_some {
+        let bytes = disk_bytes(path);
+        remove_dir_all(path)?;
+        summary.record(category, bytes);
+        return Ok(());
+    }
+
+    let mut children: Vec<_> = fs::read_dir(path)
+        .with_context(|| format!("failed to read {}", path.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    children.sort();
+    for child in children {
+        prune(zroot, &child, keep, category, summary)?;
+    }
+    Ok(())
+}
+
+fn apply(
+    zroot: &Path,
+    category: &str,
+    pattern: &str,
+    keep: &[Pattern],
+    summary: &mut Summary,
+) -> Result<()> {
+    let full = format!(
+        "{}/{}",
+        glob::Pattern::escape(&zroot.to_string_lossy()),
+        pattern
+    );
+    let paths = glob::glob_with(&full, options())
+        .with_context(|| format!("invalid minimize pattern {}", pattern))?;
+    for path in paths.filter_map(|p| p.ok()) {
+        prune(zroot, &path, keep, category, summary)?;
+    }
+    Ok(())
+}
+
+/*
+ * The paths the package manager should leave out, and those it should still
+ * install, as absolute glob patterns.
+ */
+fn exclusions(keep: &[String]) -> (Vec<String>, Vec<String>) {
+    let rules = RULES.iter().filter(|r| r.exclude);
+    let excluded = rules
+        .clone()
+        .flat_map(|r| r.patterns.iter())
+        .map(|p| format!("/{}", p))
+        .collect();
+    let included = rules
+        .flat_map(|r| r.keep.iter().map(|k| k.to_string()))
+        .chain(keep.iter().cloned())
+        .map(|k| format!("/{}", k))
+        .collect();
+    (excluded, included)
+}
+
+/*
+ * The configuration for each package manager, as the path to write and its
+ * contents. The contents of pacman.conf and make.conf are added to the
+ * existing files.
+ */
+fn exclude_config(
+    exclude: Exclude,
+    keep: &[String],
+    languages: &[String],
+) -> (&'static str, String) {
+    let (excluded, included) = exclusions(keep);
+    let header = format!(
+        "# Added by {}. Remove this to install\n\
+         # documentation, man pages and translations with packages again.\n",
+        MARKER
+    );
+    match exclude {
+        Exclude::Dpkg => {
+            let mut s = header;
+            for p in &excluded {
+                s.push_str(&format!("path-exclude={}\n", p));
+            }
+            for p in &included {
+                s.push_str(&format!("path-include={}\n", p));
+            }
+            ("etc/dpkg/dpkg.cfg.d/smartos-minimize", s)
+        }
+        Exclude::Rpm => {
+            let langs: Vec<&str> = std::iter::once("C")
+                .chain(languages.iter().map(|l| l.as_str()))
+                .collect();
+            let s = format!(
+                "{}%_excludedocs 1\n%_install_langs {}\n",
+                header,
+                langs.join(":")
+            );
+            ("etc/rpm/macros.smartos-minimize", s)
+        }
+        Exclude::Pacman => {
+            let strip = |p: &String| p.trim_start_matches('/').to_string();
+            let s = format!(
+                "{}NoExtract = {}\nNoExtract = {}\n",
+                header,
+                excluded.iter().map(strip).collect::<Vec<_>>().join(" "),
+                included
+                    .iter()
+                    .map(|p| format!("!{}", strip(p)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+            ("etc/pacman.conf", s)
+        }
+        Exclude::Xbps => {
+            let mut s = header;
+            for p in &excluded {
+                s.push_str(&format!("noextract={}\n", p));
+            }
+            for p in &included {
+                s.push_str(&format!("noextract=!{}\n", p));
+            }
+            ("etc/xbps.d/smartos-minimize.conf", s)
+        }
+        Exclude::Portage => {
+            let masks: Vec<String> = excluded
+                .iter()
+                .map(|p| p.trim_end_matches("/*").to_string())
+                .chain(included.iter().map(|p| format!("-{}", p)))
+                .collect();
+            let s = format!(
+                "{}INSTALL_MASK=\"${{INSTALL_MASK}} {}\"\n",
+                header,
+                masks.join(" ")
+            );
+            ("etc/portage/make.conf", s)
+        }
+    }
+}
+
+/*
+ * Write the package manager configuration, returning its path, or None if the
+ * image does not have the package manager's configuration directory or file.
+ */
+fn configure(
+    zroot: &Path,
+    exclude: Exclude,
+    keep: &[String],
+    languages: &[String],
+) -> Result<Option<String>> {
+    let (rel, contents) = exclude_config(exclude, keep, languages);
+    let path = zroot.join(rel);
+    /*
+     * The configuration directory may not exist yet, which
+     * has_symlink_parent() would count as a symlink.
+     */
+    let through_symlink = path
+        .ancestors()
+        .skip(1)
+        .take_while(|a| *a != zroot)
+        .any(|a| fs::symlink_metadata(a).is_ok_and(|m| m.file_type().is_symlink()));
+    if through_symlink {
+        warn!(
+            "/{} is reached through a symlink, not configuring {}",
+            rel, exclude
+        );
+        return Ok(None);
+    }
+
+    /*
+     * pacman.conf and make.conf are appended to, the others are files of
+     * their own in a directory the package manager reads.
+     */
+    let appended = matches!(exclude, Exclude::Pacman | Exclude::Portage);
+    let existing = match fs::symlink_metadata(&path) {
+        Ok(m) if m.is_file() => {
+            fs::read_to_string(&path).with_context(|| format!("failed to read /{}", rel))?
+        }
+        Ok(_) => {
+            warn!(
+                "/{} is not a regular file, not configuring {}",
+                rel, exclude
+            );
+            return Ok(None);
+        }
+        Err(_) if appended => {
+            warn!("the image has no /{}, not configuring {}", rel, exclude);
+            return Ok(None);
+        }
+        Err(_) => String::new(),
+    };
+    if existing.contains(MARKER) {
+        debug!("/{} is already configured", rel);
+        return Ok(Some(format!("/{}", rel)));
+    }
+
+    let contents = match exclude {
+        /*
+         * NoExtract is only read in the [options] section, which comes first.
+         */
+        Exclude::Pacman => {
+            let mut out = String::new();
+            let mut added = false;
+            for line in existing.lines() {
+                out.push_str(line);
+                out.push('\n');
+                if !added && line.trim() == "[options]" {
+                    out.push_str(&contents);
+                    added = true;
+                }
+            }
+            if !added {
+                warn!("/{} has no [options] section, not configuring pacman", rel);
+                return Ok(None);
+            }
+            out
+        }
+        Exclude::Portage => {
+            let mut out = existing;
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&contents);
+            out
+        }
+        _ => {
+            if let Some(parent) = path.parent() {
+                if !exists(parent) {
+                    mkdirp(parent, 0, 0, 0o755)?;
+                }
+            }
+            contents
+        }
+    };
+
+    create_file_contents(&path, contents)?;
+    Ok(Some(format!("/{}", rel)))
+}
+
+pub fn minimize<P: AsRef<Path>>(zroot: P, rules: &Rules) -> Result<Report> {
+    let zroot = zroot.as_ref();
+    let mut report = Report {
+        languages: languages(zroot),
+        ..Default::default()
+    };
+
+    let mut keep = rules.keep.clone();
+    keep.extend(
+        report
+            .languages
+            .iter()
+            .map(|l| format!("usr/share/locale/{}/*", l)),
+    );
+
+    for rule in RULES {
+        let patterns: Vec<Pattern> = rule
+            .keep
+            .iter()
+            .map(|k| k.to_string())
+            .chain(keep.iter().cloned())
+            .map(|k| Pattern::new(&k).with_context(|| format!("invalid keep pattern {}", k)))
+            .collect::<Result<_>>()?;
+        for pattern in rule.patterns {
+            apply(
+                zroot,
+                rule.category,
+                pattern,
+                &patterns,
+                &mut report.summary,
+            )?;
+        }
+    }
+
+    let patterns: Vec<Pattern> = keep
+        .iter()
+        .map(|k| Pattern::new(k).with_context(|| format!("invalid keep pattern {}", k)))
+        .collect::<Result<_>>()?;
+    for pattern in &rules.remove {
+        apply(zroot, "profile", pattern, &patterns, &mut report.summary)?;
+    }
+
+    for (name, c) in &report.summary.categories {
+        info!("{}: removed {} paths, {} bytes", name, c.paths, c.bytes);
+    }
+
+    if let Some(exclude) = rules.exclude {
+        let keep: Vec<String> = keep
+            .iter()
+            .filter(|k| k.starts_with("usr/share/"))
+            .cloned()
+            .collect();
+        if let Some(path) = configure(zroot, exclude, &keep, &report.languages)? {
+            info!(
+                "configured {} to leave out documentation in {}",
+                exclude, path
+            );
+            report.exclude = Some((exclude, path));
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for (path, contents) in files {
+            let path = zroot.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        zroot
+    }
+
+    fn contents(zroot: &Path, path: &str) -> Option<String> {
+        fs::read_to_string(zroot.join(path)).ok()
+    }
+
+    #[test]
+    fn fixture_tree() {
+        let zroot = root(
+            "minimize",
+            &[
+                ("etc/default/locale", "LANG=\"de_AT.UTF-8\"\n"),
+                (
+                    "etc/profile.d/native_manpath.sh",
+                    "MANPATH=/native/usr/share/man\n",
+                ),
+                ("etc/profile.d/vendor.sh", ""),
+                ("native/usr/share/man/man1/mdata-get.1", "mdata"),
+                ("usr/share/doc/bash/copyright", "GPL"),
+                ("usr/share/doc/bash/README", "readme"),
+                ("usr/share/doc/bash/examples/loadables.c", "code"),
+                ("usr/share/man/man1/bash.1", "man"),
+                ("usr/share/locale/locale.alias", "alias"),
+                ("usr/share/locale/de/LC_MESSAGES/bash.mo", "de"),
+                ("usr/share/locale/de_AT/LC_MESSAGES/bash.mo", "de_AT"),
+                ("usr/share/locale/fr/LC_MESSAGES/bash.mo", "fr"),
+                ("var/cache/man/index.db", "db"),
+            ],
+        );
+        let rules = Rules {
+            exclude: Some(Exclude::Dpkg),
+            remove: vec!["native/*".to_string(), "etc/profile.d/*".to_string()],
+            keep: vec![],
+        };
+        let report = minimize(&zroot, &rules).unwrap();
+        let present = |p: &str| lexists(zroot.join(p));
+        let kept: Vec<bool> = [
+            "usr/share/doc/bash/copyright",
+            "usr/share/locale/locale.alias",
+            "usr/share/locale/de/LC_MESSAGES/bash.mo",
+            "usr/share/locale/de_AT/LC_MESSAGES/bash.mo",
+            "native/usr/share/man/man1/mdata-get.1",
+            "etc/profile.d/native_manpath.sh",
+        ]
+        .iter()
+        .map(|p| present(p))
+        .collect();
+        let removed: Vec<bool> = [
+            "usr/share/doc/bash/README",
+            "usr/share/doc/bash/examples",
+            "usr/share/man/man1",
+            "usr/share/locale/fr",
+            "var/cache/man/index.db",
+            "etc/profile.d/vendor.sh",
+        ]
+        .iter()
+        .map(|p| present(p))
+        .collect();
+        let dpkg = contents(&zroot, "etc/dpkg/dpkg.cfg.d/smartos-minimize");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(kept, [true; 6]);
+        assert_eq!(removed, [false; 6]);
+        assert_eq!(report.languages, ["de", "de_AT"]);
+        assert_eq!(
+            report.exclude,
+            Some((
+                Exclude::Dpkg,
+                "/etc/dpkg/dpkg.cfg.d/smartos-minimize".to_string()
+            ))
+        );
+        let paths = |c: &str| report.summary.categories.get(c).map(|c| c.paths);
+        assert_eq!(paths("docs"), Some(2));
+        assert_eq!(paths("locales"), Some(1));
+        assert_eq!(paths("profile"), Some(1));
+
+        /*
+         * dpkg applies the last matching rule, so the includes come after
+         * the excludes.
+         */
+        let dpkg = dpkg.unwrap();
+        let rules: Vec<&str> = dpkg.lines().filter(|l| !l.starts_with('#')).collect();
+        let first_include = rules
+            .iter()
+            .position(|l| l.starts_with("path-include="))
+            .unwrap();
+        assert!(rules[..first_include]
+            .iter()
+            .all(|l| l.starts_with("path-exclude=")));
+        assert_eq!(
+            &rules[first_include..],
+            [
+                "path-include=/usr/share/doc/*/copyright",
+                "path-include=/usr/share/locale/locale.alias",
+                "path-include=/usr/share/locale/de/*",
+                "path-include=/usr/share/locale/de_AT/*",
+            ]
+        );
+        assert!(rules.contains(&"path-exclude=/usr/share/locale/*"));
+    }
+
+    #[test]
+    fn lang() {
+        for (file, data, expected) in &[
+            (
+                "etc/locale.conf",
+                "LANG=en_US.UTF-8\n",
+                &["en", "en_US"][..],
+            ),
+            (
+                "etc/locale.conf",
+                "LANG='pt_BR'\nLANG=fr_FR\n",
+                &["fr", "fr_FR"],
+            ),
+            (
+                "etc/default/locale",
+                "  LANG=sr_RS@latin\n",
+                &["sr", "sr_RS"],
+            ),
+            ("etc/env.d/02locale", "LANG=\"C.UTF-8\"\n", &[]),
+            ("etc/sysconfig/language", "RC_LANG=\"de\"\n", &["de"]),
+            ("etc/locale.conf", "LC_ALL=POSIX\nLANG=POSIX\n", &[]),
+            ("etc/locale.conf", "LC_MESSAGES=de_DE\n", &[]),
+        ] {
+            let zroot = root("minimize-lang", &[(file, data)]);
+            let langs = languages(&zroot);
+            fs::remove_dir_all(&zroot).unwrap();
+            assert_eq!(langs, *expected, "{}: {}", file, data);
+        }
+
+        /*
+         * A file that does not set the variable is passed over.
+         */
+        let zroot = root(
+            "minimize-lang-order",
+            &[
+                ("etc/locale.conf", "LC_TIME=en_GB.UTF-8\n"),
+                ("etc/default/locale", "LANG=nl_NL.UTF-8\n"),
+            ],
+        );
+        let langs = languages(&zroot);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(langs, ["nl", "nl_NL"]);
+    }
+
+    #[test]
+    fn pacman() {
+        let conf = "\
+# /etc/pacman.conf
+[options]
+HoldPkg     = pacman glibc
+Architecture = auto
+
+[core]
+Include = /etc/pacman.d/mirrorlist
+";
+        let zroot = root("minimize-pacman", &[("etc/pacman.conf", conf)]);
+        let path = configure(&zroot, Exclude::Pacman, &[], &[]).unwrap();
+        let written = contents(&zroot, "etc/pacman.conf").unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(path.as_deref(), Some("/etc/pacman.conf"));
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[1], "[options]");
+        assert!(lines[2].starts_with("# Added by smartos-lx-img-builder --minimize."));
+        assert!(lines[4].starts_with("NoExtract = usr/share/doc/* "));
+        assert_eq!(
+            lines[5],
+            "NoExtract = !usr/share/doc/*/copyright !usr/share/locale/locale.alias"
+        );
+        assert_eq!(lines[6], "HoldPkg     = pacman glibc");
+        assert_eq!(
+            &lines[lines.len() - 2..],
+            ["[core]", "Include = /etc/pacman.d/mirrorlist"]
+        );
+
+        let zroot = root(
+            "minimize-pacman-options",
+            &[("etc/pacman.conf", "[core]\n")],
+        );
+        let path = configure(&zroot, Exclude::Pacman, &[], &[]).unwrap();
+        let written = contents(&zroot, "etc/pacman.conf");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(path, None);
+        assert_eq!(written.as_deref(), Some("[core]\n"));
+    }
+
+    #[test]
+    fn portage_once() {
+        let conf = "COMMON_FLAGS=\"-O2 -pipe\"";
+        let zroot = root("minimize-portage", &[("etc/portage/make.conf", conf)]);
+        let langs = vec!["de".to_string()];
+        configure(&zroot, Exclude::Portage, &[], &langs).unwrap();
+        let once = contents(&zroot, "etc/portage/make.conf").unwrap();
+        let path = configure(&zroot, Exclude::Portage, &[], &langs).unwrap();
+        let twice = contents(&zroot, "etc/portage/make.conf").unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(path.as_deref(), Some("/etc/portage/make.conf"));
+        assert_eq!(once, twice);
+        assert!(once.starts_with("COMMON_FLAGS=\"-O2 -pipe\"\n# Added by"));
+        assert_eq!(once.matches("INSTALL_MASK=").count(), 1);
+        assert!(once.contains("INSTALL_MASK=\"${INSTALL_MASK} /usr/share/doc /usr/share/gtk-doc"));
+        assert!(once.ends_with(" -/usr/share/doc/*/copyright -/usr/share/locale/locale.alias\"\n"));
+    }
+}
@@ -0,0 +1,669 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * lx zones get their interfaces and addresses from the platform before the
+ * zone boots. Network managers shipped in the image would try to configure
+ * them again, typically with DHCP, and either fail or tear down the
+ * platform's configuration. Each network stack found in the image is
+ * neutralized with one of these strategies:
+ *
+ *   rewrite   leave the stack running, but remove its configuration for
+ *             platform NICs, or have it explicitly ignore them
+ *   disable   mask or remove the stack's services
+ *   keep      leave the stack alone
+ *
+ * rewrite is the default. Distro profiles can choose a strategy per stack.
+ */
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::init::InitSystem;
+use crate::utils::*;
+
+/*
+ * Interface name patterns for the platform's NICs.
+ */
+const PLATFORM_NICS: &[&str] = &["net*", "eth*"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    Rewrite,
+    Disable,
+    Keep,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Rewrite => "rewrite",
+            Self::Disable => "disable",
+            Self::Keep => "keep",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * The strategy for each stack, as given in a distro profile.
+ */
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Strategies {
+    pub ifupdown: Option<Strategy>,
+    pub netplan: Option<Strategy>,
+    pub networkmanager: Option<Strategy>,
+    pub networkd: Option<Strategy>,
+    pub dhcpcd: Option<Strategy>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stack {
+    Ifupdown,
+    Netplan,
+    NetworkManager,
+    Networkd,
+    Dhcpcd,
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Ifupdown => "ifupdown",
+            Self::Netplan => "netplan",
+            Self::NetworkManager => "networkmanager",
+            Self::Networkd => "networkd",
+            Self::Dhcpcd => "dhcpcd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Stack {
+    const ALL: &'static [Stack] = &[
+        Stack::Ifupdown,
+        Stack::Netplan,
+        Stack::NetworkManager,
+        Stack::Networkd,
+        Stack::Dhcpcd,
+    ];
+
+    /*
+     * Paths whose presence indicates the stack is installed.
+     */
+    fn markers(&self) -> &'static [&'static str] {
+        match self {
+            Self::Ifupdown => &["etc/network/interfaces"],
+            Self::Netplan => &["etc/netplan", "usr/sbin/netplan"],
+            Self::NetworkManager => &["usr/sbin/NetworkManager", "usr/bin/NetworkManager"],
+            Self::Networkd => &[
+                "usr/lib/systemd/systemd-networkd",
+                "lib/systemd/systemd-networkd",
+            ],
+            Self::Dhcpcd => &["sbin/dhcpcd", "usr/sbin/dhcpcd", "usr/bin/dhcpcd"],
+        }
+    }
+
+    /*
+     * The services that run the stack, by the name used by each init system.
+     */
+    fn services(&self) -> &'static [&'static str] {
+        match self {
+            Self::Ifupdown => &["networking"],
+            Self::Netplan => &[],
+            Self::NetworkManager => &["NetworkManager"],
+            Self::Networkd => &["systemd-networkd"],
+            Self::Dhcpcd => &["dhcpcd"],
+        }
+    }
+
+    fn detect(&self, zroot: &Path) -> bool {
+        self.markers().iter().any(|m| zroot.join(m).exists())
+    }
+
+    fn strategy(&self, s: &Strategies) -> Option<Strategy> {
+        match self {
+            Self::Ifupdown => s.ifupdown,
+            Self::Netplan => s.netplan,
+            Self::NetworkManager => s.networkmanager,
+            Self::Networkd => s.networkd,
+            Self::Dhcpcd => s.dhcpcd,
+        }
+    }
+
+    fn rewrite(&self, zroot: &Path) -> Result<()> {
+        match self {
+            Self::Ifupdown => {
+                let mut files = vec![zroot.join("etc/network/interfaces")];
+                let dir = zroot.join("etc/network/interfaces.d");
+                if exists(&dir) && !has_symlink_parent(zroot, &dir) {
+                    let mut entries: Vec<_> = fs::read_dir(&dir)
+                        .with_context(|| format!("failed to read {}", dir.display()))?
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|p| fs::symlink_metadata(p).is_ok_and(|m| m.is_file()))
+                        .collect();
+                    entries.sort();
+                    files.extend(entries);
+                }
+
+                for path in files {
+                    if !exists(&path) {
+                        continue;
+                    }
+                    let data = fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                    let stripped = strip_ifupdown(&data);
+                    if stripped != data {
+                        write_config(&path, stripped)?;
+                    }
+                }
+            }
+            Self::Netplan => {
+                remove_config_files(zroot, "etc/netplan")?;
+            }
+            Self::NetworkManager => {
+                let conf = zroot.join("etc/NetworkManager/conf.d");
+                if !exists(&conf) {
+                    mkdirp(&conf, 0, 0, 0o755)?;
+                }
+                let devices: Vec<_> = PLATFORM_NICS
+                    .iter()
+                    .map(|n| format!("interface-name:{}", n))
+                    .collect();
+                write_config(
+                    conf.join("90-triton-unmanaged.conf"),
+                    format!(
+                        "# Interfaces and resolv.conf are configured by the platform.\n\
+                         [main]\n\
+                         dns=none\n\n\
+                         [keyfile]\n\
+                         unmanaged-devices={}\n",
+                        devices.join(";")
+                    ),
+                )?;
+            }
+            Self::Networkd => {
+                let dir = zroot.join("etc/systemd/network");
+                if !exists(&dir) {
+                    mkdirp(&dir, 0, 0, 0o755)?;
+                }
+                write_config(
+                    dir.join("00-triton-unmanaged.network"),
+                    format!(
+                        "# Interfaces are configured by the platform.\n\
+                         [Match]\n\
+                         Name={}\n\n\
+                         [Link]\n\
+                         Unmanaged=yes\n",
+                        PLATFORM_NICS.join(" ")
+                    ),
+                )?;
+            }
+            Self::Dhcpcd => {
+                let conf = zroot.join("etc/dhcpcd.conf");
+                let line = format!("denyinterfaces {}", PLATFORM_NICS.join(" "));
+                let mut data = if exists(&conf) {
+                    fs::read_to_string(&conf)
+                        .with_context(|| format!("failed to read {}", conf.display()))?
+                } else {
+                    String::new()
+                };
+                if !data.lines().any(|l| l.trim() == line) {
+                    if !data.is_empty() && !data.ends_with('\n') {
+                        data.push('\n');
+                    }
+                    data.push_str("# Interfaces are configured by the platform.\n");
+                    data.push_str(&line);
+                    data.push('\n');
+                    write_config(&conf, data)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn disable(&self, zroot: &Path, init: Option<InitSystem>) -> Result<()> {
+        /*
+         * netplan only generates configuration for networkd or
+         * NetworkManager, so without its configuration it does nothing.
+         */
+        if *self == Self::Netplan {
+            return remove_config_files(zroot, "etc/netplan");
+        }
+
+        for svc in self.services() {
+            match init {
+                Some(InitSystem::Systemd) => {
+                    let unit = zroot.join(format!("etc/systemd/system/{}.service", svc));
+                    if fs::read_link(&unit).is_ok_and(|t| t == Path::new("/dev/null")) {
+                        continue;
+                    }
+                    if lexists(&unit) {
+                        remove_file(&unit)?;
+                    }
+                    let dir = zroot.join("etc/systemd/system");
+                    if !exists(&dir) {
+                        mkdirp(&dir, 0, 0, 0o755)?;
+                    }
+                    create_symlink("/dev/null", &unit, 0, 0)?;
+                }
+                Some(InitSystem::Openrc) => {
+                    for runlevel in &["boot", "default"] {
+                        let link = zroot.join(format!("etc/runlevels/{}/{}", runlevel, svc));
+                        if lexists(&link) {
+                            remove_file(&link)?;
+                        }
+                    }
+                }
+                Some(InitSystem::Runit) => {
+                    for dir in &["etc/runit/runsvdir/default", "etc/service", "var/service"] {
+                        let link = zroot.join(dir).join(svc);
+                        if lexists(&link) {
+                            remove_file(&link)?;
+                        }
+                    }
+                }
+                _ => {
+                    warn!(
+                        "cannot disable {} without a supported init system, rewriting its configuration instead",
+                        self
+                    );
+                    return self.rewrite(zroot);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_platform_nic(name: &str) -> bool {
+    PLATFORM_NICS
+        .iter()
+        .filter_map(|p| glob::Pattern::new(p).ok())
+        .any(|p| p.matches(name))
+}
+
+/*
+ * Remove the stanzas for platform NICs from an ifupdown interfaces file,
+ * leaving loopback and anything else alone. An iface or mapping stanza runs
+ * until the next stanza, and auto and allow-* lines may name several
+ * interfaces.
+ */
+fn strip_ifupdown(data: &str) -> String {
+    let mut out = String::new();
+    let mut skipping = false;
+
+    for line in data.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"iface") | Some(&"mapping") => {
+                skipping = words.get(1).is_some_and(|n| is_platform_nic(n));
+                if skipping {
+                    continue;
+                }
+            }
+            Some(w) if *w == "auto" || w.starts_with("allow-") || w.starts_with("no-") => {
+                skipping = false;
+                let kept: Vec<&str> = words[1..]
+                    .iter()
+                    .copied()
+                    .filter(|n| !is_platform_nic(n))
+                    .collect();
+                if kept.len() != words.len() - 1 {
+                    if !kept.is_empty() {
+                        out.push_str(&format!("{} {}\n", w, kept.join(" ")));
+                    }
+                    continue;
+                }
+            }
+            Some(&"source") | Some(&"source-directory") => skipping = false,
+            _ if skipping => continue,
+            _ => (),
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    out
+}
+
+/*
+ * Write a configuration file, replacing rather than following a symlink which
+ * may point outside of the image root.
+ */
+fn write_config<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let path = path.as_ref();
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) && lexists(path) {
+        remove_file(path)?;
+    }
+    create_file_contents(path, contents)
+}
+
+/*
+ * Remove the files in a configuration directory.
+ */
+fn remove_config_files(zroot: &Path, dir: &str) -> Result<()> {
+    let dir = zroot.join(dir);
+    if !exists(&dir) || has_symlink_parent(zroot, &dir) {
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(&dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+        if is_dir || !lexists(&path) {
+            continue;
+        }
+        remove_file(&path)?;
+    }
+
+    Ok(())
+}
+
+/*
+ * A network stack found in the image, and what was done about it.
+ */
+#[derive(Debug)]
+pub struct Neutralized {
+    pub stack: Stack,
+    pub strategy: Strategy,
+}
+
+pub fn neutralize<P: AsRef<Path>>(
+    zroot: P,
+    init: Option<InitSystem>,
+    strategies: &Strategies,
+) -> Result<Vec<Neutralized>> {
+    let zroot = zroot.as_ref();
+    let mut done = vec![];
+
+    for stack in Stack::ALL {
+        if !stack.detect(zroot) {
+            continue;
+        }
+        let strategy = stack.strategy(strategies).unwrap_or(Strategy::Rewrite);
+        info!("found {}, strategy {}", stack, strategy);
+
+        match strategy {
+            Strategy::Rewrite => stack.rewrite(zroot)?,
+            Strategy::Disable => stack.disable(zroot, init)?,
+            Strategy::Keep => (),
+        }
+
+        done.push(Neutralized {
+            stack: *stack,
+            strategy,
+        });
+    }
+
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for (path, contents) in files {
+            let path = zroot.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        zroot
+    }
+
+    fn contents(zroot: &Path, path: &str) -> Option<String> {
+        fs::read_to_string(zroot.join(path)).ok()
+    }
+
+    fn link(zroot: &Path, path: &str) -> Option<PathBuf> {
+        fs::read_link(zroot.join(path)).ok()
+    }
+
+    fn run(zroot: &Path, init: Option<InitSystem>, strategies: Strategies) -> Vec<Stack> {
+        neutralize(zroot, init, &strategies)
+            .unwrap()
+            .iter()
+            .map(|n| n.stack)
+            .collect()
+    }
+
+    const INTERFACES: &str = "\
+source /etc/network/interfaces.d/*
+
+# The loopback network interface
+auto lo eth0
+iface lo inet loopback
+
+allow-hotplug eth0
+iface eth0 inet dhcp
+    hostname zone
+
+iface br0 inet static
+    address 192.168.1.1/24
+    bridge_ports none
+
+mapping net1
+    script /usr/local/sbin/map
+";
+
+    #[test]
+    fn strip_stanzas() {
+        assert_eq!(
+            strip_ifupdown(INTERFACES),
+            "\
+source /etc/network/interfaces.d/*
+
+# The loopback network interface
+auto lo
+iface lo inet loopback
+
+iface br0 inet static
+    address 192.168.1.1/24
+    bridge_ports none
+
+"
+        );
+        assert_eq!(
+            strip_ifupdown("auto lo\niface lo inet loopback\n"),
+            "auto lo\niface lo inet loopback\n"
+        );
+    }
+
+    #[test]
+    fn ifupdown_rewrite() {
+        let zroot = root(
+            "net-ifupdown",
+            &[
+                ("etc/network/interfaces", INTERFACES),
+                (
+                    "etc/network/interfaces.d/net0",
+                    "auto net0\niface net0 inet6 auto\n",
+                ),
+            ],
+        );
+        let found = run(&zroot, None, Strategies::default());
+        let interfaces = contents(&zroot, "etc/network/interfaces").unwrap();
+        let net0 = contents(&zroot, "etc/network/interfaces.d/net0");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(found, [Stack::Ifupdown]);
+        assert!(interfaces.contains("iface lo inet loopback\n"));
+        assert!(interfaces.contains("iface br0 inet static\n"));
+        assert!(!interfaces.contains("eth0"));
+        assert!(!interfaces.contains("net1"));
+        assert_eq!(net0.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn ifupdown_disable() {
+        let zroot = root(
+            "net-ifupdown-disable",
+            &[
+                ("etc/network/interfaces", INTERFACES),
+                ("etc/systemd/system/.keep", ""),
+            ],
+        );
+        let strategies = Strategies {
+            ifupdown: Some(Strategy::Disable),
+            ..Default::default()
+        };
+        run(&zroot, Some(InitSystem::Systemd), strategies);
+        let interfaces = contents(&zroot, "etc/network/interfaces");
+        let unit = link(&zroot, "etc/systemd/system/networking.service");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(interfaces.as_deref(), Some(INTERFACES));
+        assert_eq!(unit, Some(PathBuf::from("/dev/null")));
+    }
+
+    #[test]
+    fn netplan() {
+        let yaml = "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n";
+        for strategy in &[Strategy::Rewrite, Strategy::Disable, Strategy::Keep] {
+            let zroot = root("net-netplan", &[("etc/netplan/50-cloud-init.yaml", yaml)]);
+            let strategies = Strategies {
+                netplan: Some(*strategy),
+                ..Default::default()
+            };
+            let found = run(&zroot, Some(InitSystem::Systemd), strategies);
+            let config = contents(&zroot, "etc/netplan/50-cloud-init.yaml");
+            fs::remove_dir_all(&zroot).unwrap();
+
+            assert_eq!(found, [Stack::Netplan]);
+            match strategy {
+                Strategy::Keep => assert_eq!(config.as_deref(), Some(yaml)),
+                _ => assert_eq!(config, None),
+            }
+        }
+    }
+
+    #[test]
+    fn networkmanager() {
+        let files = &[
+            ("usr/sbin/NetworkManager", ""),
+            ("etc/systemd/system/.keep", ""),
+        ];
+        let conf = "etc/NetworkManager/conf.d/90-triton-unmanaged.conf";
+
+        let zroot = root("net-nm", files);
+        run(&zroot, Some(InitSystem::Systemd), Strategies::default());
+        let rewritten = contents(&zroot, conf).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(rewritten.contains("dns=none\n"));
+        assert!(rewritten.contains("unmanaged-devices=interface-name:net*;interface-name:eth*\n"));
+        assert!(!rewritten.contains(":lo"));
+
+        let zroot = root("net-nm-disable", files);
+        let strategies = Strategies {
+            networkmanager: Some(Strategy::Disable),
+            ..Default::default()
+        };
+        run(&zroot, Some(InitSystem::Systemd), strategies);
+        let unit = link(&zroot, "etc/systemd/system/NetworkManager.service");
+        let written = contents(&zroot, conf);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(unit, Some(PathBuf::from("/dev/null")));
+        assert_eq!(written, None);
+
+        let zroot = root("net-nm-keep", files);
+        let strategies = Strategies {
+            networkmanager: Some(Strategy::Keep),
+            ..Default::default()
+        };
+        run(&zroot, Some(InitSystem::Systemd), strategies);
+        let unit = link(&zroot, "etc/systemd/system/NetworkManager.service");
+        let written = contents(&zroot, conf);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(unit, None);
+        assert_eq!(written, None);
+    }
+
+    #[test]
+    fn networkd() {
+        let files = &[
+            ("lib/systemd/systemd-networkd", ""),
+            (
+                "etc/systemd/network/80-dhcp.network",
+                "[Match]\nName=eth*\n",
+            ),
+        ];
+        let zroot = root("net-networkd", files);
+        run(&zroot, Some(InitSystem::Systemd), Strategies::default());
+        let rewritten = contents(&zroot, "etc/systemd/network/00-triton-unmanaged.network");
+        let original = contents(&zroot, "etc/systemd/network/80-dhcp.network");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        let rewritten = rewritten.unwrap();
+        assert!(rewritten.contains("Name=net* eth*\n"));
+        assert!(rewritten.contains("Unmanaged=yes\n"));
+        assert!(original.is_some());
+
+        let zroot = root("net-networkd-disable", files);
+        let strategies = Strategies {
+            networkd: Some(Strategy::Disable),
+            ..Default::default()
+        };
+        run(&zroot, Some(InitSystem::Systemd), strategies);
+        let unit = link(&zroot, "etc/systemd/system/systemd-networkd.service");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(unit, Some(PathBuf::from("/dev/null")));
+    }
+
+    #[test]
+    fn dhcpcd() {
+        let files = &[
+            ("sbin/dhcpcd", ""),
+            ("etc/dhcpcd.conf", "hostname\nclientid"),
+            ("etc/runlevels/default/dhcpcd", ""),
+        ];
+        let zroot = root("net-dhcpcd", files);
+        run(&zroot, Some(InitSystem::Openrc), Strategies::default());
+        run(&zroot, Some(InitSystem::Openrc), Strategies::default());
+        let conf = contents(&zroot, "etc/dhcpcd.conf");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            conf.as_deref(),
+            Some(
+                "hostname\nclientid\n\
+                 # Interfaces are configured by the platform.\n\
+                 denyinterfaces net* eth*\n"
+            )
+        );
+
+        let zroot = root("net-dhcpcd-disable", files);
+        let strategies = Strategies {
+            dhcpcd: Some(Strategy::Disable),
+            ..Default::default()
+        };
+        run(&zroot, Some(InitSystem::Openrc), strategies);
+        let enabled = zroot.join("etc/runlevels/default/dhcpcd").exists();
+        let conf = contents(&zroot, "etc/dhcpcd.conf");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(!enabled);
+        assert_eq!(conf.as_deref(), Some("hostname\nclientid"));
+    }
+}
@@ -0,0 +1,681 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Match the image's packages against a snapshot of the OSV vulnerability
+ * database (https://ossf.github.io/osv-schema/), such as the per-ecosystem
+ * exports at https://osv-vulnerabilities.storage.googleapis.com. Nothing is
+ * fetched: the snapshot is read from a file, so builds can run offline and
+ * are reproducible for a given snapshot.
+ *
+ * Distro advisories are published against source package names, so an entry
+ * matches a package by either its name or its source package.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::packages::{Inventory, Manager, Package};
+use crate::version;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /*
+     * Parse the severity names used by the distro trackers: Debian urgencies,
+     * Ubuntu priorities, and Red Hat and SUSE ratings.
+     */
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "unimportant" | "negligible" | "low" => Some(Self::Low),
+            "medium" | "moderate" => Some(Self::Medium),
+            "high" | "important" => Some(Self::High),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+
+    fn from_score(score: f64) -> Option<Self> {
+        match score {
+            s if s >= 9.0 => Some(Self::Critical),
+            s if s >= 7.0 => Some(Self::High),
+            s if s >= 4.0 => Some(Self::Medium),
+            s if s > 0.0 => Some(Self::Low),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Score {
+    #[serde(rename = "type")]
+    kind: String,
+    score: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AffectedPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Event {
+    introduced: Option<String>,
+    fixed: Option<String>,
+    last_affected: Option<String>,
+}
+
+impl Event {
+    fn version(&self) -> &str {
+        self.introduced
+            .as_deref()
+            .or(self.fixed.as_deref())
+            .or(self.last_affected.as_deref())
+            .unwrap_or("")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Range {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Affected {
+    package: Option<AffectedPackage>,
+    #[serde(default)]
+    ranges: Vec<Range>,
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    severity: Vec<Score>,
+    ecosystem_specific: Option<Value>,
+    database_specific: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Vuln {
+    id: String,
+    summary: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    severity: Vec<Score>,
+    #[serde(default)]
+    affected: Vec<Affected>,
+    database_specific: Option<Value>,
+}
+
+pub struct Database {
+    path: String,
+    vulns: Vec<Vuln>,
+}
+
+impl Database {
+    /*
+     * Load a snapshot. The file may hold a single OSV entry, an array of
+     * entries, an object with a "vulns" array (as returned by the OSV query
+     * API), or any number of these concatenated, such as one entry per line.
+     */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let mut values = vec![];
+        for value in serde_json::Deserializer::from_str(&data).into_iter::<Value>() {
+            let value = value.with_context(|| format!("failed to parse {}", path.display()))?;
+            match value {
+                Value::Array(a) => values.extend(a),
+                Value::Object(mut o) if o.contains_key("vulns") => match o.remove("vulns") {
+                    Some(Value::Array(a)) => values.extend(a),
+                    _ => return Err(anyhow!("{}: \"vulns\" is not an array", path.display())),
+                },
+                v => values.push(v),
+            }
+        }
+
+        let mut vulns = vec![];
+        for v in values {
+            let vuln: Vuln = serde_json::from_value(v)
+                .with_context(|| format!("{} is not an OSV database", path.display()))?;
+            if vuln.withdrawn.is_none() {
+                vulns.push(vuln);
+            }
+        }
+        Ok(Database {
+            path: path.display().to_string(),
+            vulns,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.vulns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vulns.is_empty()
+    }
+}
+
+/*
+ * The OSV ecosystem of the image's distro release, from os-release ID and
+ * VERSION_ID. Entries for the ecosystem and any of its sub-ecosystems (e.g.
+ * "Ubuntu:22.04:LTS" for "Ubuntu:22.04") are matched.
+ */
+pub fn ecosystem(id: &str, version_id: &str) -> Option<String> {
+    let mut parts = version_id.split('.');
+    let major = parts.next().unwrap_or("");
+    let minor = parts.next();
+    let with = |name: &str, v: &str| {
+        if v.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}:{}", name, v)
+        }
+    };
+
+    Some(match id {
+        "debian" => with("Debian", major),
+        "ubuntu" => with("Ubuntu", version_id),
+        "alpine" => match minor {
+            Some(minor) => format!("Alpine:v{}.{}", major, minor),
+            None => "Alpine".to_string(),
+        },
+        "rocky" => with("Rocky Linux", major),
+        "almalinux" => with("AlmaLinux", major),
+        "rhel" => "Red Hat".to_string(),
+        "mageia" => with("Mageia", version_id),
+        "opensuse-leap" => format!("openSUSE:Leap {}", version_id),
+        "opensuse-tumbleweed" => "openSUSE:Tumbleweed".to_string(),
+        "sles" => match minor {
+            Some(sp) if sp != "0" => format!("SUSE:Linux Enterprise Server {} SP{}", major, sp),
+            _ => format!("SUSE:Linux Enterprise Server {}", major),
+        },
+        "wolfi" => "Wolfi".to_string(),
+        "chainguard" => "Chainguard".to_string(),
+        _ => return None,
+    })
+}
+
+fn ecosystem_matches(candidate: &str, ecosystem: &str) -> bool {
+    candidate == ecosystem
+        || candidate
+            .strip_prefix(ecosystem)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/*
+ * Round up to one decimal place, as defined by the CVSS v3.1 specification.
+ */
+fn roundup(x: f64) -> f64 {
+    let i = (x * 100_000.0).round() as i64;
+    if i % 10_000 == 0 {
+        i as f64 / 100_000.0
+    } else {
+        ((i / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+/*
+ * The base score of a CVSS v3 vector, e.g.
+ * "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H".
+ */
+fn cvss3(vector: &str) -> Option<f64> {
+    let mut parts = vector.split('/');
+    if !parts.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    let m: HashMap<&str, &str> = parts.filter_map(|p| p.split_once(':')).collect();
+
+    let changed = match *m.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match *m.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match *m.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (*m.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match *m.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let cia = |k: &str| match *m.get(k)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let iss: f64 = 1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?);
+
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let base = if changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    Some(roundup(base.min(10.0)))
+}
+
+fn named_severity(v: &Option<Value>) -> Option<Severity> {
+    let v = v.as_ref()?;
+    ["severity", "urgency"]
+        .iter()
+        .find_map(|k| v.get(k).and_then(Value::as_str).and_then(Severity::parse))
+}
+
+/*
+ * The severity of an entry for the distro. The distro's own rating is
+ * preferred, as it accounts for how the package is built and used; failing
+ * that the CVSS v3 base score is used.
+ */
+fn severity(vuln: &Vuln, affected: &Affected) -> (Option<Severity>, Option<f64>) {
+    let scores = affected.severity.iter().chain(vuln.severity.iter());
+    let score = scores
+        .clone()
+        .filter(|s| s.kind == "CVSS_V3")
+        .find_map(|s| cvss3(&s.score));
+
+    let named = named_severity(&affected.ecosystem_specific)
+        .or_else(|| named_severity(&affected.database_specific))
+        .or_else(|| named_severity(&vuln.database_specific))
+        .or_else(|| {
+            scores
+                .filter(|s| !s.kind.starts_with("CVSS"))
+                .find_map(|s| Severity::parse(&s.score))
+        });
+
+    (
+        named.or_else(|| score.and_then(Severity::from_score)),
+        score,
+    )
+}
+
+/*
+ * Evaluate an ECOSYSTEM range as described by the OSV schema. Returns
+ * whether the version is affected, and the lowest fixed version above it.
+ */
+fn in_range(manager: Manager, events: &[Event], v: &str) -> (bool, Option<String>) {
+    let cmp = |a: &str, b: &str| version::compare(manager, a, b);
+
+    let mut events: Vec<&Event> = events.iter().collect();
+    events.sort_by(
+        |a, b| match (a.introduced.as_deref(), b.introduced.as_deref()) {
+            (Some("0"), Some("0")) => Ordering::Equal,
+            (Some("0"), _) => Ordering::Less,
+            (_, Some("0")) => Ordering::Greater,
+            _ => cmp(a.version(), b.version()),
+        },
+    );
+
+    let mut affected = false;
+    for e in &events {
+        if let Some(i) = &e.introduced {
+            if i == "0" || cmp(v, i) != Ordering::Less {
+                affected = true;
+            }
+        } else if let Some(f) = &e.fixed {
+            if cmp(v, f) != Ordering::Less {
+                affected = false;
+            }
+        } else if let Some(l) = &e.last_affected {
+            if cmp(v, l) == Ordering::Greater {
+                affected = false;
+            }
+        }
+    }
+
+    let fixed = events
+        .iter()
+        .filter_map(|e| e.fixed.as_deref())
+        .find(|f| cmp(v, f) == Ordering::Less)
+        .map(str::to_string);
+    (affected, fixed)
+}
+
+fn affects(affected: &Affected, p: &Package) -> Option<Option<String>> {
+    if affected
+        .versions
+        .iter()
+        .any(|v| version::compare(p.manager, v, &p.version) == Ordering::Equal)
+    {
+        return Some(None);
+    }
+    for range in affected.ranges.iter().filter(|r| r.kind == "ECOSYSTEM") {
+        if let (true, fixed) = in_range(p.manager, &range.events, &p.version) {
+            return Some(fixed);
+        }
+    }
+    None
+}
+
+pub struct Finding {
+    pub id: String,
+    pub aliases: Vec<String>,
+    pub summary: Option<String>,
+    pub package: String,
+    pub version: String,
+    pub fixed: Option<String>,
+    pub severity: Option<Severity>,
+    pub score: Option<f64>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = self
+            .severity
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        write!(
+            f,
+            "{} ({}) in {} {}",
+            self.id, severity, self.package, self.version
+        )?;
+        match &self.fixed {
+            Some(fixed) => write!(f, ", fixed in {}", fixed),
+            None => write!(f, ", no fix available"),
+        }
+    }
+}
+
+pub struct Report {
+    pub database: String,
+    pub ecosystem: String,
+    pub entries: usize,
+    pub scanned: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /*
+     * The number of findings of the given severity, or of unknown severity.
+     */
+    pub fn count(&self, severity: Option<Severity>) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    pub fn at_least(&self, threshold: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity.is_some_and(|s| s >= threshold))
+            .count()
+    }
+
+    pub fn severities(&self) -> Value {
+        json!({
+            "critical": self.count(Some(Severity::Critical)),
+            "high": self.count(Some(Severity::High)),
+            "medium": self.count(Some(Severity::Medium)),
+            "low": self.count(Some(Severity::Low)),
+            "unknown": self.count(None),
+        })
+    }
+
+    pub fn document(&self, name: &str, version: &str) -> Value {
+        let findings: Vec<_> = self
+            .findings
+            .iter()
+            .map(|f| {
+                json!({
+                    "id": f.id,
+                    "aliases": f.aliases,
+                    "summary": f.summary,
+                    "severity": f.severity.map(|s| s.to_string()),
+                    "cvss_score": f.score,
+                    "package": f.package,
+                    "version": f.version,
+                    "fixed_version": f.fixed,
+                })
+            })
+            .collect();
+        json!({
+            "name": name,
+            "version": version,
+            "database": self.database,
+            "database_entries": self.entries,
+            "ecosystem": self.ecosystem,
+            "packages_scanned": self.scanned,
+            "severities": self.severities(),
+            "findings": findings,
+        })
+    }
+}
+
+pub fn scan(db: &Database, ecosystem: &str, inv: &Inventory) -> Report {
+    let mut by_name: HashMap<&str, Vec<&Package>> = HashMap::new();
+    for p in &inv.packages {
+        by_name.entry(&p.name).or_default().push(p);
+        if let Some(s) = p.source.as_deref().filter(|s| *s != p.name) {
+            by_name.entry(s).or_default().push(p);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut findings = vec![];
+    for vuln in &db.vulns {
+        for affected in &vuln.affected {
+            let ap = match &affected.package {
+                Some(ap) if ecosystem_matches(&ap.ecosystem, ecosystem) => ap,
+                _ => continue,
+            };
+            for p in by_name.get(ap.name.as_str()).into_iter().flatten() {
+                let fixed = match affects(affected, p) {
+                    Some(fixed) => fixed,
+                    None => continue,
+                };
+                if !seen.insert((&vuln.id, &p.name, &p.version)) {
+                    continue;
+                }
+                let (severity, score) = severity(vuln, affected);
+                findings.push(Finding {
+                    id: vuln.id.clone(),
+                    aliases: vuln.aliases.clone(),
+                    summary: vuln.summary.clone(),
+                    package: p.name.clone(),
+                    version: p.version.clone(),
+                    fixed,
+                    severity,
+                    score,
+                });
+            }
+        }
+    }
+
+    findings.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.package.cmp(&b.package))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    Report {
+        database: db.path.clone(),
+        ecosystem: ecosystem.to_string(),
+        entries: db.len(),
+        scanned: inv.packages.len(),
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn introduced(v: &str) -> Event {
+        Event {
+            introduced: Some(v.to_string()),
+            ..Event::default()
+        }
+    }
+
+    fn fixed(v: &str) -> Event {
+        Event {
+            fixed: Some(v.to_string()),
+            ..Event::default()
+        }
+    }
+
+    fn last_affected(v: &str) -> Event {
+        Event {
+            last_affected: Some(v.to_string()),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn range() {
+        let events = [introduced("0"), fixed("1.2-1")];
+        let cases = [
+            ("1.1-1", true, Some("1.2-1")),
+            ("1.2-0~deb12u1", true, Some("1.2-1")),
+            ("1.2-1", false, None),
+            ("1:1.0-1", false, None),
+        ];
+        for (v, want, fix) in &cases {
+            let (affected, fixed) = in_range(Manager::Dpkg, &events, v);
+            assert_eq!(affected, *want, "{}", v);
+            assert_eq!(fixed.as_deref(), *fix, "{}", v);
+        }
+    }
+
+    #[test]
+    fn multiple_ranges() {
+        /*
+         * Events need not be in order.
+         */
+        let events = [
+            fixed("2.3"),
+            introduced("2.0"),
+            introduced("0"),
+            fixed("1.2"),
+        ];
+        let cases = [
+            ("1.0", true, Some("1.2")),
+            ("1.5", false, Some("2.3")),
+            ("2.0", true, Some("2.3")),
+            ("2.3", false, None),
+        ];
+        for (v, want, fix) in &cases {
+            let (affected, fixed) = in_range(Manager::Rpm, &events, v);
+            assert_eq!(affected, *want, "{}", v);
+            assert_eq!(fixed.as_deref(), *fix, "{}", v);
+        }
+    }
+
+    #[test]
+    fn range_last_affected() {
+        let events = [introduced("1.0-r0"), last_affected("1.5-r2")];
+        let cases = [
+            ("0.9-r0", false),
+            ("1.0-r0", true),
+            ("1.5-r2", true),
+            ("1.5-r3", false),
+        ];
+        for (v, want) in &cases {
+            assert_eq!(in_range(Manager::Apk, &events, v), (*want, None), "{}", v);
+        }
+    }
+
+    #[test]
+    fn round_up() {
+        assert_eq!(roundup(4.0), 4.0);
+        assert_eq!(roundup(4.02), 4.1);
+        assert_eq!(roundup(4.000_001), 4.0);
+        assert_eq!(roundup(1.5335), 1.6);
+    }
+
+    /*
+     * Base scores of common vectors, as the CVSS v3.1 specification defines.
+     */
+    #[test]
+    fn cvss() {
+        let cases = [
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H", 9.8),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H", 10.0),
+            ("CVSS:3.0/AV:N/AC:L/PR:L/UI:N/S:U/C:H/I:H/A:H", 8.8),
+            ("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:H/A:H", 7.8),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H", 7.5),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N", 6.1),
+            ("CVSS:3.1/AV:N/AC:H/PR:N/UI:N/S:U/C:H/I:N/A:N", 5.9),
+            ("CVSS:3.1/AV:L/AC:L/PR:H/UI:N/S:C/C:L/I:N/A:N", 3.2),
+            ("CVSS:3.1/AV:P/AC:H/PR:H/UI:R/S:U/C:L/I:N/A:N", 1.6),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N", 0.0),
+        ];
+        for (vector, score) in &cases {
+            assert_eq!(cvss3(vector), Some(*score), "{}", vector);
+        }
+
+        for vector in &[
+            "AV:N/AC:L/Au:N/C:P/I:P/A:P",
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/C:H/I:H/A:H",
+            "CVSS:3.1/AV:X/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+        ] {
+            assert_eq!(cvss3(vector), None, "{}", vector);
+        }
+    }
+}
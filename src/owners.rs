@@ -0,0 +1,311 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Tarballs made without root, by rootless container tools or by tar run as
+ * an ordinary user, record the builder's uid and gid as the owner of every
+ * file. Extracted as is, the image's /etc, /usr and so on would belong to
+ * whichever guest account has that id. This remaps owners across the image
+ * root, either by explicit uid and gid maps or by squashing every owner that
+ * is not a system account of the image to root.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::utils::*;
+
+/*
+ * Used when the image has no /etc/login.defs, or it does not set them.
+ */
+const DEFAULT_UID_MIN: u32 = 1000;
+const DEFAULT_GID_MIN: u32 = 1000;
+
+/*
+ * Parse a map given on the command line, "FROM:TO".
+ */
+pub fn parse_map(s: &str) -> Result<(u32, u32)> {
+    let (from, to) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid id map \"{}\", expected FROM:TO", s))?;
+    let id = |v: &str| {
+        v.parse::<u32>()
+            .map_err(|_| anyhow!("invalid id \"{}\" in map \"{}\"", v, s))
+    };
+    Ok((id(from)?, id(to)?))
+}
+
+/*
+ * The ids that are kept when squashing.
+ */
+struct System {
+    uids: BTreeSet<u32>,
+    gids: BTreeSet<u32>,
+}
+
+fn login_defs(data: &str, key: &str) -> Option<u32> {
+    data.lines().rev().find_map(|l| {
+        let mut fields = l.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(k), Some(v)) if k == key => v.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/*
+ * The ids of the third field of a passwd or group file that are below min,
+ * and those of the overflow accounts, such as nobody.
+ */
+fn ids(data: &str, min: u32) -> BTreeSet<u32> {
+    data.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split(':').nth(2))
+        .filter_map(|id| id.parse::<u32>().ok())
+        .filter(|id| *id < min || *id == 65534)
+        .collect()
+}
+
+fn system_accounts(zroot: &Path) -> Result<System> {
+    let passwd = read_in_root(zroot, "etc/passwd")?
+        .ok_or_else(|| anyhow!("the image has no /etc/passwd to find its system accounts in"))?;
+    let group = read_in_root(zroot, "etc/group")?.unwrap_or_default();
+    let defs = read_in_root(zroot, "etc/login.defs")?.unwrap_or_default();
+
+    let uid_min = login_defs(&defs, "UID_MIN").unwrap_or(DEFAULT_UID_MIN);
+    let gid_min = login_defs(&defs, "GID_MIN").unwrap_or(DEFAULT_GID_MIN);
+    let mut system = System {
+        uids: ids(&passwd, uid_min),
+        gids: ids(&group, gid_min),
+    };
+    system.uids.insert(0);
+    system.gids.insert(0);
+    Ok(system)
+}
+
+#[derive(Debug, Default)]
+pub struct Mapping {
+    pub uids: HashMap<u32, u32>,
+    pub gids: HashMap<u32, u32>,
+    pub squash: bool,
+}
+
+impl Mapping {
+    pub fn is_empty(&self) -> bool {
+        self.uids.is_empty() && self.gids.is_empty() && !self.squash
+    }
+}
+
+/*
+ * The owners that were changed, and how many paths each change applied to.
+ * Keys are ("uid" or "gid", from, to).
+ */
+#[derive(Debug, Default)]
+pub struct Report {
+    pub paths: u64,
+    pub remapped: BTreeMap<(&'static str, u32, u32), u64>,
+}
+
+pub fn remap<P: AsRef<Path>>(zroot: P, mapping: &Mapping) -> Result<Report> {
+    let zroot = zroot.as_ref();
+    let system = if mapping.squash {
+        let s = system_accounts(zroot)?;
+        debug!("keeping system uids {:?} and gids {:?}", s.uids, s.gids);
+        Some(s)
+    } else {
+        None
+    };
+
+    let map = |id: u32, explicit: &HashMap<u32, u32>, kept: Option<&BTreeSet<u32>>| match (
+        explicit.get(&id),
+        kept,
+    ) {
+        (Some(to), _) => *to,
+        (None, Some(kept)) if !kept.contains(&id) => 0,
+        _ => id,
+    };
+
+    let mut report = Report::default();
+    for entry in WalkDir::new(zroot).min_depth(1) {
+        let entry = entry.with_context(|| format!("failed to walk {}", zroot.display()))?;
+        let md = entry
+            .metadata()
+            .with_context(|| format!("failed to stat {}", entry.path().display()))?;
+        let (uid, gid) = (md.uid(), md.gid());
+        let new_uid = map(uid, &mapping.uids, system.as_ref().map(|s| &s.uids));
+        let new_gid = map(gid, &mapping.gids, system.as_ref().map(|s| &s.gids));
+        if (new_uid, new_gid) == (uid, gid) {
+            continue;
+        }
+
+        /*
+         * chown clears the setuid and setgid bits, so the mode is set again.
+         */
+        change_perms(entry.path(), new_uid, new_gid, md.mode() & 0o7777)?;
+        report.paths += 1;
+        if new_uid != uid {
+            *report.remapped.entry(("uid", uid, new_uid)).or_default() += 1;
+        }
+        if new_gid != gid {
+            *report.remapped.entry(("gid", gid, new_gid)).or_default() += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::{chown, PermissionsExt};
+    use std::path::PathBuf;
+
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for (path, contents) in files {
+            let path = zroot.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        zroot
+    }
+
+    const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+# systemd-network:x:998:998::/:/usr/sbin/nologin
+postgres:x:500:500::/var/lib/postgresql:/bin/bash
+builder:x:1000:1000::/home/builder:/bin/bash
+nobody:x:65534:65534:nobody:/nonexistent:/usr/sbin/nologin
+";
+
+    const GROUP: &str = "\
+root:x:0:
+daemon:x:1:
+postgres:x:500:
+builder:x:1000:
+nogroup:x:65534:
+";
+
+    #[test]
+    fn maps() {
+        assert_eq!(parse_map("1000:0").unwrap(), (1000, 0));
+        assert_eq!(parse_map("0:4294967295").unwrap(), (0, u32::MAX));
+        for bad in &[
+            "1000",
+            "a:b",
+            "1000:",
+            ":0",
+            "-1:0",
+            "1:2:3",
+            "4294967296:0",
+        ] {
+            assert!(parse_map(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn system_ids() {
+        let zroot = root(
+            "owners-defaults",
+            &[("etc/passwd", PASSWD), ("etc/group", GROUP)],
+        );
+        let system = system_accounts(&zroot).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(
+            system.uids.into_iter().collect::<Vec<_>>(),
+            [0, 1, 500, 65534]
+        );
+        assert_eq!(
+            system.gids.into_iter().collect::<Vec<_>>(),
+            [0, 1, 500, 65534]
+        );
+
+        /*
+         * The last setting in login.defs wins.
+         */
+        let zroot = root(
+            "owners-login-defs",
+            &[
+                ("etc/passwd", PASSWD),
+                ("etc/group", GROUP),
+                (
+                    "etc/login.defs",
+                    "UID_MIN 1000\n#UID_MIN 100\nUID_MIN\t\t 500\nGID_MIN 2000\n",
+                ),
+            ],
+        );
+        let system = system_accounts(&zroot).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(system.uids.into_iter().collect::<Vec<_>>(), [0, 1, 65534]);
+        assert_eq!(
+            system.gids.into_iter().collect::<Vec<_>>(),
+            [0, 1, 500, 1000, 65534]
+        );
+
+        let zroot = root("owners-no-passwd", &[]);
+        let err = system_accounts(&zroot);
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn squash() {
+        let zroot = root(
+            "owners-squash",
+            &[
+                ("etc/passwd", PASSWD),
+                ("etc/group", GROUP),
+                ("usr/bin/passwd", ""),
+                ("usr/bin/wall", ""),
+                ("var/lib/postgresql/data", ""),
+                ("tmp/nobody", ""),
+            ],
+        );
+        let set = |path: &str, uid: u32, gid: u32, mode: u32| {
+            let path = zroot.join(path);
+            chown(&path, Some(uid), Some(gid)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        set("usr/bin/passwd", 1000, 1000, 0o4755);
+        set("usr/bin/wall", 1000, 5, 0o2755);
+        set("var/lib/postgresql/data", 500, 500, 0o600);
+        set("tmp/nobody", 65534, 65534, 0o644);
+
+        let mapping = Mapping {
+            gids: [(5, 4)].iter().copied().collect(),
+            squash: true,
+            ..Default::default()
+        };
+        let report = remap(&zroot, &mapping).unwrap();
+        let stat = |path: &str| {
+            let md = fs::metadata(zroot.join(path)).unwrap();
+            (md.uid(), md.gid(), md.mode() & 0o7777)
+        };
+        let passwd = stat("usr/bin/passwd");
+        let wall = stat("usr/bin/wall");
+        let data = stat("var/lib/postgresql/data");
+        let nobody = stat("tmp/nobody");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(passwd, (0, 0, 0o4755));
+        assert_eq!(wall, (0, 4, 0o2755));
+        assert_eq!(data, (500, 500, 0o600));
+        assert_eq!(nobody, (65534, 65534, 0o644));
+        assert_eq!(report.paths, 2);
+        assert_eq!(report.remapped.get(&("uid", 1000, 0)), Some(&2));
+        assert_eq!(report.remapped.get(&("gid", 1000, 0)), Some(&1));
+        assert_eq!(report.remapped.get(&("gid", 5, 4)), Some(&1));
+    }
+}
@@ -0,0 +1,636 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The packages installed in an image, read directly from the package
+ * manager's database in the image root. Nothing in the image is executed, so
+ * this works for any distro on any build host.
+ */
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::rpmdb;
+use crate::utils::*;
+
+const DPKG_STATUS: &str = "var/lib/dpkg/status";
+const APK_INSTALLED: &str = "lib/apk/db/installed";
+const PACMAN_LOCAL: &str = "var/lib/pacman/local";
+const XBPS_PKGDB: &str = "var/db/xbps/pkgdb-0.38.plist";
+
+/*
+ * Where the rpm database may be, newest layout first. /var/lib/rpm is often a
+ * symlink to /usr/lib/sysimage/rpm, so only the first one found is read.
+ */
+const RPMDB: &[&str] = &[
+    "usr/lib/sysimage/rpm/rpmdb.sqlite",
+    "var/lib/rpm/rpmdb.sqlite",
+    "usr/lib/sysimage/rpm/Packages.db",
+    "var/lib/rpm/Packages.db",
+    "var/lib/rpm/Packages",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Manager {
+    Dpkg,
+    Rpm,
+    Apk,
+    Pacman,
+    Xbps,
+}
+
+impl Manager {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dpkg" => Some(Self::Dpkg),
+            "rpm" => Some(Self::Rpm),
+            "apk" => Some(Self::Apk),
+            "pacman" => Some(Self::Pacman),
+            "xbps" => Some(Self::Xbps),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Manager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Dpkg => "dpkg",
+            Self::Rpm => "rpm",
+            Self::Apk => "apk",
+            Self::Pacman => "pacman",
+            Self::Xbps => "xbps",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Package {
+    pub manager: Manager,
+    pub name: String,
+    /*
+     * The full version, including any epoch and release, in the package
+     * manager's own format.
+     */
+    pub version: String,
+    pub arch: Option<String>,
+    /*
+     * The source package the package was built from, if it is different.
+     */
+    pub source: Option<String>,
+    pub license: Option<String>,
+}
+
+impl Package {
+    pub fn new(manager: Manager, name: &str, version: &str) -> Self {
+        Package {
+            manager,
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: None,
+            source: None,
+            license: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Inventory {
+    /*
+     * The databases that were read, as paths in the image.
+     */
+    pub databases: Vec<String>,
+    pub packages: Vec<Package>,
+}
+
+/*
+ * Split a file of "Key: value" stanzas separated by blank lines. Lines
+ * starting with whitespace continue the previous value.
+ */
+fn stanzas(data: &str, sep: char) -> Vec<Vec<(String, String)>> {
+    let mut out = vec![];
+    let mut cur: Vec<(String, String)> = vec![];
+
+    for line in data.lines() {
+        if line.trim().is_empty() {
+            if !cur.is_empty() {
+                out.push(std::mem::take(&mut cur));
+            }
+            continue;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, v)) = cur.last_mut() {
+                v.push('\n');
+                v.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some((k, v)) = line.split_once(sep) {
+            cur.push((k.to_string(), v.trim().to_string()));
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+fn field<'a>(stanza: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    stanza
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn read_dpkg(data: &str) -> Vec<Package> {
+    let mut pkgs = vec![];
+    for s in stanzas(data, ':') {
+        let installed = field(&s, "Status").is_some_and(|st| st.ends_with(" installed"));
+        let (name, version) = match (field(&s, "Package"), field(&s, "Version")) {
+            (Some(n), Some(v)) if installed => (n, v),
+            _ => continue,
+        };
+        let mut p = Package::new(Manager::Dpkg, name, version);
+        p.arch = field(&s, "Architecture").map(|a| a.to_string());
+        /*
+         * "Source: glibc (2.36-9)" when the source version differs.
+         */
+        p.source = field(&s, "Source")
+            .and_then(|src| src.split_whitespace().next())
+            .filter(|src| *src != name)
+            .map(|src| src.to_string());
+        pkgs.push(p);
+    }
+    pkgs
+}
+
+fn read_apk(data: &str) -> Vec<Package> {
+    let mut pkgs = vec![];
+    for s in stanzas(data, ':') {
+        let (name, version) = match (field(&s, "P"), field(&s, "V")) {
+            (Some(n), Some(v)) => (n, v),
+            _ => continue,
+        };
+        let mut p = Package::new(Manager::Apk, name, version);
+        p.arch = field(&s, "A").map(|a| a.to_string());
+        p.license = field(&s, "L").map(|l| l.to_string());
+        p.source = field(&s, "o").filter(|o| *o != name).map(|o| o.to_string());
+        pkgs.push(p);
+    }
+    pkgs
+}
+
+/*
+ * A pacman desc file is a list of "%KEY%" lines, each followed by its values,
+ * one per line, and a blank line.
+ */
+fn read_pacman_desc(data: &str) -> Option<Package> {
+    let mut key = "";
+    let mut values: Vec<(&str, Vec<&str>)> = vec![];
+    for line in data.lines() {
+        if line.starts_with('%') && line.ends_with('%') && line.len() > 2 {
+            key = &line[1..line.len() - 1];
+            values.push((key, vec![]));
+        } else if !line.is_empty() && !key.is_empty() {
+            if let Some((_, v)) = values.last_mut() {
+                v.push(line);
+            }
+        }
+    }
+    let get = |k: &str| {
+        values
+            .iter()
+            .find(|(key, _)| *key == k)
+            .map(|(_, v)| v.as_slice())
+            .unwrap_or_default()
+    };
+
+    let mut p = Package::new(
+        Manager::Pacman,
+        get("NAME").first()?,
+        get("VERSION").first()?,
+    );
+    p.arch = get("ARCH").first().map(|a| a.to_string());
+    let licenses = get("LICENSE");
+    if !licenses.is_empty() {
+        p.license = Some(licenses.join(" AND "));
+    }
+    p.source = get("BASE")
+        .first()
+        .filter(|b| **b != p.name)
+        .map(|b| b.to_string());
+    Some(p)
+}
+
+#[derive(Debug)]
+enum Plist {
+    Dict(Vec<(String, Plist)>),
+    String(String),
+    /*
+     * Arrays, numbers, booleans and data, none of which are needed.
+     */
+    Other,
+}
+
+impl Plist {
+    fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Plist::Dict(d) => d.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self {
+            Plist::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/*
+ * Just enough of an XML property list parser for the xbps package database.
+ */
+struct PlistParser<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl<'a> PlistParser<'a> {
+    /*
+     * The next tag, skipping text, the XML declaration and the DOCTYPE.
+     */
+    fn tag(&mut self) -> Option<&'a str> {
+        loop {
+            let start = self.pos + self.data[self.pos..].find('<')?;
+            let end = start + self.data[start..].find('>')?;
+            self.pos = end + 1;
+            let tag = &self.data[start + 1..end];
+            if !tag.starts_with('?') && !tag.starts_with('!') {
+                return Some(tag);
+            }
+        }
+    }
+
+    fn text(&mut self, close: &str) -> Option<&'a str> {
+        let end = self.pos + self.data[self.pos..].find(close)?;
+        let text = &self.data[self.pos..end];
+        self.pos = end + close.len();
+        Some(text)
+    }
+
+    /*
+     * Parse the value starting at tag, or return None at a closing tag.
+     */
+    fn value(&mut self, tag: &str, depth: usize) -> Option<Plist> {
+        if depth > 64 || tag.starts_with('/') {
+            return None;
+        }
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        if tag.ends_with('/') {
+            return Some(match name.trim_end_matches('/') {
+                "dict" => Plist::Dict(vec![]),
+                "string" => Plist::String(String::new()),
+                _ => Plist::Other,
+            });
+        }
+        match name {
+            "plist" => {
+                let t = self.tag()?;
+                self.value(t, depth + 1)
+            }
+            "dict" => {
+                let mut d = vec![];
+                loop {
+                    let t = self.tag()?;
+                    if t == "/dict" {
+                        return Some(Plist::Dict(d));
+                    }
+                    if t != "key" {
+                        return None;
+                    }
+                    let key = unescape(self.text("</key>")?);
+                    let t = self.tag()?;
+                    d.push((key, self.value(t, depth + 1)?));
+                }
+            }
+            "array" => loop {
+                let t = self.tag()?;
+                if t == "/array" {
+                    return Some(Plist::Other);
+                }
+                self.value(t, depth + 1)?;
+            },
+            "string" => Some(Plist::String(unescape(self.text("</string>")?))),
+            _ => {
+                self.text(&format!("</{}>", name))?;
+                Some(Plist::Other)
+            }
+        }
+    }
+
+    fn parse(data: &'a str) -> Option<Plist> {
+        let mut p = PlistParser { data, pos: 0 };
+        let t = p.tag()?;
+        p.value(t, 0)
+    }
+}
+
+fn read_xbps(data: &str) -> Result<Vec<Package>> {
+    let plist = PlistParser::parse(data).context("invalid property list")?;
+    let dict = match plist {
+        Plist::Dict(d) => d,
+        _ => anyhow::bail!("the package database is not a dictionary"),
+    };
+
+    let mut pkgs = vec![];
+    for (name, pkg) in &dict {
+        /*
+         * Keys such as _XBPS_ALTERNATIVES_ are not packages.
+         */
+        if name.starts_with('_') || pkg.get("state").and_then(|s| s.str()) != Some("installed") {
+            continue;
+        }
+        /*
+         * pkgver is "name-version_revision".
+         */
+        let version = match pkg.get("pkgver").and_then(|v| v.str()) {
+            Some(v) => v.strip_prefix(&format!("{}-", name)).unwrap_or(v),
+            None => continue,
+        };
+        let mut p = Package::new(Manager::Xbps, name, version);
+        p.arch = pkg
+            .get("architecture")
+            .and_then(|a| a.str())
+            .map(|a| a.to_string());
+        p.license = pkg
+            .get("license")
+            .and_then(|l| l.str())
+            .map(|l| l.to_string());
+        pkgs.push(p);
+    }
+    Ok(pkgs)
+}
+
+fn read_rpm(path: &Path) -> Result<Vec<Package>> {
+    let mut pkgs = vec![];
+    for h in rpmdb::read(path)? {
+        let version = match h.epoch {
+            Some(e) => format!("{}:{}-{}", e, h.version, h.release),
+            None => format!("{}-{}", h.version, h.release),
+        };
+        let mut p = Package::new(Manager::Rpm, &h.name, &version);
+        p.arch = h.arch;
+        p.license = h.license;
+        /*
+         * SOURCERPM is "name-version-release.src.rpm".
+         */
+        p.source = h
+            .source_rpm
+            .as_deref()
+            .and_then(|s| s.rsplitn(3, '-').nth(2))
+            .filter(|s| *s != p.name)
+            .map(|s| s.to_string());
+        pkgs.push(p);
+    }
+    Ok(pkgs)
+}
+
+fn read_text(zroot: &Path, path: &str) -> Result<Option<String>> {
+    match resolve_in_root(zroot, path).filter(|p| p.is_file()) {
+        Some(p) => {
+            let data = fs::read(&p).with_context(|| format!("failed to read /{}", path))?;
+            Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+/*
+ * Read the packages from every package database found in the image root.
+ */
+pub fn inventory<P: AsRef<Path>>(zroot: P) -> Result<Inventory> {
+    let zroot = zroot.as_ref();
+    let mut inv = Inventory::default();
+
+    if let Some(data) = read_text(zroot, DPKG_STATUS)? {
+        inv.packages.extend(read_dpkg(&data));
+        inv.databases.push(format!("/{}", DPKG_STATUS));
+    }
+
+    let rpmdb = RPMDB.iter().find_map(|p| {
+        resolve_in_root(zroot, p)
+            .filter(|r| r.is_file())
+            .map(|r| (p, r))
+    });
+    if let Some((path, resolved)) = rpmdb {
+        inv.packages.extend(read_rpm(&resolved)?);
+        inv.databases.push(format!("/{}", path));
+    }
+
+    if let Some(data) = read_text(zroot, APK_INSTALLED)? {
+        inv.packages.extend(read_apk(&data));
+        inv.databases.push(format!("/{}", APK_INSTALLED));
+    }
+
+    if let Some(local) = resolve_in_root(zroot, PACMAN_LOCAL).filter(|p| p.is_dir()) {
+        let mut entries: Vec<_> = fs::read_dir(&local)
+            .with_context(|| format!("failed to read /{}", PACMAN_LOCAL))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        for e in entries {
+            let desc = format!("{}/{}/desc", PACMAN_LOCAL, e);
+            if let Some(data) = read_text(zroot, &desc)? {
+                inv.packages.extend(read_pacman_desc(&data));
+            }
+        }
+        inv.databases.push(format!("/{}", PACMAN_LOCAL));
+    }
+
+    if let Some(data) = read_text(zroot, XBPS_PKGDB)? {
+        inv.packages
+            .extend(read_xbps(&data).with_context(|| format!("failed to read /{}", XBPS_PKGDB))?);
+        inv.databases.push(format!("/{}", XBPS_PKGDB));
+    }
+
+    inv.packages.sort_by(|a, b| {
+        (a.manager, &a.name, &a.arch, &a.version).cmp(&(b.manager, &b.name, &b.arch, &b.version))
+    });
+    Ok(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpkg() {
+        let status = "\
+Package: libc6
+Status: install ok installed
+Architecture: amd64
+Source: glibc (2.36-9+deb12u4)
+Version: 2.36-9+deb12u4
+Description: GNU C Library: Shared libraries
+ Contains the standard libraries that are used by nearly all programs on
+ the system.
+
+Package: bash
+Status: install ok installed
+Architecture: amd64
+Version: 5.2.15-2+b2
+
+Package: removed
+Status: deinstall ok config-files
+Architecture: amd64
+Version: 1.0-1
+";
+        let pkgs = read_dpkg(status);
+        assert_eq!(pkgs.len(), 2);
+        assert_eq!(pkgs[0].name, "libc6");
+        assert_eq!(pkgs[0].version, "2.36-9+deb12u4");
+        assert_eq!(pkgs[0].arch.as_deref(), Some("amd64"));
+        assert_eq!(pkgs[0].source.as_deref(), Some("glibc"));
+        assert_eq!(pkgs[1].name, "bash");
+        assert_eq!(pkgs[1].source, None);
+    }
+
+    #[test]
+    fn apk() {
+        let installed = "\
+C:Q1abc=
+P:musl
+V:1.2.4-r2
+A:x86_64
+L:MIT
+o:musl
+F:lib
+R:libc.musl-x86_64.so.1
+
+P:busybox-binsh
+V:1.36.1-r5
+A:x86_64
+L:GPL-2.0-only
+o:busybox
+";
+        let pkgs = read_apk(installed);
+        assert_eq!(pkgs.len(), 2);
+        assert_eq!(pkgs[0].name, "musl");
+        assert_eq!(pkgs[0].version, "1.2.4-r2");
+        assert_eq!(pkgs[0].license.as_deref(), Some("MIT"));
+        assert_eq!(pkgs[0].source, None);
+        assert_eq!(pkgs[1].source.as_deref(), Some("busybox"));
+    }
+
+    #[test]
+    fn pacman() {
+        let desc = "\
+%NAME%
+systemd-libs
+
+%VERSION%
+1:255.4-1
+
+%BASE%
+systemd
+
+%ARCH%
+x86_64
+
+%LICENSE%
+GPL-2.0-or-later
+LGPL-2.1-or-later
+
+";
+        let p = read_pacman_desc(desc).unwrap();
+        assert_eq!(p.name, "systemd-libs");
+        assert_eq!(p.version, "1:255.4-1");
+        assert_eq!(p.source.as_deref(), Some("systemd"));
+        assert_eq!(
+            p.license.as_deref(),
+            Some("GPL-2.0-or-later AND LGPL-2.1-or-later")
+        );
+        assert!(read_pacman_desc("%NAME%\nfoo\n").is_none());
+    }
+
+    #[test]
+    fn xbps() {
+        let pkgdb = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>_XBPS_ALTERNATIVES_</key>
+	<dict>
+		<key>sh</key>
+		<dict/>
+	</dict>
+	<key>bash</key>
+	<dict>
+		<key>architecture</key>
+		<string>x86_64</string>
+		<key>automatic-install</key>
+		<true/>
+		<key>installed_size</key>
+		<integer>8765432</integer>
+		<key>license</key>
+		<string>GPL-3.0-or-later</string>
+		<key>pkgver</key>
+		<string>bash-5.2.21_1</string>
+		<key>run_depends</key>
+		<array>
+			<string>glibc&gt;=2.36_1</string>
+		</array>
+		<key>state</key>
+		<string>installed</string>
+	</dict>
+	<key>half</key>
+	<dict>
+		<key>pkgver</key>
+		<string>half-1.0_1</string>
+		<key>state</key>
+		<string>unpacked</string>
+	</dict>
+</dict>
+</plist>
+"#;
+        let pkgs = read_xbps(pkgdb).unwrap();
+        assert_eq!(pkgs.len(), 1);
+        assert_eq!(pkgs[0].name, "bash");
+        assert_eq!(pkgs[0].version, "5.2.21_1");
+        assert_eq!(pkgs[0].arch.as_deref(), Some("x86_64"));
+        assert_eq!(pkgs[0].license.as_deref(), Some("GPL-3.0-or-later"));
+        assert!(read_xbps("<plist><dict><key>x</key>").is_err());
+    }
+
+    #[test]
+    fn rpm() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/rpmdb.sqlite");
+        let pkgs = read_rpm(&path).unwrap();
+        assert_eq!(pkgs.len(), 2);
+        assert_eq!(pkgs[0].version, "5.1.8-9.el9");
+        assert_eq!(pkgs[0].source, None);
+        assert_eq!(pkgs[1].name, "kernel-core");
+        assert_eq!(pkgs[1].version, "1:5.14.0-427.el9");
+        assert_eq!(pkgs[1].source.as_deref(), Some("kernel"));
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The files in ./guest are embedded into the binary at compile time so that
+ * it can be run from anywhere. Developers iterating on the guest scripts can
+ * point --guest-dir at a checkout to use the files on disk instead.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use std::borrow::Cow;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::utils::*;
+
+pub struct GuestFile {
+    pub path: &'static str,
+    pub mode: u32,
+    pub contents: &'static [u8],
+}
+
+macro_rules! guest_file {
+    ($path:literal, $mode:expr) => {
+        GuestFile {
+            path: $path,
+            mode: $mode,
+            contents: include_bytes!(concat!("../guest/", $path)),
+        }
+    };
+}
+
+pub static EMBEDDED: &[GuestFile] = &[
+    guest_file!("etc/profile.d/native_manpath.sh", 0o744),
+    guest_file!("etc/sv/joyent/run", 0o755),
+    guest_file!("etc/systemd/system/joyent.service", 0o644),
+    guest_file!("lib/smartdc/alpine", 0o755),
+    guest_file!("lib/smartdc/archlinux", 0o755),
+    guest_file!("lib/smartdc/common.lib", 0o755),
+    guest_file!("lib/smartdc/debian", 0o755),
+    guest_file!("lib/smartdc/gentoo", 0o755),
+    guest_file!("lib/smartdc/joyent_rc.local", 0o755),
+    guest_file!("lib/smartdc/mdata-execute", 0o755),
+    guest_file!("lib/smartdc/mdata-fetch", 0o755),
+    guest_file!("lib/smartdc/mdata-image", 0o755),
+    guest_file!("lib/smartdc/mount-zfs", 0o755),
+    guest_file!("lib/smartdc/redhat", 0o755),
+    guest_file!("lib/smartdc/set-provision-state", 0o755),
+    guest_file!("lib/smartdc/suse", 0o755),
+    guest_file!("lib/smartdc/void", 0o755),
+    guest_file!("sbin/shutdown", 0o755),
+];
+
+pub struct Payload {
+    dir: Option<PathBuf>,
+}
+
+impl Payload {
+    pub fn embedded() -> Self {
+        Payload { dir: None }
+    }
+
+    pub fn new<P: AsRef<Path>>(dir: Option<P>) -> Self {
+        Payload {
+            dir: dir.map(|d| d.as_ref().to_path_buf()),
+        }
+    }
+
+    /*
+     * Files missing from --guest-dir are taken from the embedded copy, so a
+     * directory holding only the scripts for a custom profile is enough.
+     */
+    fn local(&self, path: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|d| d.join(path))
+            .filter(|p| p.exists())
+    }
+
+    fn origin(&self, path: &str) -> String {
+        match self.local(path) {
+            Some(p) => p.display().to_string(),
+            None => format!("<embedded>/{}", path),
+        }
+    }
+
+    pub fn contents(&self, path: &str) -> Result<Cow<'static, [u8]>> {
+        if let Some(src) = self.local(path) {
+            let data =
+                fs::read(&src).with_context(|| format!("failed to read {}", src.display()))?;
+            return Ok(Cow::Owned(data));
+        }
+
+        EMBEDDED
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| Cow::Borrowed(f.contents))
+            .ok_or_else(|| anyhow!("{} is not part of the guest payload", path))
+    }
+
+    /*
+     * Install a file from the payload into the image, owned by root.
+     */
+    pub fn install<P: AsRef<Path>>(&self, path: &str, dst: P, mode: u32) -> Result<()> {
+        let contents = self.contents(path)?;
+        install_file(dst, &contents, &self.origin(path), 0, 0, mode)
+    }
+
+    /*
+     * Write the embedded payload out to a directory for inspection.
+     */
+    pub fn extract<P: AsRef<Path>>(dest: P) -> Result<()> {
+        let dest = dest.as_ref();
+
+        for f in EMBEDDED {
+            let dst = dest.join(f.path);
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("mkdir -p {}", parent.display()))?;
+            }
+            fs::write(&dst, f.contents)
+                .with_context(|| format!("failed to write {}", dst.display()))?;
+            fs::set_permissions(&dst, fs::Permissions::from_mode(f.mode))
+                .with_context(|| format!("failed to set permissions on {}", dst.display()))?;
+            info!("extracted {}", dst.display());
+        }
+
+        Ok(())
+    }
+}
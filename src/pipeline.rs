@@ -0,0 +1,300 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * A build is a list of named stages run in order against a shared
+ * BuildContext. Stages may be skipped or reordered from the command line, and
+ * embedders can add their own stages to a Pipeline before running it. If a
+ * stage fails, every stage that has run (including the failed one) is given
+ * the chance to roll back, most recent first.
+ */
+
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::actions::Workspace;
+use crate::cli::{Opts, DEFAULT_KERNEL};
+use crate::error::{ErrorKind, ResultExt};
+use crate::init::{BootHook, InitSystem};
+use crate::logging;
+use crate::manifest::Manifest;
+use crate::packages::{self, Inventory};
+use crate::profile::Detection;
+use crate::result::BuildResult;
+use crate::stages;
+
+/*
+ * Details of the image being built, derived from the image root's
+ * os-release and the command line options.
+ */
+pub struct Image {
+    pub name: String,
+    pub pretty_name: String,
+    pub description: String,
+    pub filesystem: String,
+    pub manifest: String,
+}
+
+pub struct BuildContext {
+    pub opts: Opts,
+    pub result: BuildResult,
+    pub workspace: Option<Workspace>,
+    pub zroot: PathBuf,
+    pub image: Option<Image>,
+    pub kernel: String,
+    pub distro: Option<Detection>,
+    pub init: Option<InitSystem>,
+    pub boot_hook: Option<BootHook>,
+    pub packages: Option<Inventory>,
+}
+
+impl BuildContext {
+    pub fn new(opts: Opts) -> Self {
+        let utc: DateTime<Utc> = Utc::now();
+        let build_date = utc.format("%Y%m%d").to_string();
+        /*
+         * --kernel auto is resolved by the kernel stage.
+         */
+        let kernel = match opts.kernel.as_str() {
+            "auto" => DEFAULT_KERNEL.to_string(),
+            k => k.to_string(),
+        };
+        let mut result = BuildResult::new(Uuid::new_v4(), &build_date, &opts.min_platform, &kernel);
+        result.dry_run = opts.dry_run;
+
+        BuildContext {
+            opts,
+            result,
+            workspace: None,
+            zroot: PathBuf::new(),
+            image: None,
+            kernel,
+            distro: None,
+            init: None,
+            boot_hook: None,
+            packages: None,
+        }
+    }
+
+    pub fn build_date(&self) -> &str {
+        &self.result.version
+    }
+
+    pub fn zroot(&self) -> Result<&Path> {
+        if self.workspace.is_none() {
+            return Err(anyhow!("no image root, has the workspace stage run?"))
+                .kind(ErrorKind::Internal);
+        }
+        Ok(&self.zroot)
+    }
+
+    pub fn image(&self) -> Result<&Image> {
+        self.image
+            .as_ref()
+            .ok_or_else(|| anyhow!("no image details, has the metadata stage run?"))
+            .kind(ErrorKind::Internal)
+    }
+
+    /*
+     * The packages installed in the image, read on first use.
+     */
+    pub fn packages(&mut self) -> Result<&Inventory> {
+        if self.packages.is_none() {
+            let inv = packages::inventory(self.zroot()?).kind(ErrorKind::Input)?;
+            if inv.databases.is_empty() {
+                warn!("found no package database in the image");
+            } else {
+                info!(
+                    "found {} packages in {}",
+                    inv.packages.len(),
+                    inv.databases.join(", ")
+                );
+            }
+            self.packages = Some(inv);
+        }
+        Ok(self.packages.as_ref().unwrap())
+    }
+
+    pub fn manifest(&self) -> Result<Manifest<'_>> {
+        let image = self.image()?;
+        Ok(Manifest {
+            name: &image.name,
+            version: self.build_date(),
+            description: image.description.trim(),
+            homepage: &self.opts.url,
+            min_platform: &self.opts.min_platform,
+            uuid: &self.result.uuid,
+            os: "linux",
+            kernel: &self.kernel,
+            tar_file: &image.filesystem,
+        })
+    }
+}
+
+pub trait Stage {
+    fn name(&self) -> &'static str;
+
+    /*
+     * Required stages may not be skipped, and must be present when the
+     * pipeline is reordered.
+     */
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()>;
+
+    fn rollback(&self, _ctx: &mut BuildContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+    skipped: Vec<&'static str>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /*
+     * The stages that make up a normal image build.
+     */
+    pub fn standard() -> Self {
+        let mut p = Self::new();
+        p.push(Box::new(stages::CreateWorkspace));
+        p.push(Box::new(stages::Extract));
+        p.push(Box::new(stages::Owners));
+        p.push(Box::new(stages::Arch));
+        p.push(Box::new(stages::Kernel));
+        p.push(Box::new(stages::Metadata));
+        p.push(Box::new(stages::Modify));
+        p.push(Box::new(stages::GuestTools));
+        p.push(Box::new(stages::CloudInit));
+        p.push(Box::new(stages::LxCompat));
+        p.push(Box::new(stages::Network));
+        p.push(Box::new(stages::Sanitize));
+        p.push(Box::new(stages::Minimize));
+        p.push(Box::new(stages::Secrets));
+        p.push(Box::new(stages::Audit));
+        p.push(Box::new(stages::Lint));
+        p.push(Box::new(stages::Vulns));
+        p.push(Box::new(stages::Package));
+        p.push(Box::new(stages::WriteManifest));
+        p.push(Box::new(stages::Sbom));
+        p.push(Box::new(stages::FileList));
+        p.push(Box::new(stages::Cleanup));
+        p
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.stages
+            .iter()
+            .position(|s| s.name() == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown stage \"{}\", stages are: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            })
+            .kind(ErrorKind::Usage)
+    }
+
+    pub fn push(&mut self, stage: Box<dyn Stage>) {
+        self.stages.push(stage);
+    }
+
+    pub fn insert_before(&mut self, before: &str, stage: Box<dyn Stage>) -> Result<()> {
+        let pos = self.position(before)?;
+        self.stages.insert(pos, stage);
+        Ok(())
+    }
+
+    pub fn insert_after(&mut self, after: &str, stage: Box<dyn Stage>) -> Result<()> {
+        let pos = self.position(after)?;
+        self.stages.insert(pos + 1, stage);
+        Ok(())
+    }
+
+    pub fn skip<S: AsRef<str>>(&mut self, name: S) -> Result<()> {
+        let name = name.as_ref();
+        let pos = self.position(name)?;
+        if self.stages[pos].required() {
+            return Err(anyhow!(
+                "stage \"{}\" is required and cannot be skipped",
+                name
+            ))
+            .kind(ErrorKind::Usage);
+        }
+        let stage = self.stages.remove(pos);
+        self.skipped.push(stage.name());
+        Ok(())
+    }
+
+    /*
+     * Run only the named stages, in the order given. Stages that are not
+     * named are skipped.
+     */
+    pub fn reorder<S: AsRef<str>>(&mut self, names: &[S]) -> Result<()> {
+        let mut ordered = Vec::with_capacity(names.len());
+        for name in names {
+            let pos = self.position(name.as_ref())?;
+            ordered.push(self.stages.remove(pos));
+        }
+
+        if let Some(s) = self.stages.iter().find(|s| s.required()) {
+            return Err(anyhow!("stage \"{}\" is required", s.name())).kind(ErrorKind::Usage);
+        }
+
+        self.skipped.extend(self.stages.iter().map(|s| s.name()));
+        self.stages = ordered;
+        Ok(())
+    }
+
+    pub fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        for name in &self.skipped {
+            info!("skipping stage {}", name);
+            ctx.result.skipped.push(name.to_string());
+        }
+
+        for (i, stage) in self.stages.iter().enumerate() {
+            let span = logging::span(stage.name());
+            let r = stage.run(ctx);
+            ctx.result.record_stage(stage.name(), span.elapsed());
+            drop(span);
+
+            if let Err(e) = r {
+                self.rollback(ctx, i);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext, failed: usize) {
+        for stage in self.stages[..=failed].iter().rev() {
+            let _span = logging::span(stage.name());
+            if let Err(e) = stage.rollback(ctx) {
+                error!("rollback failed: {:#}", e);
+            }
+        }
+    }
+}
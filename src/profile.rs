@@ -0,0 +1,621 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Distro support is described by profiles: JSON documents describing how to
+ * recognize a distro and what to change in its image root. The profiles in
+ * ./profiles are embedded into the binary, and users can supply their own
+ * with --profile. A user profile with the same name as a built-in one
+ * replaces it, and user profiles are tried first during detection.
+ *
+ * Detection prefers the os-release ID, then the distro's marker files, then
+ * the os-release ID_LIKE list, so that derivatives such as Rocky or Mint are
+ * handled by the profile of the distro they are based on.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{ErrorKind, ResultExt};
+use crate::init::BootHook;
+use crate::minimize;
+use crate::network::Strategies;
+use crate::payload::Payload;
+use crate::utils::*;
+
+static BUILTIN: &[(&str, &str)] = &[
+    ("alpine.json", include_str!("../profiles/alpine.json")),
+    ("arch.json", include_str!("../profiles/arch.json")),
+    ("debian.json", include_str!("../profiles/debian.json")),
+    ("gentoo.json", include_str!("../profiles/gentoo.json")),
+    ("redhat.json", include_str!("../profiles/redhat.json")),
+    ("suse.json", include_str!("../profiles/suse.json")),
+    ("void.json", include_str!("../profiles/void.json")),
+];
+
+fn default_mode() -> u32 {
+    0o755
+}
+
+/*
+ * Modes are written as octal strings, e.g. "644".
+ */
+fn deserialize_mode<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<u32, D::Error> {
+    let s = String::deserialize(d)?;
+    u32::from_str_radix(&s, 8).map_err(|_| serde::de::Error::custom(format!("invalid mode {}", s)))
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Detect {
+    /*
+     * The profile matches if any of these paths exist in the image root.
+     */
+    #[serde(default)]
+    pub files: Vec<String>,
+    /*
+     * os-release IDs handled by this profile. These are matched against ID,
+     * and then against each entry of ID_LIKE.
+     */
+    #[serde(default)]
+    pub ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileEntry {
+    /*
+     * Path of the file within the guest payload.
+     */
+    pub src: String,
+    /*
+     * Path of the file within the image root.
+     */
+    pub dst: String,
+    #[serde(default = "default_mode", deserialize_with = "deserialize_mode")]
+    pub mode: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Symlink {
+    pub target: String,
+    /*
+     * Path of the symlink within the image root. Existing files are left
+     * alone.
+     */
+    pub link: String,
+    /*
+     * Only create the symlink if this path exists in the image root.
+     */
+    #[serde(default)]
+    pub requires: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditAllow {
+    /*
+     * Glob pattern of paths, relative to the image root.
+     */
+    pub path: String,
+    /*
+     * The kind of finding that is expected for these paths, or any kind if
+     * not given.
+     */
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub detect: Detect,
+    /*
+     * The guest script that configures the distro at boot. It is linked to
+     * /lib/smartdc/os-setup, which the boot hook runs before looking for
+     * marker files, so that a profile for a derivative has its own script run
+     * rather than the parent distro's.
+     */
+    #[serde(default)]
+    pub setup: Option<String>,
+    /*
+     * The boot hook to install instead of the detected init system's.
+     */
+    #[serde(default)]
+    pub boot_hook: Option<BootHook>,
+    #[serde(default)]
+    pub files: Vec<FileEntry>,
+    #[serde(default)]
+    pub remove_files: Vec<String>,
+    #[serde(default)]
+    pub remove_dirs: Vec<String>,
+    #[serde(default)]
+    pub symlinks: Vec<Symlink>,
+    /*
+     * systemd units to mask in addition to the curated lx list.
+     */
+    #[serde(default)]
+    pub mask_units: Vec<String>,
+    /*
+     * Glob patterns, relative to the image root, of additional paths to
+     * remove when sanitizing the image.
+     */
+    #[serde(default)]
+    pub sanitize: Vec<String>,
+    /*
+     * How to neutralize each network stack, if not by rewriting its
+     * configuration.
+     */
+    #[serde(default)]
+    pub network: Strategies,
+    /*
+     * Permission audit findings that are expected in the distro's images,
+     * such as its setuid binaries.
+     */
+    #[serde(default)]
+    pub audit: Vec<AuditAllow>,
+    /*
+     * What --minimize removes beyond the built-in rules, and how to keep the
+     * package manager from installing it again.
+     */
+    #[serde(default)]
+    pub minimize: minimize::Rules,
+}
+
+impl Profile {
+    fn parse(source: &str, data: &str) -> Result<Self> {
+        serde_json::from_str(data).with_context(|| format!("invalid profile {}", source))
+    }
+
+    fn matches_id(&self, id: &str) -> bool {
+        self.detect.ids.iter().any(|i| i == id)
+    }
+
+    fn matches_file(&self, zroot: &Path) -> Option<&str> {
+        self.detect
+            .files
+            .iter()
+            .find(|f| resolve_in_root(zroot, f).is_some())
+            .map(|f| f.as_str())
+    }
+
+    pub fn install(&self, zroot: &Path, payload: &Payload) -> Result<()> {
+        for f in &self.files {
+            payload.install(&f.src, zroot.join(&f.dst), f.mode)?;
+        }
+
+        for f in &self.remove_files {
+            let fname = zroot.join(f);
+            if lexists(&fname) {
+                remove_file(&fname)?;
+            }
+        }
+
+        for d in &self.remove_dirs {
+            let dname = zroot.join(d);
+            if exists(&dname) {
+                remove_dir_all(&dname)?;
+            }
+        }
+
+        if let Some(setup) = &self.setup {
+            let dst = zroot.join("lib/smartdc/os-setup");
+            if !lexists(&dst) {
+                create_symlink(Path::new("/").join(setup), &dst, 0, 0)?;
+            }
+        }
+
+        for s in &self.symlinks {
+            if let Some(r) = &s.requires {
+                if resolve_in_root(zroot, r).is_none() {
+                    continue;
+                }
+            }
+            let dst = zroot.join(&s.link);
+            if !exists(&dst) {
+                create_symlink(&s.target, &dst, 0, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Confidence {
+    /*
+     * The profile was chosen with --distro.
+     */
+    Forced,
+    /*
+     * The os-release ID or a marker file matched.
+     */
+    High,
+    /*
+     * Only an os-release ID_LIKE entry matched.
+     */
+    Medium,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Forced => "forced",
+            Self::High => "high",
+            Self::Medium => "medium",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * The profile chosen for an image, and why.
+ */
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub profile: Profile,
+    pub rule: String,
+    pub confidence: Confidence,
+}
+
+pub struct ProfileSet {
+    profiles: Vec<Profile>,
+}
+
+impl ProfileSet {
+    /*
+     * Load the built-in profiles along with any user supplied profile files,
+     * or directories of profile files.
+     */
+    pub fn load<P: AsRef<Path>>(user: &[P]) -> Result<Self> {
+        let mut profiles: Vec<Profile> = vec![];
+
+        for p in user {
+            let p = p.as_ref();
+            let mut files = vec![];
+            if p.is_dir() {
+                for entry in
+                    fs::read_dir(p).with_context(|| format!("failed to read {}", p.display()))?
+                {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|e| e == "json") {
+                        files.push(path);
+                    }
+                }
+                files.sort();
+            } else {
+                files.push(p.to_path_buf());
+            }
+
+            for f in files {
+                let data = fs::read_to_string(&f)
+                    .with_context(|| format!("failed to read profile {}", f.display()))?;
+                let profile = Profile::parse(&f.display().to_string(), &data)?;
+                debug!("loaded profile {} from {}", &profile.name, f.display());
+                profiles.retain(|x| x.name != profile.name);
+                profiles.push(profile);
+            }
+        }
+
+        for (source, data) in BUILTIN {
+            let profile = Profile::parse(source, data)?;
+            if profiles.iter().any(|p| p.name == profile.name) {
+                info!("using user supplied profile for {}", &profile.name);
+                continue;
+            }
+            profiles.push(profile);
+        }
+
+        Ok(ProfileSet { profiles })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown distro \"{}\", known profiles are: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            })
+            .kind(ErrorKind::Usage)
+    }
+
+    fn find(&self, zroot: &Path) -> Option<(&Profile, String, Confidence)> {
+        /*
+         * A missing or unreadable os-release only rules out the ID rules, as
+         * the marker files may still identify the distro.
+         */
+        let (id, id_like) = match read_os_release(zroot) {
+            Ok(r) => (r.id, r.id_like),
+            Err(e) => {
+                debug!("{:#}", e);
+                (String::new(), String::new())
+            }
+        };
+
+        if !id.is_empty() {
+            if let Some(p) = self.profiles.iter().find(|p| p.matches_id(&id)) {
+                return Some((p, format!("os-release ID={}", id), Confidence::High));
+            }
+        }
+
+        for p in &self.profiles {
+            if let Some(f) = p.matches_file(zroot) {
+                return Some((p, format!("file {}", f), Confidence::High));
+            }
+        }
+
+        for like in id_like.split_whitespace() {
+            if let Some(p) = self.profiles.iter().find(|p| p.matches_id(like)) {
+                let rule = format!("os-release ID_LIKE={} (ID={})", like, id);
+                return Some((p, rule, Confidence::Medium));
+            }
+        }
+
+        None
+    }
+
+    /*
+     * Choose the profile for the image root, or use the named profile if one
+     * was forced.
+     */
+    pub fn detect<P: AsRef<Path>>(&self, zroot: P, forced: Option<&str>) -> Result<Detection> {
+        let zroot = zroot.as_ref();
+
+        let (profile, rule, confidence) = match forced {
+            Some(name) => (self.get(name)?, "--distro".to_string(), Confidence::Forced),
+            None => self.find(zroot).ok_or_else(|| {
+                anyhow!(
+                    "failed to detect supported Linux Distribution, use --distro to choose one of: {}",
+                    self.names().join(", ")
+                )
+            })
+            .kind(ErrorKind::Distro)?,
+        };
+
+        info!(
+            "detected distro as {} (rule: {}, confidence: {})",
+            &profile.name, &rule, confidence
+        );
+        if confidence == Confidence::Medium {
+            warn!(
+                "distro {} was inferred from ID_LIKE, use --distro if this is wrong",
+                &profile.name
+            );
+        }
+
+        Ok(Detection {
+            profile: profile.clone(),
+            rule,
+            confidence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    fn profile(data: &str) -> Profile {
+        Profile::parse("test", data).unwrap()
+    }
+
+    fn set() -> ProfileSet {
+        ProfileSet {
+            profiles: vec![
+                profile(r#"{"name": "alpha", "detect": {"ids": ["alpha"]}}"#),
+                profile(
+                    r#"{"name": "beta", "detect": {"ids": ["beta"], "files": ["etc/beta-release"]}}"#,
+                ),
+            ],
+        }
+    }
+
+    fn root(name: &str, os_release: &str, files: &[&str]) -> PathBuf {
+        let zroot = test_dir(name);
+        fs::create_dir_all(zroot.join("etc")).unwrap();
+        fs::write(zroot.join("etc/os-release"), os_release).unwrap();
+        for f in files {
+            fs::write(zroot.join(f), "").unwrap();
+        }
+        zroot
+    }
+
+    fn find(zroot: &Path) -> Option<(String, String, Confidence)> {
+        let set = set();
+        let found = set
+            .find(zroot)
+            .map(|(p, rule, c)| (p.name.clone(), rule, c));
+        fs::remove_dir_all(zroot).unwrap();
+        found
+    }
+
+    #[test]
+    fn find_order() {
+        /*
+         * The os-release ID comes before marker files.
+         */
+        let zroot = root("find-id", "ID=alpha\n", &["etc/beta-release"]);
+        assert_eq!(
+            find(&zroot),
+            Some((
+                "alpha".to_string(),
+                "os-release ID=alpha".to_string(),
+                Confidence::High
+            ))
+        );
+
+        /*
+         * Marker files come before ID_LIKE.
+         */
+        let zroot = root(
+            "find-file",
+            "ID=gamma\nID_LIKE=\"delta alpha\"\n",
+            &["etc/beta-release"],
+        );
+        assert_eq!(
+            find(&zroot),
+            Some((
+                "beta".to_string(),
+                "file etc/beta-release".to_string(),
+                Confidence::High
+            ))
+        );
+
+        let zroot = root("find-like", "ID=gamma\nID_LIKE=\"delta alpha\"\n", &[]);
+        assert_eq!(
+            find(&zroot),
+            Some((
+                "alpha".to_string(),
+                "os-release ID_LIKE=alpha (ID=gamma)".to_string(),
+                Confidence::Medium
+            ))
+        );
+
+        let zroot = root("find-none", "ID=gamma\n", &[]);
+        assert_eq!(find(&zroot), None);
+    }
+
+    #[test]
+    fn marker_symlink() {
+        /*
+         * An absolute symlink is resolved within the image root, not on the
+         * build host.
+         */
+        let zroot = root("marker-symlink", "ID=gamma\n", &[]);
+        symlink("/etc/passwd", zroot.join("etc/beta-release")).unwrap();
+        assert_eq!(find(&zroot), None);
+
+        let zroot = root("marker-symlink-root", "ID=gamma\n", &["etc/release"]);
+        symlink("/etc/release", zroot.join("etc/beta-release")).unwrap();
+        assert_eq!(find(&zroot).map(|f| f.0), Some("beta".to_string()));
+    }
+
+    #[test]
+    fn load_user_profiles() {
+        let dir = test_dir("load-profiles");
+        fs::write(
+            dir.join("debian.json"),
+            r#"{"name": "debian", "detect": {"ids": ["debian"]}, "setup": "lib/smartdc/mydebian"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("mylinux.json"),
+            r#"{"name": "mylinux", "detect": {"ids": ["mylinux"]}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("README"), "not a profile").unwrap();
+
+        let set = ProfileSet::load(&[&dir]).unwrap();
+        let names = set.names();
+        assert_eq!(&names[..2], &["debian", "mylinux"]);
+        assert_eq!(names.iter().filter(|n| **n == "debian").count(), 1);
+        assert!(names.contains(&"alpine"));
+        assert_eq!(
+            set.get("debian").unwrap().setup.as_deref(),
+            Some("lib/smartdc/mydebian")
+        );
+
+        fs::write(dir.join("bad.json"), r#"{"name": "bad", "unknown": 1}"#).unwrap();
+        assert!(ProfileSet::load(&[&dir]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn setup_link() {
+        let zroot = test_dir("setup-link");
+        fs::create_dir_all(zroot.join("lib/smartdc")).unwrap();
+        let p = profile(r#"{"name": "mylinux", "setup": "lib/smartdc/mylinux"}"#);
+        p.install(&zroot, &Payload::new(None::<&Path>)).unwrap();
+        let link = fs::read_link(zroot.join("lib/smartdc/os-setup"));
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(link.unwrap(), Path::new("/lib/smartdc/mylinux"));
+    }
+
+    fn detect(name: &str, os_release: &str, forced: Option<&str>) -> (String, String, Confidence) {
+        let zroot = root(name, os_release, &[]);
+        let set = ProfileSet::load::<&Path>(&[]).unwrap();
+        let d = set.detect(&zroot, forced).unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+        (d.profile.name, d.rule, d.confidence)
+    }
+
+    #[test]
+    fn derivatives() {
+        assert_eq!(
+            detect(
+                "rocky",
+                "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n",
+                None
+            ),
+            (
+                "redhat".to_string(),
+                "os-release ID=rocky".to_string(),
+                Confidence::High
+            )
+        );
+        assert_eq!(
+            detect(
+                "linuxmint",
+                "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n",
+                None
+            ),
+            (
+                "debian".to_string(),
+                "os-release ID=linuxmint".to_string(),
+                Confidence::High
+            )
+        );
+        assert_eq!(
+            detect("neon", "ID=neon\nID_LIKE=\"ubuntu debian\"\n", None),
+            (
+                "debian".to_string(),
+                "os-release ID_LIKE=ubuntu (ID=neon)".to_string(),
+                Confidence::Medium
+            )
+        );
+        assert_eq!(
+            detect("forced", "ID=\"rocky\"\n", Some("alpine")),
+            (
+                "alpine".to_string(),
+                "--distro".to_string(),
+                Confidence::Forced
+            )
+        );
+    }
+
+    #[test]
+    fn detect_errors() {
+        let zroot = root("detect-errors", "ID=unknown\n", &[]);
+        let set = ProfileSet::load::<&Path>(&[]).unwrap();
+        let unknown = set.detect(&zroot, None).unwrap_err();
+        let forced = set.detect(&zroot, Some("nosuchlinux")).unwrap_err();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert_eq!(ErrorKind::of(&unknown), ErrorKind::Distro);
+        assert_eq!(ErrorKind::of(&forced), ErrorKind::Usage);
+    }
+}
@@ -0,0 +1,407 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+use anyhow::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::audit;
+use crate::error::ErrorKind;
+use crate::lint::Report;
+use crate::manifest::sha1_digest;
+use crate::minimize;
+use crate::osv;
+use crate::owners;
+use crate::sanitize::Summary;
+use crate::secrets;
+
+/*
+ * A machine readable summary of a build. This is filled in as the build
+ * progresses so that a partial result can still be emitted when a stage
+ * fails.
+ */
+pub struct BuildResult {
+    started: Instant,
+    pub uuid: Uuid,
+    pub dry_run: bool,
+    pub name: Option<String>,
+    pub version: String,
+    pub arch: Option<String>,
+    pub owners: Option<owners::Report>,
+    pub distro: Option<String>,
+    pub distro_rule: Option<String>,
+    pub distro_confidence: Option<String>,
+    pub init: Option<String>,
+    pub init_rule: Option<String>,
+    pub boot_hook: Option<String>,
+    pub masked_units: Vec<String>,
+    pub default_target: Option<String>,
+    pub sanitized: Option<Summary>,
+    pub minimized: Option<minimize::Report>,
+    pub secrets: Option<Vec<secrets::Finding>>,
+    pub audit: Option<audit::Report>,
+    pub network: Vec<(String, String)>,
+    pub cloud_init: Option<String>,
+    pub mdata_hooks: bool,
+    pub lint: Option<Report>,
+    pub vulns: Option<osv::Report>,
+    pub vulns_report: Option<PathBuf>,
+    pub min_platform: String,
+    pub kernel: String,
+    pub min_kernel: Option<String>,
+    pub libc: Option<String>,
+    pub filesystem: Option<PathBuf>,
+    pub manifest: Option<PathBuf>,
+    pub sbom: Option<PathBuf>,
+    pub sbom_format: Option<String>,
+    pub sbom_packages: Option<usize>,
+    pub file_list: Option<PathBuf>,
+    pub stages: Vec<(String, Duration)>,
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+    pub error: Option<(ErrorKind, String)>,
+}
+
+impl BuildResult {
+    pub fn new(uuid: Uuid, version: &str, min_platform: &str, kernel: &str) -> Self {
+        BuildResult {
+            started: Instant::now(),
+            uuid,
+            dry_run: false,
+            name: None,
+            version: version.to_string(),
+            arch: None,
+            owners: None,
+            distro: None,
+            distro_rule: None,
+            distro_confidence: None,
+            init: None,
+            init_rule: None,
+            boot_hook: None,
+            masked_units: vec![],
+            default_target: None,
+            sanitized: None,
+            minimized: None,
+            secrets: None,
+            audit: None,
+            network: vec![],
+            cloud_init: None,
+            mdata_hooks: true,
+            lint: None,
+            vulns: None,
+            vulns_report: None,
+            min_platform: min_platform.to_string(),
+            kernel: kernel.to_string(),
+            min_kernel: None,
+            libc: None,
+            filesystem: None,
+            manifest: None,
+            sbom: None,
+            sbom_format: None,
+            sbom_packages: None,
+            file_list: None,
+            stages: vec![],
+            skipped: vec![],
+            warnings: vec![],
+            error: None,
+        }
+    }
+
+    pub fn record_stage(&mut self, stage: &str, elapsed: Duration) {
+        self.stages.push((stage.to_string(), elapsed));
+    }
+
+    /*
+     * The paths and bytes removed, in total and by category.
+     */
+    fn summary(s: &Summary) -> serde_json::Value {
+        let categories: serde_json::Map<_, _> = s
+            .categories
+            .iter()
+            .map(|(name, c)| {
+                (
+                    name.clone(),
+                    serde_json::json!({ "paths": c.paths, "bytes": c.bytes }),
+                )
+            })
+            .collect();
+        serde_json::json!({
+            "paths": s.paths(),
+            "bytes": s.bytes(),
+            "categories": categories,
+        })
+    }
+
+    fn file_details(path: &Option<PathBuf>) -> Result<serde_json::Value> {
+        let path = match path {
+            Some(p) if p.exists() => p,
+            _ => return Ok(serde_json::Value::Null),
+        };
+
+        let size = fs::metadata(path)?.len();
+        let sha1 = sha1_digest(BufReader::new(File::open(path)?))?;
+        let path = fs::canonicalize(path)?;
+
+        Ok(serde_json::json!({
+            "path": path,
+            "size": size,
+            "digests": {
+                "sha1": sha1,
+            },
+        }))
+    }
+
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<()> {
+        let stages: Vec<_> = self
+            .stages
+            .iter()
+            .map(|(name, elapsed)| {
+                serde_json::json!({
+                    "name": name,
+                    "seconds": elapsed.as_secs_f64(),
+                })
+            })
+            .collect();
+
+        let (status, error) = match &self.error {
+            Some((kind, msg)) => (
+                "failed",
+                serde_json::json!({
+                    "kind": kind.to_string(),
+                    "exit_code": kind.exit_code(),
+                    "message": msg,
+                }),
+            ),
+            None => ("success", serde_json::Value::Null),
+        };
+
+        let detection = match (&self.distro_rule, &self.distro_confidence) {
+            (Some(rule), Some(confidence)) => serde_json::json!({
+                "rule": rule,
+                "confidence": confidence,
+            }),
+            _ => serde_json::Value::Null,
+        };
+
+        let init = match &self.boot_hook {
+            Some(hook) => serde_json::json!({
+                "system": self.init,
+                "rule": self.init_rule,
+                "boot_hook": hook,
+            }),
+            None => serde_json::Value::Null,
+        };
+
+        let sanitize = match &self.sanitized {
+            Some(s) => Self::summary(s),
+            None => serde_json::Value::Null,
+        };
+
+        let minimize = match &self.minimized {
+            Some(r) => {
+                let mut m = Self::summary(&r.summary);
+                m["languages"] = serde_json::json!(r.languages);
+                m["exclude"] = match &r.exclude {
+                    Some((manager, path)) => serde_json::json!({
+                        "manager": manager.to_string(),
+                        "path": path,
+                    }),
+                    None => serde_json::Value::Null,
+                };
+                m
+            }
+            None => serde_json::Value::Null,
+        };
+
+        let owners = match &self.owners {
+            Some(r) => {
+                let remapped: Vec<_> = r
+                    .remapped
+                    .iter()
+                    .map(|((kind, from, to), paths)| {
+                        serde_json::json!({
+                            "type": kind,
+                            "from": from,
+                            "to": to,
+                            "paths": paths,
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "paths": r.paths,
+                    "remapped": remapped,
+                })
+            }
+            None => serde_json::Value::Null,
+        };
+
+        let network: Vec<_> = self
+            .network
+            .iter()
+            .map(|(stack, strategy)| {
+                serde_json::json!({
+                    "stack": stack,
+                    "strategy": strategy,
+                })
+            })
+            .collect();
+
+        let cloud_init = match &self.cloud_init {
+            Some(marker) => serde_json::json!({
+                "detected_by": marker,
+                "mdata_hooks": self.mdata_hooks,
+            }),
+            None => serde_json::Value::Null,
+        };
+
+        let secrets = match &self.secrets {
+            Some(findings) => {
+                let findings: Vec<_> = findings
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "category": f.category,
+                            "path": f.path,
+                            "detail": f.detail,
+                        })
+                    })
+                    .collect();
+                serde_json::Value::from(findings)
+            }
+            None => serde_json::Value::Null,
+        };
+
+        let audit = match &self.audit {
+            Some(r) => {
+                let findings: Vec<_> = r
+                    .findings
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "category": f.category,
+                            "path": f.path,
+                            "detail": f.detail,
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "findings": findings,
+                    "allowed": r.allowed,
+                })
+            }
+            None => serde_json::Value::Null,
+        };
+
+        let lint = match &self.lint {
+            Some(r) => {
+                let findings: Vec<_> = r
+                    .findings
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "severity": f.severity.to_string(),
+                            "message": f.message,
+                        })
+                    })
+                    .collect();
+                serde_json::Value::from(findings)
+            }
+            None => serde_json::Value::Null,
+        };
+
+        let vulns = match &self.vulns {
+            Some(r) => serde_json::json!({
+                "database": r.database,
+                "ecosystem": r.ecosystem,
+                "packages_scanned": r.scanned,
+                "findings": r.findings.len(),
+                "severities": r.severities(),
+                "report": Self::file_details(&self.vulns_report)?,
+            }),
+            None => serde_json::Value::Null,
+        };
+
+        let sbom = match &self.sbom_format {
+            Some(format) => serde_json::json!({
+                "format": format,
+                "packages": self.sbom_packages,
+                "file": Self::file_details(&self.sbom)?,
+            }),
+            None => serde_json::Value::Null,
+        };
+
+        let result = serde_json::json!({
+            "status": status,
+            "error": error,
+            "dry_run": self.dry_run,
+            "uuid": self.uuid,
+            "name": self.name,
+            "version": self.version,
+            "arch": self.arch,
+            "owners": owners,
+            "distro": self.distro,
+            "distro_detection": detection,
+            "init": init,
+            "cloud_init": cloud_init,
+            "lx_compat": {
+                "masked_units": self.masked_units,
+                "default_target": self.default_target,
+            },
+            "min_platform": self.min_platform,
+            "kernel_version": self.kernel,
+            "min_kernel_version": self.min_kernel,
+            "libc": self.libc,
+            "network": network,
+            "sanitize": sanitize,
+            "minimize": minimize,
+            "secrets": secrets,
+            "audit": audit,
+            "lint": lint,
+            "vulnerabilities": vulns,
+            "filesystem": Self::file_details(&self.filesystem)?,
+            "manifest": Self::file_details(&self.manifest)?,
+            "sbom": sbom,
+            "file_list": Self::file_details(&self.file_list)?,
+            "stages": stages,
+            "skipped_stages": self.skipped,
+            "total_seconds": self.started.elapsed().as_secs_f64(),
+            "warnings": self.warnings,
+        });
+
+        Ok(serde_json::to_writer_pretty(writer, &result)?)
+    }
+
+    /*
+     * Write the result to the given path, or to stdout if the path is "-".
+     */
+    pub fn write<P: AsRef<Path>>(&self, output: P) -> Result<()> {
+        let output = output.as_ref();
+
+        if output == Path::new("-") {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            self.to_writer(&mut handle)?;
+            writeln!(handle)?;
+            return Ok(());
+        }
+
+        let f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output)?;
+        self.to_writer(f)?;
+
+        Ok(())
+    }
+}
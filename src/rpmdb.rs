@@ -0,0 +1,682 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * A read only rpm database reader, so that the packages of an image can be
+ * listed without running its rpm. Depending on the distro and its age, the
+ * database is one of:
+ *
+ *   rpmdb.sqlite   sqlite, a Packages table of (hnum, blob)   (rpm >= 4.16)
+ *   Packages.db    rpm's own "ndb" format                     (SUSE)
+ *   Packages       a Berkeley DB hash database                (older distros)
+ *
+ * Every format stores the same thing, a header blob per package. Only as much
+ * of each format as is needed to find the blobs is implemented. Changes still
+ * in an sqlite write-ahead log are not seen, which is fine for an image that
+ * was shut down or exported cleanly.
+ */
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+const TAG_NAME: u32 = 1000;
+const TAG_VERSION: u32 = 1001;
+const TAG_RELEASE: u32 = 1002;
+const TAG_EPOCH: u32 = 1003;
+const TAG_LICENSE: u32 = 1014;
+const TAG_ARCH: u32 = 1022;
+const TAG_SOURCERPM: u32 = 1044;
+
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+/*
+ * The fields of a package header that are needed for an inventory.
+ */
+#[derive(Debug, Default)]
+pub struct Header {
+    pub name: String,
+    pub version: String,
+    pub release: String,
+    pub epoch: Option<u32>,
+    pub arch: Option<String>,
+    pub license: Option<String>,
+    pub source_rpm: Option<String>,
+}
+
+fn be32(b: &[u8], off: usize) -> Option<u32> {
+    let b = b.get(off..off + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/*
+ * Parse a header blob as stored in the database: the index entry count and
+ * data length, the index entries, then the data.
+ */
+pub fn parse_header(blob: &[u8]) -> Option<Header> {
+    let il = be32(blob, 0)? as usize;
+    let dl = be32(blob, 4)? as usize;
+    let data_start = 8 + il.checked_mul(16)?;
+    if il == 0 || data_start.checked_add(dl)? > blob.len() {
+        return None;
+    }
+    let data = &blob[data_start..data_start + dl];
+
+    let string = |off: usize| -> Option<String> {
+        let s = data.get(off..)?;
+        let end = s.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&s[..end]).into_owned())
+    };
+
+    let mut h = Header::default();
+    for i in 0..il {
+        let e = 8 + i * 16;
+        let tag = be32(blob, e)?;
+        let typ = be32(blob, e + 4)?;
+        let off = be32(blob, e + 8)? as usize;
+
+        let value = match typ {
+            TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18NSTRING => string(off),
+            TYPE_INT32 => be32(data, off).map(|v| v.to_string()),
+            _ => None,
+        };
+        let value = match value {
+            Some(v) => v,
+            None => continue,
+        };
+
+        match tag {
+            TAG_NAME => h.name = value,
+            TAG_VERSION => h.version = value,
+            TAG_RELEASE => h.release = value,
+            TAG_EPOCH => h.epoch = value.parse().ok(),
+            TAG_LICENSE => h.license = Some(value),
+            TAG_ARCH => h.arch = Some(value),
+            TAG_SOURCERPM => h.source_rpm = Some(value),
+            _ => (),
+        }
+    }
+
+    if h.name.is_empty() || h.version.is_empty() {
+        return None;
+    }
+    Some(h)
+}
+
+/*
+ * sqlite's variable length integers.
+ */
+fn varint(b: &[u8], off: usize) -> Option<(u64, usize)> {
+    let mut v: u64 = 0;
+    for i in 0..9 {
+        let byte = *b.get(off + i)?;
+        if i == 8 {
+            return Some(((v << 8) | u64::from(byte), 9));
+        }
+        v = (v << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((v, i + 1));
+        }
+    }
+    None
+}
+
+struct Sqlite {
+    data: Vec<u8>,
+    page_size: usize,
+    usable: usize,
+}
+
+impl Sqlite {
+    fn open(data: Vec<u8>) -> Result<Self> {
+        if !data.starts_with(b"SQLite format 3\0") || data.len() < 100 {
+            bail!("not an sqlite database");
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            n => usize::from(n),
+        };
+        if !page_size.is_power_of_two() || !(512..=65536).contains(&page_size) {
+            bail!("invalid page size {}", page_size);
+        }
+        /*
+         * sqlite itself requires at least 480 usable bytes, which keeps the
+         * payload size arithmetic below from underflowing.
+         */
+        let usable = page_size.saturating_sub(usize::from(data[20]));
+        if usable < 480 {
+            bail!("invalid usable page size {}", usable);
+        }
+        Ok(Sqlite {
+            data,
+            page_size,
+            usable,
+        })
+    }
+
+    fn page(&self, n: u32) -> Result<&[u8]> {
+        let start = (n as usize)
+            .checked_sub(1)
+            .map(|p| p * self.page_size)
+            .ok_or_else(|| anyhow!("invalid page number"))?;
+        self.data
+            .get(start..start + self.page_size)
+            .ok_or_else(|| anyhow!("page {} is past the end of the file", n))
+    }
+
+    /*
+     * The payload of a table leaf cell, following its overflow pages.
+     */
+    fn payload(&self, page: &[u8], cell: usize) -> Result<Vec<u8>> {
+        let bad = || anyhow!("invalid cell");
+        let (len, n1) = varint(page, cell).ok_or_else(bad)?;
+        let (_rowid, n2) = varint(page, cell + n1).ok_or_else(bad)?;
+        if len > self.data.len() as u64 {
+            bail!("cell is larger than the database");
+        }
+        let len = len as usize;
+        let start = cell + n1 + n2;
+
+        let u = self.usable;
+        let x = u - 35;
+        let local = if len <= x {
+            len
+        } else {
+            let m = ((u - 12) * 32 / 255) - 23;
+            let k = m + ((len - m) % (u - 4));
+            if k <= x {
+                k
+            } else {
+                m
+            }
+        };
+
+        let mut out = page.get(start..start + local).ok_or_else(bad)?.to_vec();
+        if local < len {
+            let mut next = be32(page, start + local).ok_or_else(bad)?;
+            while out.len() < len {
+                if next == 0 {
+                    bail!("overflow chain ends early");
+                }
+                let p = self.page(next)?;
+                next = be32(p, 0).ok_or_else(bad)?;
+                let take = (len - out.len()).min(u - 4);
+                out.extend_from_slice(p.get(4..4 + take).ok_or_else(bad)?);
+            }
+        }
+        Ok(out)
+    }
+
+    /*
+     * Call f with the payload of every row of the table rooted at page n.
+     * seen holds the pages already visited, so that a damaged database whose
+     * pages refer to each other cannot make this run forever.
+     */
+    fn walk(
+        &self,
+        n: u32,
+        depth: usize,
+        seen: &mut HashSet<u32>,
+        f: &mut dyn FnMut(&[u8]),
+    ) -> Result<()> {
+        if depth > 32 {
+            bail!("b-tree is too deep");
+        }
+        if !seen.insert(n) {
+            bail!("page {} is used twice", n);
+        }
+        let page = self.page(n)?;
+        let hdr = if n == 1 { 100 } else { 0 };
+        let u16_at = |off: usize| {
+            page.get(off..off + 2)
+                .map(|b| usize::from(u16::from_be_bytes([b[0], b[1]])))
+                .ok_or_else(|| anyhow!("invalid page {}", n))
+        };
+        let kind = *page.get(hdr).ok_or_else(|| anyhow!("invalid page {}", n))?;
+        let cells = u16_at(hdr + 3)?;
+        let ptrs = hdr + if kind == 0x05 { 12 } else { 8 };
+
+        for i in 0..cells {
+            let cell = u16_at(ptrs + i * 2)?;
+            match kind {
+                0x05 => {
+                    let child = be32(page, cell).ok_or_else(|| anyhow!("invalid cell"))?;
+                    self.walk(child, depth + 1, seen, f)?;
+                }
+                0x0d => f(&self.payload(page, cell)?),
+                k => bail!("unexpected page type {:#x}", k),
+            }
+        }
+        if kind == 0x05 {
+            let right = be32(page, hdr + 8).ok_or_else(|| anyhow!("invalid page"))?;
+            self.walk(right, depth + 1, seen, f)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * The columns of an sqlite record, as (serial type, bytes).
+ */
+fn record(payload: &[u8]) -> Option<Vec<(u64, &[u8])>> {
+    let (hlen, mut off) = varint(payload, 0)?;
+    let mut types = vec![];
+    while off < hlen as usize {
+        let (t, n) = varint(payload, off)?;
+        types.push(t);
+        off += n;
+    }
+
+    let mut body = hlen as usize;
+    let mut cols = vec![];
+    for t in types {
+        let len = match t {
+            0 | 8 | 9 => 0,
+            1..=4 => t as usize,
+            5 => 6,
+            6 | 7 => 8,
+            t if t >= 12 => ((t - 12) / 2) as usize,
+            _ => return None,
+        };
+        cols.push((t, payload.get(body..body + len)?));
+        body += len;
+    }
+    Some(cols)
+}
+
+fn read_sqlite(data: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+    let db = Sqlite::open(data)?;
+
+    /*
+     * sqlite_master, on page 1, has (type, name, tbl_name, rootpage, sql).
+     */
+    let mut root = None;
+    db.walk(1, 0, &mut HashSet::new(), &mut |p| {
+        if let Some(cols) = record(p) {
+            if cols.len() >= 4 && cols[0].1 == b"table" && cols[1].1 == b"Packages" {
+                let mut v: u32 = 0;
+                for b in cols[3].1 {
+                    v = (v << 8) | u32::from(*b);
+                }
+                root = Some(v);
+            }
+        }
+    })?;
+    let root = root.ok_or_else(|| anyhow!("no Packages table"))?;
+
+    let mut blobs = vec![];
+    db.walk(root, 0, &mut HashSet::new(), &mut |p| {
+        if let Some(cols) = record(p) {
+            if let Some((t, b)) = cols.get(1) {
+                if *t >= 12 && t % 2 == 0 {
+                    blobs.push(b.to_vec());
+                }
+            }
+        }
+    })?;
+    Ok(blobs)
+}
+
+const NDB_HEADER_MAGIC: &[u8] = b"RpmP";
+const NDB_SLOT_MAGIC: &[u8] = b"Slot";
+const NDB_BLOB_MAGIC: &[u8] = b"BlbS";
+const NDB_SLOT_PAGE: usize = 4096;
+
+fn le32(b: &[u8], off: usize) -> Option<u32> {
+    let b = b.get(off..off + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/*
+ * ndb starts with pages of 16 byte slots, the first of which is the file
+ * header. Each used slot gives the position of a blob in 16 byte blocks.
+ */
+fn read_ndb(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if !data.starts_with(NDB_HEADER_MAGIC) {
+        bail!("not an ndb database");
+    }
+    let npages = le32(data, 12).ok_or_else(|| anyhow!("truncated ndb header"))? as usize;
+
+    let mut blobs = vec![];
+    for slot in data[..(npages * NDB_SLOT_PAGE).min(data.len())]
+        .chunks_exact(16)
+        .skip(1)
+    {
+        if &slot[..4] != NDB_SLOT_MAGIC {
+            continue;
+        }
+        let pkgidx = le32(slot, 4).unwrap_or(0);
+        let blkoff = le32(slot, 8).unwrap_or(0) as usize;
+        if pkgidx == 0 || blkoff == 0 {
+            continue;
+        }
+        let off = blkoff * 16;
+        if data.get(off..off + 4) != Some(NDB_BLOB_MAGIC) || le32(data, off + 4) != Some(pkgidx) {
+            continue;
+        }
+        let len = le32(data, off + 12).unwrap_or(0) as usize;
+        if let Some(b) = data.get(off + 16..off + 16 + len) {
+            blobs.push(b.to_vec());
+        }
+    }
+    Ok(blobs)
+}
+
+const BDB_HASH_MAGIC: u32 = 0x061561;
+const BDB_PAGE_HASH_UNSORTED: u8 = 2;
+const BDB_PAGE_HASH: u8 = 13;
+const BDB_KEYDATA: u8 = 1;
+const BDB_OFFPAGE: u8 = 3;
+
+/*
+ * Berkeley DB hash pages hold alternating key and data items, growing down
+ * from the end of the page. Large items, such as every header of note, live
+ * in a chain of overflow pages.
+ */
+fn read_bdb(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let magic = le32(data, 12).ok_or_else(|| anyhow!("truncated database"))?;
+    let le = if magic == BDB_HASH_MAGIC {
+        true
+    } else if magic.swap_bytes() == BDB_HASH_MAGIC {
+        false
+    } else {
+        bail!("not a Berkeley DB hash database");
+    };
+    let u32_at = |b: &[u8], off: usize| -> Option<u32> {
+        if le {
+            le32(b, off)
+        } else {
+            be32(b, off)
+        }
+    };
+    let u16_at = |b: &[u8], off: usize| -> Option<u16> {
+        let b = b.get(off..off + 2)?;
+        Some(if le {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+
+    let page_size = u32_at(data, 20).unwrap_or(0) as usize;
+    if !(512..=65536).contains(&page_size) {
+        bail!("invalid page size {}", page_size);
+    }
+    let page = |n: u32| data.get(n as usize * page_size..(n as usize + 1) * page_size);
+
+    let overflow = |mut n: u32, len: usize| -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(len.min(data.len()));
+        let mut hops = 0;
+        while out.len() < len && n != 0 && hops < 1 << 16 {
+            let p = page(n)?;
+            let used = usize::from(u16_at(p, 22)?);
+            out.extend_from_slice(p.get(26..26 + used)?);
+            n = u32_at(p, 16)?;
+            hops += 1;
+        }
+        if out.len() < len {
+            return None;
+        }
+        out.truncate(len);
+        Some(out)
+    };
+
+    let mut blobs = vec![];
+    for n in 1..(data.len() / page_size) as u32 {
+        let p = match page(n) {
+            Some(p) => p,
+            None => break,
+        };
+        if p[25] != BDB_PAGE_HASH && p[25] != BDB_PAGE_HASH_UNSORTED {
+            continue;
+        }
+        let entries = usize::from(u16_at(p, 20).unwrap_or(0));
+        let offset = |i: usize| u16_at(p, 26 + i * 2).map(usize::from);
+
+        for i in (1..entries).step_by(2) {
+            let (start, end) = match (offset(i), offset(i - 1)) {
+                (Some(s), Some(e)) if s < e && e <= page_size => (s, e),
+                _ => continue,
+            };
+            let item = &p[start..end];
+            let blob = match item[0] {
+                BDB_KEYDATA => Some(item[1..].to_vec()),
+                BDB_OFFPAGE if item.len() >= 12 => {
+                    let pgno = u32_at(item, 4).unwrap_or(0);
+                    let len = u32_at(item, 8).unwrap_or(0) as usize;
+                    overflow(pgno, len)
+                }
+                _ => None,
+            };
+            if let Some(b) = blob {
+                blobs.push(b);
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+/*
+ * Read every package header from the database at path.
+ */
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<Header>> {
+    let path = path.as_ref();
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    let blobs = if data.starts_with(b"SQLite format 3\0") {
+        read_sqlite(data)
+    } else if data.starts_with(NDB_HEADER_MAGIC) {
+        read_ndb(&data)
+    } else {
+        read_bdb(&data)
+    }
+    .with_context(|| format!("failed to read rpm database {}", path.display()))?;
+
+    /*
+     * The database also holds imported signing keys as gpg-pubkey packages.
+     */
+    Ok(blobs
+        .iter()
+        .filter_map(|b| parse_header(b))
+        .filter(|h| h.name != "gpg-pubkey")
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * A header blob with a string entry for each field.
+     */
+    fn header(fields: &[(u32, &str)]) -> Vec<u8> {
+        let mut index = vec![];
+        let mut data = vec![];
+        for (tag, value) in fields {
+            index.extend_from_slice(&tag.to_be_bytes());
+            index.extend_from_slice(&TYPE_STRING.to_be_bytes());
+            index.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            index.extend_from_slice(&1u32.to_be_bytes());
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        let mut blob = vec![];
+        blob.extend_from_slice(&(fields.len() as u32).to_be_bytes());
+        blob.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        blob.extend(index);
+        blob.extend(data);
+        blob
+    }
+
+    fn bash() -> Vec<u8> {
+        header(&[
+            (TAG_NAME, "bash"),
+            (TAG_VERSION, "5.2.15"),
+            (TAG_RELEASE, "3.fc38"),
+            (TAG_ARCH, "x86_64"),
+        ])
+    }
+
+    fn sqlite_fixture() -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/rpmdb.sqlite");
+        fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn header_fields() {
+        let h = parse_header(&bash()).unwrap();
+        assert_eq!(h.name, "bash");
+        assert_eq!(h.version, "5.2.15");
+        assert_eq!(h.release, "3.fc38");
+        assert_eq!(h.arch.as_deref(), Some("x86_64"));
+        assert_eq!(h.epoch, None);
+    }
+
+    #[test]
+    fn header_truncated() {
+        let blob = bash();
+        for len in 0..blob.len() {
+            assert!(parse_header(&blob[..len]).is_none());
+        }
+        assert!(parse_header(&header(&[(TAG_NAME, "bash")])).is_none());
+    }
+
+    #[test]
+    fn sqlite() {
+        let blobs = read_sqlite(sqlite_fixture()).unwrap();
+        let headers: Vec<Header> = blobs.iter().filter_map(|b| parse_header(b)).collect();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers[0].name, "bash");
+        assert_eq!(headers[0].license.as_deref(), Some("GPLv3+"));
+        /*
+         * The kernel's header is larger than a page, so is read through
+         * overflow pages.
+         */
+        assert_eq!(headers[1].name, "kernel-core");
+        assert_eq!(headers[1].epoch, Some(1));
+        assert_eq!(headers[1].release, "427.el9");
+        assert_eq!(
+            headers[1].source_rpm.as_deref(),
+            Some("kernel-5.14.0-427.el9.src.rpm")
+        );
+        assert_eq!(headers[2].name, "gpg-pubkey");
+    }
+
+    #[test]
+    fn sqlite_invalid_page_size() {
+        for (size, reserved) in [(0u16, 0u8), (1000, 0), (256, 0), (65535, 0), (512, 33)] {
+            let mut data = sqlite_fixture();
+            data[16..18].copy_from_slice(&size.to_be_bytes());
+            data[20] = reserved;
+            assert!(Sqlite::open(data).is_err(), "{} {}", size, reserved);
+        }
+    }
+
+    /*
+     * A damaged database is an error, not a panic or a hang.
+     */
+    #[test]
+    fn sqlite_corrupt() {
+        let fixture = sqlite_fixture();
+        for off in 0..fixture.len() {
+            for b in [0x00, 0x01, 0x7f, 0xff] {
+                let mut data = fixture.clone();
+                data[off] = b;
+                let _ = read_sqlite(data);
+            }
+        }
+        for len in (0..fixture.len()).step_by(7) {
+            let _ = read_sqlite(fixture[..len].to_vec());
+        }
+    }
+
+    #[test]
+    fn ndb() {
+        let blob = bash();
+        let mut data = vec![0u8; NDB_SLOT_PAGE];
+        data[..4].copy_from_slice(NDB_HEADER_MAGIC);
+        data[12..16].copy_from_slice(&1u32.to_le_bytes());
+        /*
+         * One used slot, for package 7 at block 256, and one free slot.
+         */
+        data[16..20].copy_from_slice(NDB_SLOT_MAGIC);
+        data[20..24].copy_from_slice(&7u32.to_le_bytes());
+        data[24..28].copy_from_slice(&256u32.to_le_bytes());
+        data[32..36].copy_from_slice(NDB_SLOT_MAGIC);
+
+        let mut b = NDB_BLOB_MAGIC.to_vec();
+        b.extend_from_slice(&7u32.to_le_bytes());
+        b.extend_from_slice(&0u32.to_le_bytes());
+        b.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+        b.extend_from_slice(&blob);
+        data.extend(b);
+
+        let blobs = read_ndb(&data).unwrap();
+        assert_eq!(blobs, vec![blob]);
+        assert!(read_ndb(b"RpmP").is_err());
+    }
+
+    #[test]
+    fn bdb() {
+        const PAGE: usize = 512;
+        let small = bash();
+        let large = header(&[
+            (TAG_NAME, "glibc"),
+            (TAG_VERSION, "2.37"),
+            (TAG_RELEASE, "4.fc38"),
+            (TAG_LICENSE, &"L".repeat(700)),
+        ]);
+
+        let mut data = vec![0u8; 4 * PAGE];
+        data[12..16].copy_from_slice(&BDB_HASH_MAGIC.to_le_bytes());
+        data[20..24].copy_from_slice(&(PAGE as u32).to_le_bytes());
+
+        /*
+         * Page 1 holds a key and the small header on the page, then a key
+         * and an off page reference to the large header.
+         */
+        let p = PAGE;
+        data[p + 25] = BDB_PAGE_HASH;
+        data[p + 20..p + 22].copy_from_slice(&4u16.to_le_bytes());
+        let mut end = PAGE;
+        let mut items: Vec<Vec<u8>> = vec![];
+        items.push(vec![BDB_KEYDATA, 1, 0, 0, 0]);
+        items.push([&[BDB_KEYDATA][..], &small].concat());
+        items.push(vec![BDB_KEYDATA, 2, 0, 0, 0]);
+        let mut off = vec![BDB_OFFPAGE, 0, 0, 0];
+        off.extend_from_slice(&2u32.to_le_bytes());
+        off.extend_from_slice(&(large.len() as u32).to_le_bytes());
+        items.push(off);
+        for (i, item) in items.iter().enumerate() {
+            end -= item.len();
+            data[p + end..p + end + item.len()].copy_from_slice(item);
+            data[p + 26 + i * 2..p + 28 + i * 2].copy_from_slice(&(end as u16).to_le_bytes());
+        }
+
+        /*
+         * Pages 2 and 3 are the overflow chain.
+         */
+        let room = PAGE - 26;
+        for (n, chunk) in large.chunks(room).enumerate() {
+            let p = (n + 2) * PAGE;
+            let next = if n == 0 { 3u32 } else { 0 };
+            data[p + 16..p + 20].copy_from_slice(&next.to_le_bytes());
+            data[p + 22..p + 24].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+            data[p + 26..p + 26 + chunk.len()].copy_from_slice(chunk);
+        }
+
+        let blobs = read_bdb(&data).unwrap();
+        assert_eq!(blobs, vec![small, large]);
+    }
+}
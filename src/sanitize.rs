@@ -0,0 +1,356 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Input tarballs are often exported from a system that has been running for a
+ * while, and carry its identity along: machine-id, ssh host keys, shell
+ * histories, DHCP leases and so on. Every instance created from the image
+ * would share them. The sanitize pass removes that state, along with caches
+ * and logs that only take up space.
+ *
+ * Rules are glob patterns relative to the image root, applied in order. Log
+ * files are truncated rather than removed, as some daemons will not recreate
+ * them.
+ */
+
+use anyhow::{Context, Result};
+use glob::MatchOptions;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::utils::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Remove,
+    Truncate,
+}
+
+struct Rule {
+    category: &'static str,
+    action: Action,
+    patterns: &'static [&'static str],
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        category: "machine-id",
+        action: Action::Truncate,
+        patterns: &["etc/machine-id", "var/lib/dbus/machine-id"],
+    },
+    Rule {
+        category: "ssh-host-keys",
+        action: Action::Remove,
+        patterns: &["etc/ssh/ssh_host_*"],
+    },
+    Rule {
+        category: "history",
+        action: Action::Remove,
+        patterns: &[
+            "root/.*_history",
+            "root/.lesshst",
+            "root/.viminfo",
+            "home/*/.*_history",
+            "home/*/.lesshst",
+            "home/*/.viminfo",
+        ],
+    },
+    Rule {
+        category: "package-cache",
+        action: Action::Remove,
+        patterns: &[
+            "var/cache/apt/archives/*.deb",
+            "var/cache/apt/*.bin",
+            "var/lib/apt/lists/*_*",
+            "var/cache/dnf/*",
+            "var/cache/yum/*",
+            "var/cache/apk/*",
+            "var/cache/pacman/pkg/*",
+            "var/cache/zypp/packages/*",
+            "var/cache/xbps/*",
+            "var/cache/distfiles/*",
+        ],
+    },
+    Rule {
+        category: "logs",
+        action: Action::Remove,
+        patterns: &[
+            "var/log/**/*.gz",
+            "var/log/**/*.xz",
+            "var/log/**/*.old",
+            "var/log/**/*.[0-9]",
+            "var/log/journal/*",
+        ],
+    },
+    Rule {
+        category: "logs",
+        action: Action::Truncate,
+        patterns: &["var/log/**/*"],
+    },
+    Rule {
+        category: "tmp",
+        action: Action::Remove,
+        patterns: &["tmp/*", "var/tmp/*"],
+    },
+    Rule {
+        category: "random-seed",
+        action: Action::Remove,
+        patterns: &[
+            "var/lib/systemd/random-seed",
+            "var/lib/random-seed",
+            "var/lib/urandom/random-seed",
+            "var/lib/misc/random-seed",
+        ],
+    },
+    Rule {
+        category: "dhcp-leases",
+        action: Action::Remove,
+        patterns: &[
+            "var/lib/dhcp/*.lease*",
+            "var/lib/dhclient/*.lease*",
+            "var/lib/NetworkManager/*.lease*",
+            "var/lib/dhcpcd/*.lease*",
+            "var/db/dhcpcd/*.lease*",
+            "var/lib/systemd/netif/leases/*",
+            "var/lib/wicked/lease-*",
+        ],
+    },
+];
+
+#[derive(Debug, Default)]
+pub struct Category {
+    pub paths: u64,
+    pub bytes: u64,
+}
+
+/*
+ * What was removed or truncated, by category.
+ */
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub categories: BTreeMap<String, Category>,
+}
+
+impl Summary {
+    pub fn paths(&self) -> u64 {
+        self.categories.values().map(|c| c.paths).sum()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.categories.values().map(|c| c.bytes).sum()
+    }
+
+    pub fn record(&mut self, category: &str, bytes: u64) {
+        let c = self.categories.entry(category.to_string()).or_default();
+        c.paths += 1;
+        c.bytes += bytes;
+    }
+}
+
+/*
+ * The size of the files under a directory.
+ */
+pub fn disk_bytes(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn apply(
+    zroot: &Path,
+    category: &str,
+    action: Action,
+    pattern: &str,
+    summary: &mut Summary,
+) -> Result<()> {
+    let options = MatchOptions {
+        require_literal_leading_dot: false,
+        ..MatchOptions::new()
+    };
+    let full = format!(
+        "{}/{}",
+        glob::Pattern::escape(&zroot.to_string_lossy()),
+        pattern
+    );
+    let paths = glob::glob_with(&full, options)
+        .with_context(|| format!("invalid sanitize pattern {}", pattern))?;
+
+    for path in paths.filter_map(|p| p.ok()) {
+        /*
+         * In a dry run earlier rules have only been planned, so check the
+         * plan before acting on a path twice.
+         */
+        if !lexists(&path) || has_symlink_parent(zroot, &path) {
+            continue;
+        }
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+
+        match action {
+            Action::Remove => {
+                let bytes = if meta.is_dir() {
+                    let bytes = disk_bytes(&path);
+                    remove_dir_all(&path)?;
+                    bytes
+                } else {
+                    remove_file(&path)?;
+                    if meta.is_file() {
+                        meta.len()
+                    } else {
+                        0
+                    }
+                };
+                summary.record(category, bytes);
+            }
+            Action::Truncate => {
+                if !meta.is_file() || meta.len() == 0 {
+                    continue;
+                }
+                truncate_file(&path)?;
+                summary.record(category, meta.len());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Apply the built-in rules, then remove anything matching the extra patterns
+ * (from the distro profile).
+ */
+pub fn sanitize<P: AsRef<Path>, S: AsRef<str>>(zroot: P, extra: &[S]) -> Result<Summary> {
+    let zroot = zroot.as_ref();
+    let mut summary = Summary::default();
+
+    for rule in RULES {
+        for pattern in rule.patterns {
+            apply(zroot, rule.category, rule.action, pattern, &mut summary)?;
+        }
+    }
+
+    for pattern in extra {
+        apply(
+            zroot,
+            "profile",
+            Action::Remove,
+            pattern.as_ref(),
+            &mut summary,
+        )?;
+    }
+
+    for (name, c) in &summary.categories {
+        info!("{}: cleaned {} paths, {} bytes", name, c.paths, c.bytes);
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let zroot = test_dir(name);
+        for (path, contents) in files {
+            let path = zroot.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        zroot
+    }
+
+    fn contents(zroot: &Path, path: &str) -> Option<String> {
+        fs::read_to_string(zroot.join(path)).ok()
+    }
+
+    #[test]
+    fn fixture_tree() {
+        let zroot = root(
+            "sanitize",
+            &[
+                ("etc/machine-id", "0123456789abcdef\n"),
+                ("etc/ssh/ssh_host_ed25519_key", "key"),
+                ("etc/ssh/sshd_config", "PermitRootLogin no\n"),
+                ("root/.bash_history", "ls\n"),
+                ("var/log/messages", "boot\n"),
+                ("var/log/messages.1", "old boot\n"),
+                ("var/log/apt/history.log.2.gz", "gz"),
+                ("var/log/apt/history.log", "install\n"),
+                ("var/lib/dhcp/dhclient.eth0.leases", "lease"),
+                ("tmp/build/output", "tmp"),
+                ("usr/share/doc/README", "docs"),
+            ],
+        );
+
+        let summary = sanitize(&zroot, &["usr/share/doc/*"]).unwrap();
+        let machine_id = contents(&zroot, "etc/machine-id");
+        let host_key = contents(&zroot, "etc/ssh/ssh_host_ed25519_key");
+        let sshd_config = contents(&zroot, "etc/ssh/sshd_config");
+        let history = contents(&zroot, "root/.bash_history");
+        let messages = contents(&zroot, "var/log/messages");
+        let rotated = contents(&zroot, "var/log/messages.1");
+        let compressed = contents(&zroot, "var/log/apt/history.log.2.gz");
+        let apt_log = contents(&zroot, "var/log/apt/history.log");
+        let lease = contents(&zroot, "var/lib/dhcp/dhclient.eth0.leases");
+        let tmp = zroot.join("tmp/build").exists();
+        let doc = contents(&zroot, "usr/share/doc/README");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        /*
+         * machine-id and live logs are truncated, not removed.
+         */
+        assert_eq!(machine_id.as_deref(), Some(""));
+        assert_eq!(messages.as_deref(), Some(""));
+        assert_eq!(apt_log.as_deref(), Some(""));
+        assert_eq!(rotated, None);
+        assert_eq!(compressed, None);
+
+        assert_eq!(host_key, None);
+        assert_eq!(sshd_config.as_deref(), Some("PermitRootLogin no\n"));
+        assert_eq!(history, None);
+        assert_eq!(lease, None);
+        assert!(!tmp);
+        assert_eq!(doc, None);
+
+        let paths = |c: &str| summary.categories.get(c).map(|c| c.paths);
+        assert_eq!(paths("machine-id"), Some(1));
+        assert_eq!(paths("logs"), Some(4));
+        assert_eq!(paths("profile"), Some(1));
+        assert_eq!(summary.paths(), 10);
+    }
+
+    #[test]
+    fn symlink_parent() {
+        let outside = root("sanitize-outside", &[("messages", "host\n")]);
+        let zroot = root("sanitize-symlink", &[("etc/hostname", "zone\n")]);
+        fs::create_dir_all(zroot.join("var")).unwrap();
+        symlink(&outside, zroot.join("var/log")).unwrap();
+
+        let summary = sanitize(&zroot, &["var/log/*"]).unwrap();
+        let messages = contents(&outside, "messages");
+        fs::remove_dir_all(&zroot).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+
+        assert_eq!(messages.as_deref(), Some("host\n"));
+        assert_eq!(summary.paths(), 0);
+    }
+}
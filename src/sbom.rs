@@ -0,0 +1,645 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * A software bill of materials for the image, listing the packages found by
+ * packages::inventory, as SPDX 2.3 or CycloneDX 1.5 JSON. Packages are
+ * identified by package URL (purl) where the ecosystem has a purl type.
+ *
+ * SBOMs can also be read back into an Inventory, so that builds can be
+ * compared after their image roots are gone.
+ */
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::packages::{Inventory, Manager, Package};
+
+const TOOL: &str = env!("CARGO_PKG_NAME");
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Spdx,
+    CycloneDx,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "spdx" => Ok(Self::Spdx),
+            "cyclonedx" => Ok(Self::CycloneDx),
+            _ => Err(anyhow!(
+                "unknown SBOM format \"{}\", formats are: spdx, cyclonedx",
+                s
+            )),
+        }
+    }
+
+    /*
+     * The file name extension conventionally used for the format.
+     */
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Spdx => "spdx.json",
+            Self::CycloneDx => "cdx.json",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Spdx => "spdx",
+            Self::CycloneDx => "cyclonedx",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * The image the SBOM describes.
+ */
+pub struct Subject<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub uuid: &'a Uuid,
+    /*
+     * The distro profile, and the rule it was detected by.
+     */
+    pub distro: Option<(&'a str, &'a str)>,
+    /*
+     * ID and VERSION_ID from os-release.
+     */
+    pub os_id: &'a str,
+    pub os_version: &'a str,
+}
+
+/*
+ * Percent-encode a purl component.
+ */
+fn encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b".-_~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+pub fn purl(p: &Package, subject: &Subject) -> Option<String> {
+    let typ = match p.manager {
+        Manager::Dpkg => "deb",
+        Manager::Rpm => "rpm",
+        Manager::Apk => "apk",
+        Manager::Pacman => "alpm",
+        Manager::Xbps => return None,
+    };
+    if subject.os_id.is_empty() {
+        return None;
+    }
+
+    let mut version = p.version.as_str();
+    let mut qualifiers = vec![];
+    if let Some(a) = &p.arch {
+        qualifiers.push(format!("arch={}", encode(a)));
+    }
+    /*
+     * rpm purls carry the epoch as a qualifier rather than in the version.
+     */
+    if p.manager == Manager::Rpm {
+        if let Some((epoch, rest)) = version.split_once(':') {
+            qualifiers.push(format!("epoch={}", encode(epoch)));
+            version = rest;
+        }
+    }
+    if !subject.os_version.is_empty() {
+        qualifiers.push(format!(
+            "distro={}",
+            encode(&format!("{}-{}", subject.os_id, subject.os_version))
+        ));
+    }
+
+    let mut purl = format!(
+        "pkg:{}/{}/{}@{}",
+        typ,
+        encode(subject.os_id),
+        encode(&p.name),
+        encode(version)
+    );
+    if !qualifiers.is_empty() {
+        purl.push('?');
+        purl.push_str(&qualifiers.join("&"));
+    }
+    Some(purl)
+}
+
+fn distro_comment(subject: &Subject) -> String {
+    match subject.distro {
+        Some((name, rule)) => format!(
+            "distro profile {} (detected by {}), os-release {} {}",
+            name, rule, subject.os_id, subject.os_version
+        ),
+        None => format!("os-release {} {}", subject.os_id, subject.os_version),
+    }
+}
+
+fn spdx(subject: &Subject, inv: &Inventory, created: &str) -> Value {
+    let mut packages = vec![json!({
+        "SPDXID": "SPDXRef-Image",
+        "name": subject.name,
+        "versionInfo": subject.version,
+        "downloadLocation": "NOASSERTION",
+        "primaryPackagePurpose": "OPERATING-SYSTEM",
+        "comment": distro_comment(subject),
+    })];
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": "SPDXRef-Image",
+    })];
+
+    for (i, p) in inv.packages.iter().enumerate() {
+        let id = format!("SPDXRef-Package-{}-{}", p.manager, i);
+        let mut pkg = json!({
+            "SPDXID": id,
+            "name": p.name,
+            "versionInfo": p.version,
+            "downloadLocation": "NOASSERTION",
+            "licenseConcluded": "NOASSERTION",
+            /*
+             * Package managers record licenses in their own notations, which
+             * are not always valid SPDX expressions.
+             */
+            "licenseDeclared": "NOASSERTION",
+        });
+        let mut comment = vec![format!("installed by {}", p.manager)];
+        if let Some(l) = &p.license {
+            comment.push(format!("license: {}", l));
+        }
+        if let Some(s) = &p.source {
+            pkg["sourceInfo"] = json!(format!("built from source package {}", s));
+        }
+        pkg["comment"] = json!(comment.join(", "));
+        if let Some(purl) = purl(p, subject) {
+            pkg["externalRefs"] = json!([{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": purl,
+            }]);
+        }
+        packages.push(pkg);
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-Image",
+            "relationshipType": "CONTAINS",
+            "relatedSpdxElement": id,
+        }));
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}-{}", subject.name, subject.version),
+        "documentNamespace": format!(
+            "https://spdx.org/spdxdocs/{}-{}-{}",
+            encode(subject.name),
+            subject.version,
+            subject.uuid
+        ),
+        "creationInfo": {
+            "created": created,
+            "creators": [format!("Tool: {}-{}", TOOL, TOOL_VERSION)],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+fn cyclonedx(subject: &Subject, inv: &Inventory, created: &str) -> Value {
+    let mut properties = vec![
+        json!({ "name": format!("{}:os-release:id", TOOL), "value": subject.os_id }),
+        json!({ "name": format!("{}:os-release:version_id", TOOL), "value": subject.os_version }),
+    ];
+    if let Some((name, rule)) = subject.distro {
+        properties.push(json!({ "name": format!("{}:distro", TOOL), "value": name }));
+        properties.push(json!({ "name": format!("{}:distro_rule", TOOL), "value": rule }));
+    }
+
+    /*
+     * bom-refs must be unique, and the same package can be installed twice
+     * for different architectures without an arch qualifier to tell them
+     * apart.
+     */
+    let mut refs = HashSet::new();
+    let components: Vec<_> = inv
+        .packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let purl = purl(p, subject);
+            let mut bom_ref = purl
+                .clone()
+                .unwrap_or_else(|| format!("{}:{}@{}", p.manager, p.name, p.version));
+            if !refs.insert(bom_ref.clone()) {
+                bom_ref = format!("{}#{}", bom_ref, i);
+            }
+            let mut c = json!({
+                "type": "library",
+                "bom-ref": bom_ref,
+                "name": p.name,
+                "version": p.version,
+                "properties": [{ "name": format!("{}:package_manager", TOOL), "value": p.manager.to_string() }],
+            });
+            if let Some(purl) = purl {
+                c["purl"] = json!(purl);
+            }
+            if let Some(l) = &p.license {
+                c["licenses"] = json!([{ "license": { "name": l } }]);
+            }
+            if let Some(a) = &p.arch {
+                c["properties"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!({ "name": format!("{}:arch", TOOL), "value": a }));
+            }
+            if let Some(s) = &p.source {
+                c["properties"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!({ "name": format!("{}:source_package", TOOL), "value": s }));
+            }
+            c
+        })
+        .collect();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", Uuid::new_v4()),
+        "version": 1,
+        "metadata": {
+            "timestamp": created,
+            "tools": [{ "name": TOOL, "version": TOOL_VERSION }],
+            "component": {
+                "type": "operating-system",
+                "bom-ref": subject.uuid.to_string(),
+                "name": subject.name,
+                "version": subject.version,
+                "description": distro_comment(subject),
+                "properties": properties,
+            },
+        },
+        "components": components,
+    })
+}
+
+pub fn document(format: Format, subject: &Subject, inv: &Inventory) -> Value {
+    let created = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    match format {
+        Format::Spdx => spdx(subject, inv, &created),
+        Format::CycloneDx => cyclonedx(subject, inv, &created),
+    }
+}
+
+/*
+ * The package manager of a purl, the reverse of purl().
+ */
+fn purl_manager(purl: &str) -> Option<Manager> {
+    match purl.strip_prefix("pkg:")?.split('/').next()? {
+        "deb" => Some(Manager::Dpkg),
+        "rpm" => Some(Manager::Rpm),
+        "apk" => Some(Manager::Apk),
+        "alpm" => Some(Manager::Pacman),
+        _ => None,
+    }
+}
+
+fn decode(s: &str) -> String {
+    let mut out = vec![];
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            if let Some(v) = s
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(b[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn purl_qualifier(purl: &str, key: &str) -> Option<String> {
+    let (_, qualifiers) = purl.split_once('?')?;
+    qualifiers
+        .split('&')
+        .filter_map(|q| q.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| decode(v))
+}
+
+fn package(
+    manager: Option<Manager>,
+    name: Option<&str>,
+    version: Option<&str>,
+    arch: Option<String>,
+    source: Option<String>,
+) -> Option<Package> {
+    let mut p = Package::new(manager?, name?, version?);
+    p.arch = arch;
+    p.source = source;
+    Some(p)
+}
+
+fn read_spdx(packages: &[Value]) -> Vec<Package> {
+    packages
+        .iter()
+        .filter(|p| p["SPDXID"] != "SPDXRef-Image")
+        .filter_map(|p| {
+            let purl = p["externalRefs"]
+                .as_array()
+                .and_then(|refs| refs.iter().find(|r| r["referenceType"] == "purl"))
+                .and_then(|r| r["referenceLocator"].as_str());
+            let manager = p["comment"]
+                .as_str()
+                .and_then(|c| c.strip_prefix("installed by "))
+                .and_then(|c| Manager::parse(c.split(',').next().unwrap_or("")))
+                .or_else(|| purl.and_then(purl_manager));
+            let source = p["sourceInfo"]
+                .as_str()
+                .and_then(|s| s.strip_prefix("built from source package "))
+                .map(str::to_string);
+            package(
+                manager,
+                p["name"].as_str(),
+                p["versionInfo"].as_str(),
+                purl.and_then(|purl| purl_qualifier(purl, "arch")),
+                source,
+            )
+        })
+        .collect()
+}
+
+fn read_cyclonedx(components: &[Value]) -> Vec<Package> {
+    components
+        .iter()
+        .filter_map(|c| {
+            let property = |name: &str| {
+                let name = format!("{}:{}", TOOL, name);
+                c["properties"]
+                    .as_array()
+                    .and_then(|ps| ps.iter().find(|p| p["name"] == name.as_str()))
+                    .and_then(|p| p["value"].as_str())
+                    .map(str::to_string)
+            };
+            let purl = c["purl"].as_str();
+            let manager = property("package_manager")
+                .and_then(|m| Manager::parse(&m))
+                .or_else(|| purl.and_then(purl_manager));
+            package(
+                manager,
+                c["name"].as_str(),
+                c["version"].as_str(),
+                property("arch").or_else(|| purl.and_then(|purl| purl_qualifier(purl, "arch"))),
+                property("source_package"),
+            )
+        })
+        .collect()
+}
+
+/*
+ * Read the packages listed in an SBOM. Documents written by other tools are
+ * read as far as their package URLs allow; packages whose package manager
+ * cannot be determined are left out.
+ */
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Inventory> {
+    let path = path.as_ref();
+    let f = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let doc: Value = serde_json::from_reader(BufReader::new(f))
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let mut packages = if let Some(packages) = doc["packages"].as_array() {
+        read_spdx(packages)
+    } else if let Some(components) = doc["components"].as_array() {
+        read_cyclonedx(components)
+    } else {
+        return Err(anyhow!(
+            "{} is not an SPDX or CycloneDX document",
+            path.display()
+        ));
+    };
+    packages.sort_by(|a, b| {
+        (a.manager, &a.name, &a.arch, &a.version).cmp(&(b.manager, &b.name, &b.arch, &b.version))
+    });
+
+    Ok(Inventory {
+        databases: vec![path.display().to_string()],
+        packages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir;
+    use std::fs;
+
+    fn subject(uuid: &Uuid) -> Subject<'_> {
+        Subject {
+            name: "debian-12",
+            version: "20260101",
+            uuid,
+            distro: Some(("debian", "ID=debian")),
+            os_id: "debian",
+            os_version: "12",
+        }
+    }
+
+    fn pkg(manager: Manager, name: &str, version: &str, arch: Option<&str>) -> Package {
+        let mut p = Package::new(manager, name, version);
+        p.arch = arch.map(str::to_string);
+        p
+    }
+
+    fn inventory() -> Inventory {
+        let mut libc = pkg(Manager::Dpkg, "libc6", "2.36-9+deb12u4", Some("amd64"));
+        libc.source = Some("glibc".to_string());
+        libc.license = Some("LGPL-2.1+".to_string());
+        Inventory {
+            databases: vec!["/var/lib/dpkg/status".to_string()],
+            packages: vec![
+                pkg(Manager::Apk, "busybox", "1.36.1-r5", None),
+                libc,
+                pkg(Manager::Dpkg, "libstdc++6", "12.2.0-14", Some("amd64")),
+                pkg(Manager::Dpkg, "zlib1g", "1:1.2.13", None),
+                pkg(Manager::Dpkg, "zlib1g", "1:1.2.13", None),
+                pkg(Manager::Rpm, "bash", "5.1.8-6.el9", Some("x86_64")),
+                pkg(Manager::Xbps, "runit-void", "20231124_1", None),
+            ],
+        }
+    }
+
+    #[test]
+    fn purls() {
+        let uuid = Uuid::nil();
+        let s = subject(&uuid);
+        let of = |p: Package| purl(&p, &s);
+
+        assert_eq!(
+            of(pkg(Manager::Dpkg, "libstdc++6", "12.2.0-14", Some("amd64"))).as_deref(),
+            Some("pkg:deb/debian/libstdc%2B%2B6@12.2.0-14?arch=amd64&distro=debian-12")
+        );
+        /*
+         * dpkg keeps the epoch in the version, rpm moves it to a qualifier.
+         */
+        assert_eq!(
+            of(pkg(Manager::Dpkg, "zlib1g", "1:1.2.13.dfsg-1", None)).as_deref(),
+            Some("pkg:deb/debian/zlib1g@1%3A1.2.13.dfsg-1?distro=debian-12")
+        );
+        assert_eq!(
+            of(pkg(
+                Manager::Rpm,
+                "openssl",
+                "1:3.0.7-27.el9",
+                Some("x86_64")
+            ))
+            .as_deref(),
+            Some("pkg:rpm/debian/openssl@3.0.7-27.el9?arch=x86_64&epoch=1&distro=debian-12")
+        );
+        assert_eq!(
+            of(pkg(Manager::Pacman, "glibc", "2.39+r52", Some("x86_64"))).as_deref(),
+            Some("pkg:alpm/debian/glibc@2.39%2Br52?arch=x86_64&distro=debian-12")
+        );
+        assert_eq!(of(pkg(Manager::Xbps, "bash", "5.2.21_1", None)), None);
+
+        let unknown = Subject {
+            os_id: "",
+            ..subject(&uuid)
+        };
+        assert_eq!(
+            purl(&pkg(Manager::Dpkg, "bash", "5.2", None), &unknown),
+            None
+        );
+        let rolling = Subject {
+            os_version: "",
+            ..subject(&uuid)
+        };
+        assert_eq!(
+            purl(&pkg(Manager::Dpkg, "bash", "5.2", None), &rolling).as_deref(),
+            Some("pkg:deb/debian/bash@5.2")
+        );
+
+        assert_eq!(decode(&encode("a b/c%d+é")), "a b/c%d+é");
+    }
+
+    #[test]
+    fn spdx_document() {
+        let uuid = Uuid::nil();
+        let doc = document(Format::Spdx, &subject(&uuid), &inventory());
+
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 8);
+        assert_eq!(packages[0]["SPDXID"], "SPDXRef-Image");
+        assert_eq!(packages[0]["primaryPackagePurpose"], "OPERATING-SYSTEM");
+
+        let libc = &packages[2];
+        assert_eq!(libc["SPDXID"], "SPDXRef-Package-dpkg-1");
+        assert_eq!(libc["versionInfo"], "2.36-9+deb12u4");
+        assert_eq!(libc["sourceInfo"], "built from source package glibc");
+        assert_eq!(libc["comment"], "installed by dpkg, license: LGPL-2.1+");
+        assert_eq!(libc.get("primaryPackagePurpose"), None);
+        assert_eq!(
+            libc["externalRefs"][0]["referenceLocator"],
+            "pkg:deb/debian/libc6@2.36-9%2Bdeb12u4?arch=amd64&distro=debian-12"
+        );
+        assert_eq!(packages[7].get("externalRefs"), None);
+
+        let relationships = doc["relationships"].as_array().unwrap();
+        assert_eq!(relationships.len(), 8);
+        assert_eq!(relationships[0]["relationshipType"], "DESCRIBES");
+        assert_eq!(
+            relationships[2]["relatedSpdxElement"],
+            "SPDXRef-Package-dpkg-1"
+        );
+    }
+
+    #[test]
+    fn cyclonedx_document() {
+        let uuid = Uuid::nil();
+        let doc = document(Format::CycloneDx, &subject(&uuid), &inventory());
+
+        assert_eq!(doc["specVersion"], "1.5");
+        assert_eq!(doc["metadata"]["component"]["type"], "operating-system");
+        assert_eq!(doc["metadata"]["component"]["bom-ref"], uuid.to_string());
+
+        let components = doc["components"].as_array().unwrap();
+        assert_eq!(components.len(), 7);
+        assert_eq!(components[1]["licenses"][0]["license"]["name"], "LGPL-2.1+");
+
+        /*
+         * The duplicate zlib1g gets a bom-ref of its own.
+         */
+        let zlib = "pkg:deb/debian/zlib1g@1%3A1.2.13?distro=debian-12";
+        assert_eq!(components[3]["bom-ref"], zlib);
+        assert_eq!(components[4]["bom-ref"], format!("{}#4", zlib));
+        assert_eq!(components[4]["purl"], zlib);
+
+        assert_eq!(components[6]["bom-ref"], "xbps:runit-void@20231124_1");
+        assert_eq!(components[6].get("purl"), None);
+    }
+
+    #[test]
+    fn read_round_trip() {
+        let uuid = Uuid::nil();
+        let dir = test_dir("sbom");
+        let mut expected = inventory().packages;
+        for p in &mut expected {
+            p.license = None;
+        }
+        expected.sort_by(|a, b| {
+            (a.manager, &a.name, &a.arch, &a.version)
+                .cmp(&(b.manager, &b.name, &b.arch, &b.version))
+        });
+
+        for format in &[Format::Spdx, Format::CycloneDx] {
+            let path = dir.join(format!("image.{}", format.extension()));
+            let doc = document(*format, &subject(&uuid), &inventory());
+            fs::write(&path, serde_json::to_vec(&doc).unwrap()).unwrap();
+
+            let inv = read(&path).unwrap();
+            assert_eq!(inv.packages, expected, "{}", format);
+            assert_eq!(inv.databases, [path.display().to_string()]);
+        }
+
+        let path = dir.join("other.json");
+        fs::write(&path, "{\"bomFormat\": \"other\"}").unwrap();
+        let err = read(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,343 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Look for credentials left in the image root, such as private keys, cloud
+ * credentials and shell histories. Images built from a developer's machine or
+ * a container export are the usual source. The sanitize stage removes the
+ * state it knows about; this is the check that nothing else was left behind.
+ *
+ * The trees that belong to the package manager (/usr other than /usr/local,
+ * /lib, /bin and /sbin) are not searched, as they are full of test keys and
+ * would only produce noise. Neither are the kernel filesystems or /native.
+ */
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::utils::*;
+
+const SKIP: &[&str] = &[
+    "bin", "dev", "lib", "lib32", "lib64", "libx32", "native", "proc", "run", "sbin", "sys", "usr",
+];
+
+/*
+ * Only the start of files up to this size is searched for private keys.
+ */
+const MAX_CONTENT: u64 = 1024 * 1024;
+
+/*
+ * Credential files, by the end of their path. If a marker is given the file
+ * only counts if it contains it, as some of these files are also used for
+ * settings.
+ */
+const CREDENTIALS: &[(&str, Option<&str>)] = &[
+    (".aws/credentials", None),
+    (".azure/accessTokens.json", None),
+    (".azure/msal_token_cache.json", None),
+    (".config/gcloud/access_tokens.db", None),
+    (".config/gcloud/application_default_credentials.json", None),
+    (".config/gcloud/credentials.db", None),
+    (".docker/config.json", Some("\"auth\"")),
+    (".git-credentials", None),
+    (".kube/config", Some("user:")),
+    (".npmrc", Some("_auth")),
+    (".pypirc", Some("password")),
+    (".s3cfg", Some("secret_key")),
+    (".boto", Some("secret_access_key")),
+];
+
+const KEYSTORES: &[&str] = &["p12", "pfx"];
+
+const ENV_SAMPLES: &[&str] = &["example", "sample", "template", "dist"];
+
+#[derive(Debug)]
+pub struct Finding {
+    pub category: &'static str,
+    pub path: String,
+    pub detail: String,
+}
+
+/*
+ * Paths and authorized keys that are expected in the image. Each line of an
+ * allowlist file is either an authorized_keys entry (only its key is
+ * compared), or a glob pattern of paths in the image root. Lines starting
+ * with # are comments.
+ */
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    patterns: Vec<Pattern>,
+    keys: HashSet<String>,
+}
+
+impl Allowlist {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut allow = Allowlist::default();
+        for (n, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, _)) = authorized_key(line) {
+                allow.keys.insert(key.to_string());
+                continue;
+            }
+            let pattern = format!("/{}", line.trim_start_matches('/'));
+            allow.patterns.push(
+                Pattern::new(&pattern)
+                    .with_context(|| format!("{}:{}: invalid pattern", path.display(), n + 1))?,
+            );
+        }
+        Ok(allow)
+    }
+
+    fn path(&self, path: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_leading_dot: false,
+            ..MatchOptions::new()
+        };
+        self.patterns.iter().any(|p| p.matches_with(path, options))
+    }
+}
+
+/*
+ * The key and comment of an authorized_keys entry, skipping any options
+ * before the key type.
+ */
+fn authorized_key(line: &str) -> Option<(&str, Option<&str>)> {
+    let mut fields = line.split_whitespace();
+    while let Some(f) = fields.next() {
+        if f.starts_with("ssh-") || f.starts_with("ecdsa-") || f.starts_with("sk-") {
+            return fields.next().map(|key| (key, fields.next()));
+        }
+    }
+    None
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/*
+ * The type of the first PEM or PGP private key block in the data, e.g.
+ * "OPENSSH PRIVATE KEY".
+ */
+fn private_key(data: &[u8]) -> Option<String> {
+    const BEGIN: &[u8] = b"-----BEGIN ";
+    let mut rest = data;
+    while let Some(i) = rest.windows(BEGIN.len()).position(|w| w == BEGIN) {
+        rest = &rest[i + BEGIN.len()..];
+        let end = rest
+            .iter()
+            .position(|c| *c == b'-' || *c == b'\n')
+            .unwrap_or(rest.len());
+        let label = String::from_utf8_lossy(&rest[..end]);
+        if label.contains("PRIVATE KEY") {
+            return Some(label.into_owned());
+        }
+    }
+    None
+}
+
+fn read_start(path: &Path) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    File::open(path)
+        .and_then(|f| f.take(MAX_CONTENT).read_to_end(&mut data))
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(data)
+}
+
+fn check(zroot: &Path, path: &Path, allow: &Allowlist) -> Result<Option<Finding>> {
+    let rel = format!(
+        "/{}",
+        path.strip_prefix(zroot).unwrap_or(path).to_string_lossy()
+    );
+    if allow.path(&rel) {
+        return Ok(None);
+    }
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) if m.is_file() && m.len() > 0 => m,
+        _ => return Ok(None),
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let finding = |category, detail: String| {
+        Ok(Some(Finding {
+            category,
+            path: rel.clone(),
+            detail,
+        }))
+    };
+
+    if name.ends_with("_history") || name == ".history" {
+        return finding("history", format!("{} bytes of shell history", meta.len()));
+    }
+    if name == ".netrc" || name == "_netrc" {
+        return finding("netrc", "login credentials".to_string());
+    }
+    if name == ".env"
+        || name
+            .strip_prefix(".env.")
+            .is_some_and(|ext| !ENV_SAMPLES.contains(&ext))
+    {
+        return finding("env", "environment file".to_string());
+    }
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        if KEYSTORES.contains(&ext.to_ascii_lowercase().as_str()) {
+            return finding("private-key", "PKCS#12 keystore".to_string());
+        }
+    }
+
+    for (suffix, marker) in CREDENTIALS {
+        if !rel.ends_with(&format!("/{}", suffix)) {
+            continue;
+        }
+        let matched = match marker {
+            Some(m) => contains(&read_start(path)?, m.as_bytes()),
+            None => true,
+        };
+        if matched {
+            return finding("cloud-credentials", "credentials file".to_string());
+        }
+    }
+
+    if name == "authorized_keys" || name == "authorized_keys2" {
+        let data = String::from_utf8_lossy(&read_start(path)?).into_owned();
+        let unexpected: Vec<&str> = data
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter(|l| authorized_key(l).is_none_or(|(k, _)| !allow.keys.contains(k)))
+            .collect();
+        if unexpected.is_empty() {
+            return Ok(None);
+        }
+        let comments: Vec<&str> = unexpected
+            .iter()
+            .map(|l| match authorized_key(l) {
+                Some((_, Some(comment))) => comment,
+                _ => "no comment",
+            })
+            .collect();
+        return finding(
+            "authorized-keys",
+            format!(
+                "{} unexpected keys ({})",
+                unexpected.len(),
+                comments.join(", ")
+            ),
+        );
+    }
+
+    if meta.len() <= MAX_CONTENT {
+        if let Some(label) = private_key(&read_start(path)?) {
+            return finding("private-key", label);
+        }
+    }
+
+    Ok(None)
+}
+
+pub fn scan<P: AsRef<Path>>(zroot: P, allow: &Allowlist) -> Result<Vec<Finding>> {
+    let zroot = zroot.as_ref();
+
+    let mut roots = vec![zroot.join("usr/local")];
+    for entry in
+        fs::read_dir(zroot).with_context(|| format!("failed to read {}", zroot.display()))?
+    {
+        let entry = entry.with_context(|| format!("failed to read {}", zroot.display()))?;
+        if !SKIP.contains(&entry.file_name().to_string_lossy().as_ref()) {
+            roots.push(entry.path());
+        }
+    }
+    roots.sort();
+    /*
+     * WalkDir follows a symlink given as its root, which could lead out of
+     * the image root. Symlinks below the roots are not followed. A regular
+     * file given as the root, such as /.bash_history, is walked as itself.
+     */
+    roots.retain(|r| fs::symlink_metadata(r).is_ok_and(|m| m.is_dir() || m.is_file()));
+
+    let mut findings = vec![];
+    for root in roots {
+        /*
+         * In a dry run, skip what the sanitize stage has planned to remove.
+         */
+        let walk = WalkDir::new(&root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| lexists(e.path()));
+        for entry in walk {
+            let entry = entry.with_context(|| format!("failed to walk {}", root.display()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Some(f) = check(zroot, entry.path(), allow)? {
+                findings.push(f);
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_files() {
+        let zroot = std::env::temp_dir().join(format!("secrets-test-{}", std::process::id()));
+        for dir in &["root", "usr/local/etc", "usr/share/doc", "etc"] {
+            fs::create_dir_all(zroot.join(dir)).unwrap();
+        }
+        for (path, contents) in &[
+            (".env", "TOKEN=secret\n"),
+            (".bash_history", "ls\n"),
+            (".netrc", "machine example.com login me password secret\n"),
+            (".env.example", "TOKEN=\n"),
+            ("root/.bash_history", "ls\n"),
+            ("usr/local/etc/.netrc", "machine example.com\n"),
+            ("usr/share/doc/.env", "TOKEN=test\n"),
+            ("etc/hostname", "example\n"),
+        ] {
+            fs::write(zroot.join(path), contents).unwrap();
+        }
+
+        let findings = scan(&zroot, &Allowlist::default());
+        fs::remove_dir_all(&zroot).unwrap();
+        let found: Vec<(&str, &str)> = findings
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|f| (f.category, f.path.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("history", "/.bash_history"),
+                ("env", "/.env"),
+                ("netrc", "/.netrc"),
+                ("history", "/root/.bash_history"),
+                ("netrc", "/usr/local/etc/.netrc"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,890 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2022 Joyent, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * The stages of a standard image build. Each is a thin wrapper around the
+ * functions in actions, moving state in and out of the BuildContext.
+ */
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::actions::*;
+use crate::audit;
+use crate::cli;
+use crate::cloudinit;
+use crate::elf;
+use crate::error::{ErrorKind, ResultExt};
+use crate::files;
+use crate::init::InitSystem;
+use crate::kernel;
+use crate::lint::{self, Severity};
+use crate::minimize;
+use crate::network;
+use crate::osv;
+use crate::owners;
+use crate::payload::Payload;
+use crate::pipeline::{BuildContext, Image, Stage};
+use crate::profile::ProfileSet;
+use crate::sanitize;
+use crate::sbom;
+use crate::secrets;
+use crate::systemd;
+use crate::utils;
+
+fn get_zfs_parent(s: &str) -> Result<String> {
+    if !s.is_empty() {
+        return Ok(s.to_string());
+    }
+
+    let zonename = zonename::getzonename()
+        .context("failed to get zonename")
+        .kind(ErrorKind::Zfs)?;
+    if zonename == "global" {
+        // This bakes in a smartos-ism, and poorly. It would be better to
+        // query SMF for the blessed system_pool like we're supposed to.
+        // It may also be a good idea to search for alternate pools
+        // (e.g., rpool). For now at least, they can' use -z to specify.
+        Ok("zones".to_string())
+    } else {
+        Ok(format!("zones/{}/data", &zonename))
+    }
+}
+
+/*
+ * Remove a partially written output file.
+ */
+fn remove_output<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    if path.exists() {
+        fs::remove_file(path).with_context(|| format!("failed to remove {}", path.display()))?;
+        info!("removed {}", path.display());
+    }
+    Ok(())
+}
+
+pub struct CreateWorkspace;
+
+impl Stage for CreateWorkspace {
+    fn name(&self) -> &'static str {
+        "workspace"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let iuuid = format!("{}-{}", &ctx.result.uuid, ctx.build_date());
+
+        let (ws, zroot) = if ctx.opts.dry_run {
+            create_scratch(&iuuid)?
+        } else {
+            let zfs_parent = get_zfs_parent(&ctx.opts.zfs_parent)?;
+            let dataset = format!("{}/{}", &zfs_parent, &iuuid);
+            let zroot = create_dataset(&dataset)?;
+            (Workspace::Dataset(dataset), zroot)
+        };
+
+        ctx.workspace = Some(ws);
+        ctx.zroot = zroot;
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext) -> Result<()> {
+        match ctx.workspace.take() {
+            Some(ws) => ws.destroy(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct Extract;
+
+impl Stage for Extract {
+    fn name(&self) -> &'static str {
+        "extract"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let zroot = ctx.zroot()?;
+        install_tar(zroot, ctx.opts.tar()?)?;
+
+        /*
+         * Everything after extraction is only planned during a dry run.
+         */
+        if ctx.opts.dry_run {
+            utils::start_plan(zroot);
+        }
+        Ok(())
+    }
+}
+
+pub struct Owners;
+
+impl Stage for Owners {
+    fn name(&self) -> &'static str {
+        "owners"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let mapping = owners::Mapping {
+            uids: ctx.opts.uid_maps.iter().copied().collect(),
+            gids: ctx.opts.gid_maps.iter().copied().collect(),
+            squash: ctx.opts.squash_owners,
+        };
+        if mapping.is_empty() {
+            info!("no --uid-map, --gid-map or --squash-owners given, leaving owners alone");
+            return Ok(());
+        }
+
+        let report = owners::remap(ctx.zroot()?, &mapping).kind(ErrorKind::Input)?;
+        for ((kind, from, to), paths) in &report.remapped {
+            info!("{} {} -> {}: {} paths", kind, from, to, paths);
+        }
+        info!("changed the owner of {} paths", report.paths);
+        ctx.result.owners = Some(report);
+        Ok(())
+    }
+}
+
+pub struct Arch;
+
+impl Stage for Arch {
+    fn name(&self) -> &'static str {
+        "arch"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let report = elf::check_arch(ctx.zroot()?)?;
+        if let Some(arch) = report.arch() {
+            info!("image architecture is {}", arch);
+            ctx.result.arch = Some(arch.to_string());
+        }
+        Ok(())
+    }
+}
+
+pub struct Kernel;
+
+impl Stage for Kernel {
+    fn name(&self) -> &'static str {
+        "kernel"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let req = kernel::requirement(ctx.zroot()?).kind(ErrorKind::Input)?;
+        match (&req.minimum, req.libc) {
+            (Some((v, source)), _) => info!("{} requires kernel {}", source, v),
+            (None, Some(libc)) => info!("{} has no minimum kernel", libc),
+            (None, None) => info!("found no libc to read a minimum kernel from"),
+        }
+
+        let chosen =
+            kernel::choose(&ctx.opts.kernel, cli::DEFAULT_KERNEL, &req).kind(ErrorKind::Usage)?;
+        if ctx.opts.kernel == "auto" {
+            info!("using kernel version {}", chosen);
+        }
+        ctx.result.libc = req.libc.map(|l| l.to_string());
+        ctx.result.min_kernel = req.minimum.map(|(v, _)| v.to_string());
+        ctx.result.kernel = chosen.clone();
+        ctx.kernel = chosen;
+        Ok(())
+    }
+}
+
+pub struct Metadata;
+
+impl Stage for Metadata {
+    fn name(&self) -> &'static str {
+        "metadata"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let os_release = utils::read_os_release(ctx.zroot()?)
+            .context("failed to read os-release")
+            .kind(ErrorKind::Distro)?;
+        let opts = &ctx.opts;
+
+        let name = if !opts.image_name.is_empty() {
+            opts.image_name.clone()
+        } else {
+            if os_release.version_id.is_empty() {
+                warn!("os-release has no VERSION_ID, image name will not include a version");
+            }
+            format!("{}-{}", os_release.id, os_release.version_id)
+                .trim_end_matches('-')
+                .to_string()
+        };
+        let build_date = ctx.build_date();
+        let filesystem = format!("output/{}-{}.zfs.gz", &name, build_date);
+        let manifest = format!("output/{}-{}.json", &name, build_date);
+
+        let description = format!(
+            "Container-native {} 64-bit image. {}",
+            os_release.pretty_name, &opts.description
+        );
+
+        ctx.result.name = Some(name.clone());
+        ctx.image = Some(Image {
+            name,
+            pretty_name: os_release.pretty_name,
+            description,
+            filesystem,
+            manifest,
+        });
+        Ok(())
+    }
+}
+
+pub struct Modify;
+
+impl Stage for Modify {
+    fn name(&self) -> &'static str {
+        "modify"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let image = ctx.image()?;
+        let build_date = ctx.build_date();
+        let url = &ctx.opts.url;
+
+        let product = format!(
+            r#"Name: Triton Instance
+Image: {} {}
+Documentation: {}
+Description: {}
+
+"#,
+            &image.pretty_name, build_date, url, &image.description
+        );
+
+        let motd = format!(
+            r#"         *--+--*--*
+         |\ |\ |\ |\
+         | \| \| \| \     #####  ####   #  #####  ###   #   # TM
+         +--*--+--*--*      #    #   #  #    #   #   #  ##  #
+         |\ |\ |\ |\ |      #    ####   #    #   #   #  # # #
+         | \| \| \| \|      #    #  #   #    #   #   #  #  ##
+         *--+--+--+--+      #    #   #  #    #    ###   #   #
+          \ |\ |\ |\ |
+           \| \| \| \|     LX Instance ({} {})
+            *--+--*--*     {}
+
+"#,
+            &image.pretty_name, build_date, url
+        );
+
+        modify_image(ctx.zroot()?, &product, &motd)
+    }
+}
+
+pub struct GuestTools;
+
+impl Stage for GuestTools {
+    fn name(&self) -> &'static str {
+        "guest-tools"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let payload = Payload::new(ctx.opts.guest_dir.as_ref());
+        let profiles = ProfileSet::load(&ctx.opts.profiles).kind(ErrorKind::Usage)?;
+        let setup = install_guest_tools(
+            ctx.zroot()?,
+            &payload,
+            &profiles,
+            ctx.opts.distro.as_deref(),
+        )?;
+        let distro = setup.distro;
+        ctx.result.distro = Some(distro.profile.name.clone());
+        ctx.result.distro_rule = Some(distro.rule.clone());
+        ctx.result.distro_confidence = Some(distro.confidence.to_string());
+        ctx.distro = Some(distro);
+        if let Some((init, rule)) = setup.init {
+            ctx.result.init = Some(init.to_string());
+            ctx.result.init_rule = Some(rule);
+            ctx.init = Some(init);
+        }
+        ctx.result.boot_hook = Some(setup.boot_hook.to_string());
+        ctx.boot_hook = Some(setup.boot_hook);
+        Ok(())
+    }
+}
+
+pub struct CloudInit;
+
+impl Stage for CloudInit {
+    fn name(&self) -> &'static str {
+        "cloud-init"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let zroot = ctx.zroot()?;
+        let marker = match cloudinit::detect(zroot) {
+            Some(m) => m,
+            None => {
+                info!("cloud-init is not installed");
+                if ctx.opts.no_mdata_hooks {
+                    warn!("--no-mdata-hooks has no effect without cloud-init");
+                }
+                return Ok(());
+            }
+        };
+        info!(
+            "found cloud-init ({}), using the SmartOS datasource",
+            marker
+        );
+
+        cloudinit::configure(zroot).kind(ErrorKind::Guest)?;
+        if ctx.opts.no_mdata_hooks {
+            cloudinit::remove_mdata_hooks(zroot).kind(ErrorKind::Guest)?;
+        }
+        ctx.result.cloud_init = Some(marker.to_string());
+        ctx.result.mdata_hooks = !ctx.opts.no_mdata_hooks;
+        Ok(())
+    }
+}
+
+pub struct LxCompat;
+
+impl Stage for LxCompat {
+    fn name(&self) -> &'static str {
+        "lx-compat"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let zroot = ctx.zroot()?;
+        let init = match ctx.init {
+            Some(i) => Some(i),
+            None => InitSystem::detect(zroot).map(|(i, _)| i),
+        };
+        if init != Some(InitSystem::Systemd) {
+            info!("not a systemd image, nothing to do");
+            return Ok(());
+        }
+
+        let mut units = ctx.opts.mask_units.clone();
+        if let Some(d) = &ctx.distro {
+            units.extend(d.profile.mask_units.iter().cloned());
+        }
+
+        let changes = systemd::lx_compat(zroot, &units).kind(ErrorKind::Guest)?;
+        info!(
+            "masked {} units{}",
+            changes.masked.len(),
+            match &changes.default_target {
+                Some(t) => format!(", default target is now {}", t),
+                None => String::new(),
+            }
+        );
+        ctx.result.masked_units = changes.masked;
+        ctx.result.default_target = changes.default_target;
+        Ok(())
+    }
+}
+
+pub struct Network;
+
+impl Stage for Network {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        if ctx.opts.keep_network {
+            info!("--keep-network given, not changing network configuration");
+            return Ok(());
+        }
+
+        let zroot = ctx.zroot()?;
+        let init = match ctx.init {
+            Some(i) => Some(i),
+            None => InitSystem::detect(zroot).map(|(i, _)| i),
+        };
+        let strategies = match &ctx.distro {
+            Some(d) => d.profile.network.clone(),
+            None => Default::default(),
+        };
+
+        let done = network::neutralize(zroot, init, &strategies).kind(ErrorKind::Guest)?;
+        if done.is_empty() {
+            info!("no network managers found");
+        }
+        ctx.result.network = done
+            .iter()
+            .map(|n| (n.stack.to_string(), n.strategy.to_string()))
+            .collect();
+        Ok(())
+    }
+}
+
+pub struct Sanitize;
+
+impl Stage for Sanitize {
+    fn name(&self) -> &'static str {
+        "sanitize"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let extra = match &ctx.distro {
+            Some(d) => d.profile.sanitize.clone(),
+            None => vec![],
+        };
+
+        let summary = sanitize::sanitize(ctx.zroot()?, &extra).kind(ErrorKind::Guest)?;
+        info!(
+            "removed host specific state: {} paths, {} bytes",
+            summary.paths(),
+            summary.bytes()
+        );
+        ctx.result.sanitized = Some(summary);
+        Ok(())
+    }
+}
+
+pub struct Minimize;
+
+impl Stage for Minimize {
+    fn name(&self) -> &'static str {
+        "minimize"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        if !ctx.opts.minimize {
+            info!("no --minimize given, keeping documentation and locales");
+            return Ok(());
+        }
+
+        let rules = match &ctx.distro {
+            Some(d) => d.profile.minimize.clone(),
+            None => Default::default(),
+        };
+
+        let report = minimize::minimize(ctx.zroot()?, &rules).kind(ErrorKind::Guest)?;
+        info!(
+            "minimized the image: {} paths, {} bytes",
+            report.summary.paths(),
+            report.summary.bytes()
+        );
+        ctx.result.minimized = Some(report);
+        Ok(())
+    }
+}
+
+pub struct Secrets;
+
+impl Stage for Secrets {
+    fn name(&self) -> &'static str {
+        "secrets"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let allow = match &ctx.opts.secrets_allow {
+            Some(path) => secrets::Allowlist::load(path).kind(ErrorKind::Input)?,
+            None => secrets::Allowlist::default(),
+        };
+
+        let findings = secrets::scan(ctx.zroot()?, &allow).kind(ErrorKind::Input)?;
+        for f in &findings {
+            warn!("{} {}: {}", f.category, f.path, f.detail);
+        }
+        let count = findings.len();
+        ctx.result.secrets = Some(findings);
+
+        if count > 0 && ctx.opts.fail_on_secrets {
+            return Err(anyhow::anyhow!(
+                "found {} possible secrets in the image, remove them or add them to --secrets-allow",
+                count
+            ))
+            .kind(ErrorKind::Secrets);
+        }
+        info!("found {} possible secrets", count);
+        Ok(())
+    }
+}
+
+pub struct Audit;
+
+impl Stage for Audit {
+    fn name(&self) -> &'static str {
+        "audit"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let allow = match &ctx.distro {
+            Some(d) => audit::Allowlist::new(&d.profile.audit).kind(ErrorKind::Distro)?,
+            None => audit::Allowlist::default(),
+        };
+
+        let report = audit::audit(ctx.zroot()?, &allow).kind(ErrorKind::Input)?;
+        for f in &report.findings {
+            warn!("{} {}: {}", f.category, f.path, f.detail);
+        }
+        let count = report.findings.len();
+        let allowed = report.allowed;
+        ctx.result.audit = Some(report);
+
+        if count > 0 && ctx.opts.fail_on_audit {
+            return Err(anyhow::anyhow!(
+                "found {} unexpected permissions in the image, fix them or allow them in the distro profile",
+                count
+            ))
+            .kind(ErrorKind::Audit);
+        }
+        info!(
+            "found {} unexpected permissions, {} allowed",
+            count, allowed
+        );
+        Ok(())
+    }
+}
+
+pub struct Lint;
+
+impl Stage for Lint {
+    fn name(&self) -> &'static str {
+        "lint"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let payload = Payload::new(ctx.opts.guest_dir.as_ref());
+        let profile = ctx.distro.as_ref().map(|d| &d.profile);
+        let report = lint::lint(ctx.zroot()?, &payload, profile, ctx.boot_hook);
+
+        let errors = report.count(Severity::Error);
+        let warnings = report.count(Severity::Warning);
+        info!("{} errors, {} warnings", errors, warnings);
+        ctx.result.lint = Some(report);
+
+        let fail = if ctx.opts.strict_lint {
+            errors + warnings > 0
+        } else {
+            ctx.opts.fail_on_lint && errors > 0
+        };
+        if fail {
+            return Err(anyhow::anyhow!(
+                "the image failed lint checks with {} errors and {} warnings",
+                errors,
+                warnings
+            ))
+            .kind(ErrorKind::Lint);
+        }
+        Ok(())
+    }
+}
+
+/*
+ * There is no rollback: the report is kept when the build fails, as it says
+ * why.
+ */
+pub struct Vulns;
+
+impl Stage for Vulns {
+    fn name(&self) -> &'static str {
+        "vulns"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let database = match &ctx.opts.osv_db {
+            Some(db) => db.clone(),
+            None => {
+                info!("no --osv-db given, not scanning for vulnerabilities");
+                return Ok(());
+            }
+        };
+        let threshold = match &ctx.opts.fail_on_vuln {
+            Some(s) => Some(
+                osv::Severity::parse(s)
+                    .ok_or_else(|| anyhow::anyhow!("unknown severity {}", s))
+                    .kind(ErrorKind::Usage)?,
+            ),
+            None => None,
+        };
+
+        let ecosystem = match &ctx.opts.osv_ecosystem {
+            Some(e) => e.clone(),
+            None => {
+                let os_release = utils::read_os_release(ctx.zroot()?).kind(ErrorKind::Input)?;
+                osv::ecosystem(&os_release.id, &os_release.version_id)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "no OSV ecosystem is known for {} {}, use --osv-ecosystem",
+                            os_release.id,
+                            os_release.version_id
+                        )
+                    })
+                    .kind(ErrorKind::Distro)?
+            }
+        };
+
+        let db = osv::Database::load(&database).kind(ErrorKind::Input)?;
+        if db.is_empty() {
+            warn!("{} has no vulnerabilities", &database);
+        }
+        let report = osv::scan(&db, &ecosystem, ctx.packages()?);
+        for f in &report.findings {
+            info!("{}", f);
+        }
+        info!(
+            "found {} vulnerabilities in {} packages, using {} entries for {}",
+            report.findings.len(),
+            report.scanned,
+            report.entries,
+            &ecosystem
+        );
+
+        let image = ctx.image()?;
+        let output = format!("output/{}-{}.vulns.json", &image.name, ctx.build_date());
+        let doc = report.document(&image.name, ctx.build_date());
+        let failed = threshold.map(|t| (t, report.at_least(t)));
+        ctx.result.vulns = Some(report);
+
+        if ctx.opts.dry_run {
+            info!("dry run, not creating {}", &output);
+        } else {
+            fs::create_dir_all("output")
+                .context("Unable to create output directory")
+                .kind(ErrorKind::Output)?;
+            let f = fs::File::create(&output)
+                .with_context(|| format!("failed to create {}", &output))
+                .kind(ErrorKind::Output)?;
+            serde_json::to_writer_pretty(f, &doc)
+                .with_context(|| format!("failed to write {}", &output))
+                .kind(ErrorKind::Output)?;
+            info!("wrote vulnerability report to {}", &output);
+            ctx.result.vulns_report = Some(output.into());
+        }
+
+        match failed {
+            Some((threshold, n)) if n > 0 => Err(anyhow::anyhow!(
+                "the image has {} vulnerabilities of severity {} or higher",
+                n,
+                threshold
+            ))
+            .kind(ErrorKind::Vulns),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub struct Package;
+
+impl Stage for Package {
+    fn name(&self) -> &'static str {
+        "package"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let filesystem = ctx.image()?.filesystem.clone();
+
+        if ctx.opts.dry_run {
+            info!("dry run, not creating {}", &filesystem);
+            return Ok(());
+        }
+
+        let dataset = match &ctx.workspace {
+            Some(Workspace::Dataset(ds)) => ds,
+            _ => {
+                return Err(anyhow::anyhow!("no dataset to package")).kind(ErrorKind::Internal);
+            }
+        };
+
+        fs::create_dir_all("output")
+            .context("Unable to create output directory")
+            .kind(ErrorKind::Output)?;
+        create_dataset_gzip(dataset, &filesystem)?;
+        ctx.result.filesystem = Some(filesystem.into());
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext) -> Result<()> {
+        if ctx.opts.dry_run {
+            return Ok(());
+        }
+        match &ctx.image {
+            Some(image) => remove_output(&image.filesystem),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct WriteManifest;
+
+impl Stage for WriteManifest {
+    fn name(&self) -> &'static str {
+        "manifest"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let output = ctx.image()?.manifest.clone();
+
+        if ctx.opts.dry_run {
+            info!("dry run, not creating {}", &output);
+            return Ok(());
+        }
+
+        create_manifest(ctx.manifest()?, &output)?;
+        ctx.result.manifest = Some(output.into());
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext) -> Result<()> {
+        if ctx.opts.dry_run {
+            return Ok(());
+        }
+        match &ctx.image {
+            Some(image) => remove_output(&image.manifest),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct Sbom;
+
+impl Stage for Sbom {
+    fn name(&self) -> &'static str {
+        "sbom"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        let format = match &ctx.opts.sbom {
+            Some(f) => sbom::Format::parse(f).kind(ErrorKind::Usage)?,
+            None => {
+                info!("no --sbom format given, not writing an SBOM");
+                return Ok(());
+            }
+        };
+
+        let os_release = utils::read_os_release(ctx.zroot()?).kind(ErrorKind::Input)?;
+        let image = ctx.image()?;
+        let output = format!(
+            "output/{}-{}.{}",
+            &image.name,
+            ctx.build_date(),
+            format.extension()
+        );
+        let name = image.name.clone();
+        ctx.packages()?;
+
+        let inv = ctx.packages.as_ref().unwrap();
+        let subject = sbom::Subject {
+            name: &name,
+            version: ctx.build_date(),
+            uuid: &ctx.result.uuid,
+            distro: ctx
+                .distro
+                .as_ref()
+                .map(|d| (d.profile.name.as_str(), d.rule.as_str())),
+            os_id: &os_release.id,
+            os_version: &os_release.version_id,
+        };
+        let doc = sbom::document(format, &subject, inv);
+        let count = inv.packages.len();
+        ctx.result.sbom_format = Some(format.to_string());
+        ctx.result.sbom_packages = Some(count);
+
+        if ctx.opts.dry_run {
+            info!("dry run, not creating {}", &output);
+            return Ok(());
+        }
+
+        fs::create_dir_all("output")
+            .context("Unable to create output directory")
+            .kind(ErrorKind::Output)?;
+        let f = fs::File::create(&output)
+            .with_context(|| format!("failed to create {}", &output))
+            .kind(ErrorKind::Output)?;
+        serde_json::to_writer_pretty(f, &doc)
+            .with_context(|| format!("failed to write {}", &output))
+            .kind(ErrorKind::Output)?;
+        info!("wrote {} SBOM of {} packages to {}", format, count, &output);
+        ctx.result.sbom = Some(output.into());
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext) -> Result<()> {
+        match &ctx.result.sbom {
+            Some(path) if !ctx.opts.dry_run => remove_output(path),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub struct FileList;
+
+impl Stage for FileList {
+    fn name(&self) -> &'static str {
+        "files"
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        if !ctx.opts.file_list {
+            info!("no --file-list given, not writing a file list");
+            return Ok(());
+        }
+
+        let image = ctx.image()?;
+        let output = format!(
+            "output/{}-{}.{}",
+            &image.name,
+            ctx.build_date(),
+            files::EXTENSION
+        );
+        if ctx.opts.dry_run {
+            info!("dry run, not creating {}", &output);
+            return Ok(());
+        }
+
+        let entries = files::list(ctx.zroot()?).kind(ErrorKind::Input)?;
+        fs::create_dir_all("output")
+            .context("Unable to create output directory")
+            .kind(ErrorKind::Output)?;
+        files::write(&entries, &output).kind(ErrorKind::Output)?;
+        info!("wrote a list of {} files to {}", entries.len(), &output);
+        ctx.result.file_list = Some(output.into());
+        Ok(())
+    }
+
+    fn rollback(&self, ctx: &mut BuildContext) -> Result<()> {
+        match &ctx.result.file_list {
+            Some(path) if !ctx.opts.dry_run => remove_output(path),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub struct Cleanup;
+
+impl Stage for Cleanup {
+    fn name(&self) -> &'static str {
+        "cleanup"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &mut BuildContext) -> Result<()> {
+        match ctx.workspace.take() {
+            Some(ws) => ws.destroy(),
+            None => Ok(()),
+        }
+    }
+}
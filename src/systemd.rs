@@ -0,0 +1,256 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Many of the units enabled in a stock systemd image expect hardware, kernel
+ * interfaces or privileges that an lx zone does not have, and boot into a
+ * pile of failed units. The lx compatibility pass masks those units (the
+ * curated list in files/lx-masked-units, plus any named by the distro profile
+ * or on the command line) and makes sure the image boots to multi-user.target
+ * rather than a graphical target.
+ */
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::*;
+
+const UNIT_DIRS: &[&str] = &[
+    "etc/systemd/system",
+    "usr/lib/systemd/system",
+    "lib/systemd/system",
+];
+
+/*
+ * The changes made by the compatibility pass, for the build result.
+ */
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub masked: Vec<String>,
+    pub default_target: Option<String>,
+}
+
+pub fn curated_units() -> Vec<&'static str> {
+    include_str!("../files/lx-masked-units")
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+/*
+ * Find the file a unit is loaded from. Instances of template units, such as
+ * getty@tty1.service, are loaded from the template.
+ */
+fn find_unit(zroot: &Path, unit: &str) -> Option<PathBuf> {
+    let template = match (unit.find('@'), unit.rfind('.')) {
+        (Some(at), Some(dot)) if at < dot => Some(format!("{}{}", &unit[..=at], &unit[dot..])),
+        _ => None,
+    };
+
+    UNIT_DIRS
+        .iter()
+        .flat_map(|d| {
+            let dir = zroot.join(d);
+            let mut paths = vec![dir.join(unit)];
+            if let Some(t) = &template {
+                paths.push(dir.join(t));
+            }
+            paths
+        })
+        .find(|p| lexists(p))
+}
+
+fn is_masked(path: &Path) -> bool {
+    fs::read_link(path).is_ok_and(|t| t == Path::new("/dev/null"))
+}
+
+fn mask_unit(zroot: &Path, unit: &str) -> Result<bool> {
+    if find_unit(zroot, unit).is_none() {
+        trace!("{} is not present, not masking", unit);
+        return Ok(false);
+    }
+
+    let dst = zroot.join("etc/systemd/system").join(unit);
+    if is_masked(&dst) {
+        debug!("{} is already masked", unit);
+        return Ok(false);
+    }
+    if lexists(&dst) {
+        if fs::symlink_metadata(&dst).is_ok_and(|m| m.file_type().is_symlink()) {
+            remove_file(&dst)?;
+        } else {
+            warn!(
+                "not masking {}, it is overridden in /etc/systemd/system",
+                unit
+            );
+            return Ok(false);
+        }
+    }
+
+    create_symlink("/dev/null", &dst, 0, 0)?;
+    info!("masked {}", unit);
+    Ok(true)
+}
+
+/*
+ * Point default.target at multi-user.target unless it already is.
+ */
+fn set_default_target(zroot: &Path) -> Result<Option<String>> {
+    let target = match UNIT_DIRS[1..]
+        .iter()
+        .map(|d| Path::new(d).join("multi-user.target"))
+        .find(|p| exists(zroot.join(p)))
+    {
+        Some(t) => Path::new("/").join(t),
+        None => {
+            warn!("multi-user.target is not present, not setting the default target");
+            return Ok(None);
+        }
+    };
+
+    let link = zroot.join("etc/systemd/system/default.target");
+    if let Ok(current) = fs::read_link(&link) {
+        if current.file_name() == target.file_name() {
+            return Ok(None);
+        }
+    }
+    if lexists(&link) {
+        remove_file(&link)?;
+    }
+
+    create_symlink(&target, &link, 0, 0)?;
+    info!("set default target to {}", target.display());
+    Ok(Some(target.display().to_string()))
+}
+
+pub fn lx_compat<P: AsRef<Path>, S: AsRef<str>>(zroot: P, extra: &[S]) -> Result<Changes> {
+    let zroot = zroot.as_ref();
+    let mut changes = Changes::default();
+
+    let system = zroot.join("etc/systemd/system");
+    if !exists(&system) {
+        mkdirp(&system, 0, 0, 0o755)?;
+    }
+
+    let mut units = curated_units();
+    for u in extra {
+        if !units.contains(&u.as_ref()) {
+            units.push(u.as_ref());
+        }
+    }
+
+    for unit in units {
+        if mask_unit(zroot, unit)? {
+            changes.masked.push(unit.to_string());
+        }
+    }
+
+    changes.default_target = set_default_target(zroot)?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(name: &str, units: &[&str]) -> PathBuf {
+        let zroot = test_dir(name);
+        let dir = zroot.join("lib/systemd/system");
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(zroot.join("etc/systemd/system")).unwrap();
+        for u in units {
+            fs::write(dir.join(u), "[Unit]\n").unwrap();
+        }
+        zroot
+    }
+
+    fn link(zroot: &Path, unit: &str) -> Option<PathBuf> {
+        fs::read_link(zroot.join("etc/systemd/system").join(unit)).ok()
+    }
+
+    #[test]
+    fn mask_curated() {
+        let zroot = root(
+            "systemd-mask",
+            &[
+                "systemd-udevd.service",
+                "getty@.service",
+                "graphical.target",
+            ],
+        );
+        let changes = lx_compat(&zroot, &["rsyslog.service"]).unwrap();
+        let udevd = link(&zroot, "systemd-udevd.service");
+        let getty = link(&zroot, "getty@tty1.service");
+        let timesyncd = link(&zroot, "systemd-timesyncd.service");
+        let rsyslog = link(&zroot, "rsyslog.service");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(
+            changes.masked,
+            ["systemd-udevd.service", "getty@tty1.service"]
+        );
+        assert_eq!(udevd, Some(PathBuf::from("/dev/null")));
+        assert_eq!(getty, Some(PathBuf::from("/dev/null")));
+        assert_eq!(timesyncd, None);
+        assert_eq!(rsyslog, None);
+    }
+
+    #[test]
+    fn mask_twice() {
+        let zroot = root("systemd-mask-twice", &["systemd-udevd.service"]);
+        assert!(mask_unit(&zroot, "systemd-udevd.service").unwrap());
+        let again = mask_unit(&zroot, "systemd-udevd.service").unwrap();
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(!again);
+    }
+
+    #[test]
+    fn mask_override() {
+        let zroot = root("systemd-mask-override", &["systemd-udevd.service"]);
+        fs::write(
+            zroot.join("etc/systemd/system/systemd-udevd.service"),
+            "[Unit]\n",
+        )
+        .unwrap();
+        let masked = mask_unit(&zroot, "systemd-udevd.service").unwrap();
+        let udevd = link(&zroot, "systemd-udevd.service");
+        fs::remove_dir_all(&zroot).unwrap();
+        assert!(!masked);
+        assert_eq!(udevd, None);
+    }
+
+    #[test]
+    fn default_target() {
+        let zroot = root("systemd-target", &["multi-user.target", "graphical.target"]);
+        create_symlink(
+            "/lib/systemd/system/graphical.target",
+            zroot.join("etc/systemd/system/default.target"),
+            0,
+            0,
+        )
+        .unwrap();
+        let set = set_default_target(&zroot).unwrap();
+        let again = set_default_target(&zroot).unwrap();
+        let target = link(&zroot, "default.target");
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(
+            set.as_deref(),
+            Some("/lib/systemd/system/multi-user.target")
+        );
+        assert_eq!(again, None);
+        assert_eq!(
+            target,
+            Some(PathBuf::from("/lib/systemd/system/multi-user.target"))
+        );
+    }
+}
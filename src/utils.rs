@@ -6,21 +6,121 @@
 
 /*
  * Copyright 2022 Joyent, Inc.
- * Copyright 2023 MNX Cloud, Inc.
+ * Copyright 2026 MNX Cloud, Inc.
  */
 
 use anyhow::{bail, Context, Result};
 use errno::errno;
-use std::ffi::CString;
+use os_release::OsRelease;
+use std::collections::HashMap;
+use std::ffi::{CString, OsString};
 use std::fs;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/*
+ * When a plan is active (--dry-run) the helpers below record the change they
+ * would have made instead of touching the filesystem. Paths under the plan
+ * root are recorded relative to it so that plans from different builds can
+ * be diffed. The overlay tracks which paths the plan has created or removed
+ * so that later existence checks see the planned state of the image.
+ */
+struct Plan {
+    root: PathBuf,
+    changes: Vec<String>,
+    overlay: HashMap<PathBuf, bool>,
+}
+
+static PLAN: Mutex<Option<Plan>> = Mutex::new(None);
+
+pub fn start_plan<P: AsRef<Path>>(root: P) {
+    *PLAN.lock().unwrap() = Some(Plan {
+        root: root.as_ref().to_path_buf(),
+        changes: vec![],
+        overlay: HashMap::new(),
+    });
+}
+
+pub fn finish_plan() -> Vec<String> {
+    match PLAN.lock().unwrap().take() {
+        Some(plan) => plan.changes,
+        None => vec![],
+    }
+}
+
+/*
+ * Whether a plan is active, in which case changes have been recorded rather
+ * than made.
+ */
+pub fn is_planning() -> bool {
+    PLAN.lock().unwrap().is_some()
+}
+
+fn plan_path(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(p) => Path::new("/").join(p).display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/*
+ * Record a change if a plan is active. Returns true if the change was
+ * recorded, in which case the caller must not perform it.
+ */
+fn plan(op: &str, path: &Path, exists: Option<bool>, detail: &str) -> bool {
+    let mut guard = PLAN.lock().unwrap();
+    let plan = match guard.as_mut() {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let mut change = format!("{:<8} {}", op, plan_path(&plan.root, path));
+    if !detail.is_empty() {
+        change.push(' ');
+        change.push_str(detail);
+    }
+    debug!("plan: {}", &change);
+    plan.changes.push(change);
+
+    if let Some(e) = exists {
+        plan.overlay.insert(path.to_path_buf(), e);
+    }
+
+    true
+}
+
+fn planned_exists(path: &Path) -> Option<bool> {
+    let guard = PLAN.lock().unwrap();
+    guard.as_ref().and_then(|p| p.overlay.get(path).copied())
+}
+
+/*
+ * Like Path::exists(), but takes planned changes into account.
+ */
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    planned_exists(path).unwrap_or_else(|| path.exists())
+}
+
+/*
+ * Like exists(), but does not follow symlinks, so dangling symlinks are
+ * reported as existing.
+ */
+pub fn lexists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    planned_exists(path).unwrap_or_else(|| fs::symlink_metadata(path).is_ok())
+}
+
+fn perms_detail(owner: u32, group: u32, mode: u32) -> String {
+    format!("({}:{} {:o})", owner, group, mode)
+}
 
 fn set_permissions<P: AsRef<Path>>(path: P, mode: u32) -> Result<()> {
     let path = path.as_ref();
     let perms = fs::Permissions::from_mode(mode);
-    fs::set_permissions(&path, perms).with_context(|| {
+    fs::set_permissions(path, perms).with_context(|| {
         format!(
             "failed to set permissions to {:o} on {}",
             mode,
@@ -57,17 +157,28 @@ fn chown<P: AsRef<Path>>(path: P, owner: u32, group: u32) -> Result<()> {
 
 pub fn mkdirp<P: AsRef<Path>>(path: P, owner: u32, group: u32, mode: u32) -> Result<()> {
     let path = path.as_ref();
-    println!("creating dir {}", &path.display());
-    fs::create_dir_all(&path).with_context(|| format!("mkdir -p {}", &path.display()))?;
-    change_perms(&path, owner, group, mode)?;
+    if plan("mkdir", path, Some(true), &perms_detail(owner, group, mode)) {
+        return Ok(());
+    }
+    debug!("creating dir {}", &path.display());
+    fs::create_dir_all(path).with_context(|| format!("mkdir -p {}", &path.display()))?;
+    change_perms(path, owner, group, mode)?;
     Ok(())
 }
 
 pub fn create_file_contents<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
     let path = path.as_ref();
-
-    println!("creating file {}", &path.display());
     let contents = contents.as_ref();
+
+    if plan(
+        "write",
+        path,
+        Some(true),
+        &format!("({} bytes)", contents.len()),
+    ) {
+        return Ok(());
+    }
+    debug!("creating file {}", &path.display());
     fs::write(path, contents).context("writing file contents")?;
     Ok(())
 }
@@ -81,7 +192,10 @@ pub fn create_symlink<S: AsRef<Path>, D: AsRef<Path>>(
     let src = src.as_ref();
     let dst = dst.as_ref();
 
-    println!(
+    if plan("symlink", dst, Some(true), &format!("-> {}", src.display())) {
+        return Ok(());
+    }
+    debug!(
         "creating symlink from {} to {}",
         src.display(),
         dst.display()
@@ -94,12 +208,15 @@ pub fn create_symlink<S: AsRef<Path>, D: AsRef<Path>>(
 
 pub fn change_perms<P: AsRef<Path>>(path: P, owner: u32, group: u32, mode: u32) -> Result<()> {
     let path = path.as_ref();
+    if plan("chmod", path, None, &perms_detail(owner, group, mode)) {
+        return Ok(());
+    }
 
     // symlinks don't have permissions so skip them
-    let attr = fs::symlink_metadata(&path)?;
+    let attr = fs::symlink_metadata(path)?;
     if attr.file_type().is_symlink() {
-        chown(&path, owner, group)?;
-        println!(
+        chown(path, owner, group)?;
+        trace!(
             "{} changed ownership to owner: {} group: {}",
             &path.display(),
             owner,
@@ -108,9 +225,9 @@ pub fn change_perms<P: AsRef<Path>>(path: P, owner: u32, group: u32, mode: u32)
         return Ok(());
     }
 
-    chown(&path, owner, group)?;
-    set_permissions(&path, mode)?;
-    println!(
+    chown(path, owner, group)?;
+    set_permissions(path, mode)?;
+    trace!(
         "set permissions for {} to owner: {} group: {} mode: {:o}",
         &path.display(),
         owner,
@@ -131,9 +248,237 @@ pub fn copy_file<S: AsRef<Path>, D: AsRef<Path>>(
     let src = src.as_ref();
     let dst = dst.as_ref();
 
-    println!("copying {} to {}", src.display(), dst.display());
+    let detail = format!(
+        "from {} {}",
+        src.display(),
+        perms_detail(owner, group, mode)
+    );
+    if plan("copy", dst, Some(true), &detail) {
+        return Ok(());
+    }
+    debug!("copying {} to {}", src.display(), dst.display());
     fs::copy(src, dst).with_context(|| format!("copy {} to {}", src.display(), dst.display()))?;
     change_perms(dst, owner, group, mode)?;
 
     Ok(())
 }
+
+/*
+ * Write contents to dst and set its ownership and mode. The origin of the
+ * contents is only used for logging.
+ */
+pub fn install_file<P: AsRef<Path>>(
+    dst: P,
+    contents: &[u8],
+    origin: &str,
+    owner: u32,
+    group: u32,
+    mode: u32,
+) -> Result<()> {
+    let dst = dst.as_ref();
+
+    let detail = format!("from {} {}", origin, perms_detail(owner, group, mode));
+    if plan("install", dst, Some(true), &detail) {
+        return Ok(());
+    }
+    debug!("installing {} to {}", origin, dst.display());
+    fs::write(dst, contents).with_context(|| format!("failed to write {}", dst.display()))?;
+    change_perms(dst, owner, group, mode)?;
+
+    Ok(())
+}
+
+pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    if plan("unlink", path, Some(false), "") {
+        return Ok(());
+    }
+    fs::remove_file(path).with_context(|| format!("failed to unlink {}", path.display()))?;
+    debug!("unlinked {}", path.display());
+
+    Ok(())
+}
+
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    if plan("rmdir", path, Some(false), "") {
+        return Ok(());
+    }
+    fs::remove_dir_all(path).with_context(|| format!("failed to remove {}", path.display()))?;
+    debug!("removed directory {}", path.display());
+
+    Ok(())
+}
+
+/*
+ * Empty a regular file. Anything else, in particular a symlink that may point
+ * outside of the image root, is left alone.
+ */
+pub fn truncate_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    let meta =
+        fs::symlink_metadata(path).with_context(|| format!("failed to stat {}", path.display()))?;
+    if !meta.file_type().is_file() {
+        bail!("{} is not a regular file", path.display());
+    }
+    if plan("truncate", path, None, &format!("({} bytes)", meta.len())) {
+        return Ok(());
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .with_context(|| format!("failed to truncate {}", path.display()))?;
+    debug!("truncated {}", path.display());
+
+    Ok(())
+}
+
+/*
+ * Whether any directory between the image root and path is a symlink. Paths
+ * found by globbing the image root must not be acted on if they were reached
+ * through a symlink, which could lead outside of it.
+ */
+pub fn has_symlink_parent<R: AsRef<Path>, P: AsRef<Path>>(zroot: R, path: P) -> bool {
+    let zroot = zroot.as_ref();
+    let path = path.as_ref();
+
+    path.ancestors()
+        .skip(1)
+        .take_while(|a| a.starts_with(zroot) && *a != zroot)
+        .any(|a| fs::symlink_metadata(a).map_or(true, |m| m.file_type().is_symlink()))
+}
+
+/*
+ * Read the image's os-release. /etc/os-release is often a symlink to
+ * /usr/lib/os-release, which is resolved within the image root rather than on
+ * the build host, and that is the documented fallback when it is missing.
+ */
+pub fn read_os_release<P: AsRef<Path>>(zroot: P) -> Result<OsRelease> {
+    let zroot = zroot.as_ref();
+    let path = ["etc/os-release", "usr/lib/os-release"]
+        .iter()
+        .find_map(|p| resolve_in_root(zroot, p))
+        .unwrap_or_else(|| zroot.join("usr/lib/os-release"));
+    let release =
+        OsRelease::new_from(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(release)
+}
+
+/*
+ * Resolve a path within the image root, following symlinks as the guest
+ * would: absolute link targets are taken relative to the image root rather
+ * than the host's. Returns None if the path does not exist, or a symlink loop
+ * is found.
+ */
+pub fn resolve_in_root<R: AsRef<Path>, P: AsRef<Path>>(zroot: R, path: P) -> Option<PathBuf> {
+    let zroot = zroot.as_ref();
+
+    let mut pending: Vec<OsString> = vec![];
+    let push = |pending: &mut Vec<OsString>, p: &Path| {
+        let start = pending.len();
+        pending.extend(p.components().map(|c| c.as_os_str().to_os_string()));
+        pending[start..].reverse();
+    };
+    push(&mut pending, path.as_ref());
+
+    let mut resolved = PathBuf::new();
+    let mut hops = 0;
+    while let Some(c) = pending.pop() {
+        if c == "/" || c == "." {
+            continue;
+        }
+        if c == ".." {
+            resolved.pop();
+            continue;
+        }
+
+        let full = zroot.join(&resolved).join(&c);
+        let meta = match fs::symlink_metadata(&full) {
+            Ok(m) => m,
+            Err(_) if planned_exists(&full) == Some(true) => {
+                resolved.push(&c);
+                continue;
+            }
+            Err(_) => return None,
+        };
+        if !meta.file_type().is_symlink() {
+            resolved.push(&c);
+            continue;
+        }
+
+        hops += 1;
+        if hops > 40 {
+            return None;
+        }
+        let target = fs::read_link(&full).ok()?;
+        if target.is_absolute() {
+            resolved = PathBuf::new();
+        }
+        push(&mut pending, &target);
+    }
+
+    let full = zroot.join(resolved);
+    if planned_exists(&full) == Some(false) {
+        return None;
+    }
+    Some(full)
+}
+
+/*
+ * Read a text file within the image root, resolving symlinks as
+ * resolve_in_root does. Returns None if it does not exist or is not a file.
+ */
+pub fn read_in_root(zroot: &Path, path: &str) -> Result<Option<String>> {
+    match resolve_in_root(zroot, path) {
+        Some(p) if p.is_file() => Ok(Some(
+            fs::read_to_string(&p).with_context(|| format!("failed to read /{}", path))?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/*
+ * An empty scratch directory for a unit test, unique to the test process.
+ */
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "smartos-lx-img-builder-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn os_release_symlink() {
+        let zroot = test_dir("os-release-symlink");
+        fs::create_dir_all(zroot.join("etc")).unwrap();
+        fs::create_dir_all(zroot.join("usr/lib")).unwrap();
+        fs::write(zroot.join("usr/lib/os-release"), "ID=imagelinux\n").unwrap();
+        symlink("/usr/lib/os-release", zroot.join("etc/os-release")).unwrap();
+        let id = read_os_release(&zroot).map(|r| r.id);
+
+        fs::remove_file(zroot.join("etc/os-release")).unwrap();
+        let fallback = read_os_release(&zroot).map(|r| r.id);
+
+        fs::remove_file(zroot.join("usr/lib/os-release")).unwrap();
+        let missing = read_os_release(&zroot);
+        fs::remove_dir_all(&zroot).unwrap();
+
+        assert_eq!(id.unwrap(), "imagelinux");
+        assert_eq!(fallback.unwrap(), "imagelinux");
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,435 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2026 MNX Cloud, Inc.
+ */
+
+/*
+ * Package version ordering, as each package manager defines it. dpkg and rpm
+ * are implemented as documented by their upstreams; pacman and xbps use rpm's
+ * algorithm on their own epoch and revision syntax. apk's ordering is
+ * approximated by mapping its pre- and post-release suffixes onto rpm's ~ and
+ * ^ separators.
+ */
+
+use std::cmp::Ordering;
+
+use crate::packages::Manager;
+
+fn dpkg_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+        Some(c) => i32::from(c) + 256,
+    }
+}
+
+/*
+ * dpkg's verrevcmp(): alternating runs of non-digits, compared with ~ before
+ * everything (even the end of the string) and letters before other
+ * characters, and digits, compared numerically.
+ */
+fn dpkg_part(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let ac = dpkg_order(a.get(i).copied());
+            let bc = dpkg_order(b.get(j).copied());
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && a[i].is_ascii_digit() && j < b.len() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/*
+ * Split "epoch:version-release" into its parts. The epoch defaults to 0 and
+ * the release to "".
+ */
+fn split_evr(v: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match v.split_once(':') {
+        Some((e, rest)) if e.bytes().all(|b| b.is_ascii_digit()) => (e.parse().unwrap_or(0), rest),
+        _ => (0, v),
+    };
+    let (version, release) = match rest.rfind('-') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    (epoch, version, release)
+}
+
+pub fn dpkg_compare(a: &str, b: &str) -> Ordering {
+    let (ae, av, ar) = split_evr(a);
+    let (be, bv, br) = split_evr(b);
+    ae.cmp(&be)
+        .then_with(|| dpkg_part(av, bv))
+        .then_with(|| dpkg_part(ar, br))
+}
+
+/*
+ * rpm's rpmvercmp(): runs of digits or letters separated by anything else.
+ * Digits sort after letters and compare numerically, ~ sorts before
+ * everything, and ^ sorts after the end of the string but before anything
+ * else.
+ */
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (mut one, mut two) = (a.as_bytes(), b.as_bytes());
+    let sep = |c: u8| !c.is_ascii_alphanumeric() && c != b'~' && c != b'^';
+
+    loop {
+        while !one.is_empty() && sep(one[0]) {
+            one = &one[1..];
+        }
+        while !two.is_empty() && sep(two[0]) {
+            two = &two[1..];
+        }
+
+        if one.first() == Some(&b'~') || two.first() == Some(&b'~') {
+            if one.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.first() == Some(&b'^') || two.first() == Some(&b'^') {
+            if one.is_empty() {
+                return Ordering::Less;
+            }
+            if two.is_empty() {
+                return Ordering::Greater;
+            }
+            if one[0] != b'^' {
+                return Ordering::Greater;
+            }
+            if two[0] != b'^' {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        let numeric = one[0].is_ascii_digit();
+        let class = |c: &u8| {
+            if numeric {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let n1 = one.iter().take_while(|c| class(c)).count();
+        let n2 = two.iter().take_while(|c| class(c)).count();
+        let (s1, s2) = (&one[..n1], &two[..n2]);
+        one = &one[n1..];
+        two = &two[n2..];
+
+        if s2.is_empty() {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let ord = if numeric {
+            let s1 = &s1[s1.iter().take_while(|c| **c == b'0').count()..];
+            let s2 = &s2[s2.iter().take_while(|c| **c == b'0').count()..];
+            s1.len().cmp(&s2.len()).then_with(|| s1.cmp(s2))
+        } else {
+            s1.cmp(s2)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (false, _) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+/*
+ * rpm and pacman versions, "epoch:version-release".
+ */
+pub fn rpm_compare(a: &str, b: &str) -> Ordering {
+    let (ae, av, ar) = split_evr(a);
+    let (be, bv, br) = split_evr(b);
+    ae.cmp(&be)
+        .then_with(|| rpmvercmp(av, bv))
+        .then_with(|| rpmvercmp(ar, br))
+}
+
+/*
+ * Split a version at the last occurrence of sep followed by a number, the
+ * package revision.
+ */
+fn split_revision<'a>(v: &'a str, sep: &str) -> (&'a str, u64) {
+    match v.rfind(sep) {
+        Some(i) => match v[i + sep.len()..].parse() {
+            Ok(r) => (&v[..i], r),
+            Err(_) => (v, 0),
+        },
+        None => (v, 0),
+    }
+}
+
+/*
+ * apk versions, e.g. "1.2.4_rc1-r2". _alpha, _beta, _pre and _rc sort before
+ * the release, other suffixes such as _p after it.
+ */
+pub fn apk_compare(a: &str, b: &str) -> Ordering {
+    let map = |v: &str| {
+        let mut v = v.to_string();
+        for pre in &["_alpha", "_beta", "_pre", "_rc"] {
+            v = v.replace(pre, &format!("~{}", &pre[1..]));
+        }
+        v.replace('_', "^")
+    };
+    let (av, ar) = split_revision(a, "-r");
+    let (bv, br) = split_revision(b, "-r");
+    rpmvercmp(&map(av), &map(bv)).then_with(|| ar.cmp(&br))
+}
+
+/*
+ * xbps versions, "version_revision".
+ */
+pub fn xbps_compare(a: &str, b: &str) -> Ordering {
+    let (av, ar) = split_revision(a, "_");
+    let (bv, br) = split_revision(b, "_");
+    rpmvercmp(av, bv).then_with(|| ar.cmp(&br))
+}
+
+pub fn compare(manager: Manager, a: &str, b: &str) -> Ordering {
+    match manager {
+        Manager::Dpkg => dpkg_compare(a, b),
+        Manager::Rpm | Manager::Pacman => rpm_compare(a, b),
+        Manager::Apk => apk_compare(a, b),
+        Manager::Xbps => xbps_compare(a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * Each case is checked in both directions.
+     */
+    fn check(cmp: fn(&str, &str) -> Ordering, cases: &[(&str, &str, i32)]) {
+        for (a, b, want) in cases {
+            let want = want.cmp(&0);
+            assert_eq!(cmp(a, b), want, "{} vs {}", a, b);
+            assert_eq!(cmp(b, a), want.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    /*
+     * Checked against dpkg --compare-versions, including the ~ examples of
+     * Debian policy 5.6.12.
+     */
+    #[test]
+    fn dpkg() {
+        check(
+            dpkg_compare,
+            &[
+                ("1.0", "1.0", 0),
+                ("1.0", "1.1", -1),
+                ("0:1.0", "1.0", 0),
+                ("1:1.0", "2.0", 1),
+                ("1:0.1-1", "0.2-1", 1),
+                ("1.0-1", "1.0-2", -1),
+                ("1.0-0", "1.0", 0),
+                ("1.0", "1.0.0", -1),
+                ("2.5-1", "2.10-1", -1),
+                ("0.9.9-1", "0.10-1", -1),
+                ("01", "1", 0),
+                ("1.2-5", "1.2.3-5", -1),
+                ("1.0~rc1", "1.0", -1),
+                ("1.0~~", "1.0~~a", -1),
+                ("1.0~~a", "1.0~", -1),
+                ("1.0~", "1.0", -1),
+                ("1.0", "1.0a", -1),
+                ("1.0a", "1.0+", -1),
+                ("1.0", "1.0+b1", -1),
+                ("1.0.a", "1.0-a", 1),
+                ("1.0~beta1~svn1245", "1.0~beta1", -1),
+                ("1.0-test6", "1.0-rc1", 1),
+                ("1.0-1ubuntu1", "1.0-1", 1),
+                ("2.7.4+reloaded2-13ubuntu1", "2.7.4+reloaded2-13+deb9u1", -1),
+                ("2.36-9+deb12u4", "2.36-9+deb12u10", -1),
+            ],
+        );
+    }
+
+    /*
+     * From rpm's tests/rpmvercmp.at.
+     */
+    #[test]
+    fn rpm() {
+        check(
+            rpmvercmp,
+            &[
+                ("1.0", "1.0", 0),
+                ("1.0", "2.0", -1),
+                ("2.0.1", "2.0.1", 0),
+                ("2.0", "2.0.1", -1),
+                ("2.0.1a", "2.0.1a", 0),
+                ("2.0.1a", "2.0.1", 1),
+                ("5.5p1", "5.5p1", 0),
+                ("5.5p1", "5.5p2", -1),
+                ("5.5p10", "5.5p10", 0),
+                ("5.5p1", "5.5p10", -1),
+                ("10xyz", "10.1xyz", -1),
+                ("xyz10", "xyz10", 0),
+                ("xyz10", "xyz10.1", -1),
+                ("xyz.4", "xyz.4", 0),
+                ("xyz.4", "8", -1),
+                ("xyz.4", "2", -1),
+                ("5.5p2", "5.6p1", -1),
+                ("5.6p1", "6.5p1", -1),
+                ("6.0.rc1", "6.0", 1),
+                ("10b2", "10a1", 1),
+                ("10a2", "10b2", -1),
+                ("1.0aa", "1.0aa", 0),
+                ("1.0a", "1.0aa", -1),
+                ("10.0001", "10.0001", 0),
+                ("10.0001", "10.1", 0),
+                ("10.0001", "10.0039", -1),
+                ("4.999.9", "5.0", -1),
+                ("20101121", "20101121", 0),
+                ("20101121", "20101122", -1),
+                ("2_0", "2_0", 0),
+                ("2.0", "2_0", 0),
+                ("a", "a", 0),
+                ("a+", "a+", 0),
+                ("a+", "a_", 0),
+                ("+a", "+a", 0),
+                ("+a", "_a", 0),
+                ("+_", "+_", 0),
+                ("_+", "+_", 0),
+                ("_+", "_", 0),
+                ("+", "_", 0),
+                ("1.0~rc1", "1.0~rc1", 0),
+                ("1.0~rc1", "1.0", -1),
+                ("1.0~rc1", "1.0~rc2", -1),
+                ("1.0~rc1~git123", "1.0~rc1~git123", 0),
+                ("1.0~rc1~git123", "1.0~rc1", -1),
+                ("1.0^", "1.0^", 0),
+                ("1.0^", "1.0", 1),
+                ("1.0^git1", "1.0^git1", 0),
+                ("1.0^git1", "1.0", 1),
+                ("1.0^git1", "1.0^git2", -1),
+                ("1.0^git1", "1.01", -1),
+                ("1.0^20160101", "1.0^20160101", 0),
+                ("1.0^20160101", "1.0.1", -1),
+                ("1.0^20160101^git1", "1.0^20160101^git1", 0),
+                ("1.0^20160102", "1.0^20160101^git1", 1),
+                ("1.0~rc1^git1", "1.0~rc1^git1", 0),
+                ("1.0~rc1^git1", "1.0~rc1", 1),
+                ("1.0^git1~pre", "1.0^git1~pre", 0),
+                ("1.0^git1", "1.0^git1~pre", 1),
+            ],
+        );
+        check(
+            rpm_compare,
+            &[
+                ("1:1.0-1", "2.0-1", 1),
+                ("0:1.0-1", "1.0-1", 0),
+                ("1.0-1.el9", "1.0-1.el9_2", -1),
+                ("5.14.0-427.el9", "5.14.0-70.el9", 1),
+            ],
+        );
+    }
+
+    /*
+     * The suffix ordering of apk-tools' version tests.
+     */
+    #[test]
+    fn apk() {
+        check(
+            apk_compare,
+            &[
+                ("1.0", "1.0", 0),
+                ("1.0", "1.1", -1),
+                ("2.34", "0.1.0_alpha", 1),
+                ("0.1.0_alpha", "0.1.3_alpha", -1),
+                ("1.0_alpha", "1.0_beta", -1),
+                ("1.0_beta", "1.0_pre", -1),
+                ("1.0_pre", "1.0_rc", -1),
+                ("1.0_rc1", "1.0_rc2", -1),
+                ("1.0_rc2", "1.0", -1),
+                ("1.0", "1.0_p1", -1),
+                ("1.0_p1", "1.0_p2", -1),
+                ("1.0", "1.0a", -1),
+                ("1.0", "1.0-r1", -1),
+                ("1.0-r1", "1.0-r10", -1),
+                ("1.2.4_rc1-r2", "1.2.4-r0", -1),
+                ("1.36.1-r5", "1.36.1-r5", 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn xbps() {
+        check(
+            xbps_compare,
+            &[
+                ("1.0_1", "1.0_1", 0),
+                ("1.0_1", "1.0_2", -1),
+                ("1.0_2", "1.1_1", -1),
+                ("1.0_10", "1.0_9", 1),
+                ("2.38_1", "2.38.1_1", -1),
+            ],
+        );
+    }
+}